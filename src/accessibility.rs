@@ -0,0 +1,130 @@
+//! Lints for toasts that are hard to use with a screen reader, in high contrast or for users who
+//! do not tell colors apart. Windows shows these toasts as written, so [`Toast::validate`] does
+//! not report them; [`Toast::lint_accessibility`] does, each with a suggested fix.
+
+use crate::notification::*;
+use crate::validate::Diagnostic;
+
+/// Alternative texts that say nothing about the image.
+const PLACEHOLDER_ALTS: &[&str] = &["image", "img", "picture", "photo", "icon", "logo", "alt", "placeholder", "untitled", "todo"];
+
+impl Toast {
+    /// Checks the toast for:
+    ///
+    /// - images without alternative text, or with a placeholder such as `image` or the file name;
+    /// - buttons with an icon and no tooltip, which screen readers announce by the icon alone;
+    /// - buttons without content whose style is all that tells what they do;
+    /// - subtle text styles in urgent, alarm and call toasts, or next to a critical button;
+    /// - groups whose columns hold several lines each, which are read column by column.
+    pub fn lint_accessibility(&self) -> Vec<Diagnostic> {
+        let mut lints = Vec::new();
+        let critical = matches!(self.scenario, Scenario::Urgent | Scenario::Alarm | Scenario::IncomingCall)
+            || self.actions.iter().any(|elem| matches!(elem, ActionsElem::Action_(action) if action.hint_button_style.as_deref() == Some("Critical")));
+        for (i, binding) in self.binding_elems.iter().enumerate() {
+            let path = format!("binding_elems[{}]", i);
+            if let Some(image) = &binding.icon {
+                lint_image(image, format!("{}.icon", path), &mut lints);
+            }
+            if let Some(image) = &binding.hero {
+                lint_image(image, format!("{}.hero", path), &mut lints);
+            }
+            for (j, elem) in binding.elems.iter().enumerate() {
+                let path = format!("{}.elems[{}]", path, j);
+                let subgroups = match elem {
+                    BindingInnerElement::Text(_) => continue,
+                    BindingInnerElement::Image(image) => {
+                        lint_image(image, path, &mut lints);
+                        continue;
+                    }
+                    BindingInnerElement::Group(subgroups) => subgroups,
+                };
+                if subgroups.len() > 1 && subgroups.iter().any(|subgroup| subgroup.len() > 1) {
+                    lints.push(Diagnostic::warning(&path, "the columns of the group hold several lines each, and are read one column after the other")
+                        .suggest("put each row in a group of its own, with a subgroup per column"));
+                }
+                for (k, subgroup) in subgroups.iter().enumerate() {
+                    for (l, elem) in subgroup.iter().enumerate() {
+                        let path = format!("{}[{}][{}]", path, k, l);
+                        match elem {
+                            SubgroupElement::Text_(text) => {
+                                if let Some(style) = unsubtle(&text.hint_style).filter(|_| critical) {
+                                    lints.push(Diagnostic::warning(path, format!("{} text is hard to read in a critical toast", text.hint_style.attr().unwrap_or_default()))
+                                        .suggest(format!("use {} instead", style.attr().unwrap_or_default())));
+                                }
+                            }
+                            SubgroupElement::Image_(image) => lint_image(image, path, &mut lints),
+                        }
+                    }
+                }
+            }
+        }
+        for (i, elem) in self.actions.iter().enumerate() {
+            let ActionsElem::Action_(action) = elem else { continue };
+            let path = format!("actions[{}]", i);
+            if action.image_uri.is_some() && action.hint_tool_tip.is_none() {
+                lints.push(Diagnostic::warning(&path, "the button has an icon and no tooltip")
+                    .suggest("set hint_tool_tip to what the button does"));
+            }
+            if action.hint_button_style.is_some() && action.content.trim().is_empty() {
+                lints.push(Diagnostic::warning(&path, "only the color of the button tells what it does")
+                    .suggest("set content to what the button does, such as \"Delete\""));
+            }
+        }
+        lints
+    }
+}
+
+fn lint_image(image: &Image, path: String, lints: &mut Vec<Diagnostic>) {
+    let alt = image.alt.trim();
+    let file_name = image.src.rsplit(['/', '\\']).next().unwrap_or_default();
+    if alt.is_empty() {
+        lints.push(Diagnostic::warning(path, "the image has no alternative text").suggest("set alt to what the image shows"));
+    } else if PLACEHOLDER_ALTS.iter().any(|placeholder| alt.eq_ignore_ascii_case(placeholder)) || alt == file_name || alt == image.src {
+        lints.push(Diagnostic::warning(path, format!("the alternative text {:?} does not say what the image shows", alt))
+            .suggest("set alt to what the image shows"));
+    }
+}
+
+/// The style of the same size without the subtle opacity.
+fn unsubtle(style: &TextStyle) -> Option<TextStyle> {
+    Some(match style {
+        TextStyle::CaptionSubtle => TextStyle::Caption,
+        TextStyle::BodySubtle => TextStyle::Body,
+        TextStyle::BaseSubtle => TextStyle::Base,
+        TextStyle::SubtitleSubtle => TextStyle::Subtitle,
+        TextStyle::TitleSubtle => TextStyle::Title,
+        TextStyle::SubheaderSubtle => TextStyle::Subheader,
+        TextStyle::HeaderSubtle => TextStyle::Header,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_lint_accessibility() {
+        let mut toast = sample_toast();
+        assert_eq!(toast.lint_accessibility().iter().map(|lint| lint.path.as_str()).collect::<Vec<_>>(), ["binding_elems[0].icon"]);
+
+        toast.binding_elems[0].icon.as_mut().unwrap().alt = "The CI logo".to_string();
+        toast.scenario = Scenario::Urgent;
+        toast.binding_elems[0].elems.push(BindingInnerElement::Group(vec![
+            vec![SubgroupElement::Text_(InnerText { text: "Passed".to_string(), ..InnerText::default() }), SubgroupElement::Text_(InnerText { text: "Failed".to_string(), ..InnerText::default() })],
+            vec![SubgroupElement::Text_(InnerText { text: "42".to_string(), ..InnerText::default() }), SubgroupElement::Text_(InnerText { text: "3".to_string(), ..InnerText::default() })],
+        ]));
+        toast.actions.push(ActionsElem::Action_(Action {
+            image_uri: Some("file:///C:/delete.png".to_string()),
+            hint_button_style: Some("Critical".to_string()),
+            ..Action::default()
+        }));
+        let lints = toast.lint_accessibility();
+        let paths: Vec<&str> = lints.iter().map(|lint| lint.path.as_str()).collect();
+        assert_eq!(paths, ["binding_elems[0].elems[0][0][0]", "binding_elems[0].elems[1]", "actions[2]", "actions[2]"]);
+        assert_eq!(lints[0].to_string(), "warning: binding_elems[0].elems[0][0][0]: baseSubtle text is hard to read in a critical toast\n  help: use base instead");
+        assert!(lints.iter().all(|lint| lint.suggestion.is_some()));
+        assert!(toast.validate().iter().all(|diagnostic| diagnostic.suggestion.is_none()));
+    }
+}
@@ -0,0 +1,363 @@
+//! Adaptive Cards turned into toasts, so a card made for Teams can also be shown on Windows.
+//!
+//! The first `TextBlock` of the card becomes the title and the rest of the body becomes the
+//! content of the binding: text blocks as text, sized and weighted ones as styled text in a
+//! group, a `ColumnSet` as a group with a subgroup per column, images as inline images and a
+//! `FactSet` as a group per fact. `Input.Text` and `Input.ChoiceSet` become inputs, and
+//! `Action.Submit` and `Action.OpenUrl` buttons. Whatever does not fit a toast is reported with a
+//! warning at its path in the card, such as `body[2].columns[0]`.
+
+use serde_json::Value;
+
+use crate::notification::*;
+use crate::validate::Diagnostic;
+
+/// The toast a card converts to, and the warnings for what it could not keep.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Converted {
+    pub toast: Toast,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Whether the JSON is an Adaptive Card.
+pub fn is_card(json: &Value) -> bool {
+    json.get("type").and_then(Value::as_str) == Some("AdaptiveCard")
+}
+
+/// Converts the card. The toast has no app id.
+pub fn convert(card: &Value) -> Result<Converted, String> {
+    if !is_card(card) {
+        return Err("the JSON is not an Adaptive Card, its type is not \"AdaptiveCard\"".to_string());
+    }
+    let mut converter = Converter::default();
+    for key in ["backgroundImage", "selectAction", "refresh"] {
+        if card.get(key).is_some() {
+            converter.warn(key, format!("{} is not supported and is left out", key));
+        }
+    }
+    let mut body = array(card, "body").iter().enumerate().peekable();
+    if let Some((_, first)) = body.peek() {
+        if kind(first) == "TextBlock" && visible(first) {
+            converter.binding.title = (str_field(first, "text").to_string(), first.get("maxLines").and_then(Value::as_u64).map(|lines| lines as u32));
+            body.next();
+        }
+    }
+    if converter.binding.title.0.is_empty() {
+        converter.warn("body", "the card does not start with a TextBlock, so the toast has no title");
+    }
+    for (i, elem) in body {
+        converter.element(elem, &format!("body[{}]", i));
+    }
+    for (i, action) in array(card, "actions").iter().enumerate() {
+        converter.action(action, &format!("actions[{}]", i));
+    }
+    let Converter { binding, actions, use_btn_style, warnings } = converter;
+    Ok(Converted { toast: Toast { binding_elems: vec![binding], actions, use_btn_style, ..Toast::default() }, warnings })
+}
+
+#[derive(Default)]
+struct Converter {
+    binding: BindingElem,
+    actions: Vec<ActionsElem>,
+    use_btn_style: bool,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Converter {
+    fn element(&mut self, elem: &Value, path: &str) {
+        if !visible(elem) {
+            return;
+        }
+        match kind(elem) {
+            "TextBlock" => {
+                let text = self.text_block(elem, path);
+                self.binding.elems.push(if is_plain(&text) {
+                    BindingInnerElement::Text(text.text)
+                } else {
+                    BindingInnerElement::Group(vec![vec![SubgroupElement::Text_(text)]])
+                });
+            }
+            "RichTextBlock" => {
+                self.warn(path, "the formatting of a RichTextBlock is left out");
+                let text: String = array(elem, "inlines").iter()
+                    .map(|inline| inline.as_str().unwrap_or_else(|| str_field(inline, "text")))
+                    .collect();
+                self.binding.elems.push(BindingInnerElement::Text(text));
+            }
+            "Image" => {
+                let image = self.image(elem, path);
+                self.binding.elems.push(BindingInnerElement::Image(image));
+            }
+            "ImageSet" => {
+                self.warn(path, "the images of an ImageSet are shown one below the other");
+                for (i, image) in array(elem, "images").iter().enumerate() {
+                    let image = self.image(image, &format!("{}.images[{}]", path, i));
+                    self.binding.elems.push(BindingInnerElement::Image(image));
+                }
+            }
+            "ColumnSet" => {
+                let columns = array(elem, "columns").iter().enumerate()
+                    .filter(|(_, column)| visible(column))
+                    .map(|(i, column)| self.column(column, &format!("{}.columns[{}]", path, i)))
+                    .collect();
+                self.binding.elems.push(BindingInnerElement::Group(columns));
+            }
+            "FactSet" => {
+                for fact in array(elem, "facts") {
+                    let cell = |text: &str, hint_style| vec![SubgroupElement::Text_(InnerText { text: text.to_string(), hint_style, ..InnerText::default() })];
+                    self.binding.elems.push(BindingInnerElement::Group(vec![
+                        cell(str_field(fact, "title"), TextStyle::Base),
+                        cell(str_field(fact, "value"), TextStyle::Default),
+                    ]));
+                }
+            }
+            "Container" => {
+                for key in ["style", "selectAction", "backgroundImage"] {
+                    if elem.get(key).is_some() {
+                        self.warn(path, format!("the {} of a Container is left out", key));
+                    }
+                }
+                for (i, item) in array(elem, "items").iter().enumerate() {
+                    self.element(item, &format!("{}.items[{}]", path, i));
+                }
+            }
+            "ActionSet" => {
+                for (i, action) in array(elem, "actions").iter().enumerate() {
+                    self.action(action, &format!("{}.actions[{}]", path, i));
+                }
+            }
+            "Input.Text" | "Input.Number" | "Input.Date" | "Input.Time" => {
+                if kind(elem) != "Input.Text" {
+                    self.warn(path, format!("an {} is shown as a text input", kind(elem)));
+                }
+                let value = elem.get("value").map(|value| value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string()));
+                self.input(elem, InputType::Text(value));
+            }
+            "Input.ChoiceSet" => {
+                if elem.get("isMultiSelect").and_then(Value::as_bool) == Some(true) {
+                    self.warn(path, "only one choice can be picked in a toast");
+                }
+                if str_field(elem, "style") == "expanded" {
+                    self.warn(path, "the choices are shown as a drop-down list");
+                }
+                let choices = array(elem, "choices").iter()
+                    .map(|choice| (str_field(choice, "value").to_string(), str_field(choice, "title").to_string()))
+                    .collect();
+                let value = elem.get("value").and_then(Value::as_str).map(str::to_string);
+                self.input(elem, InputType::Selection(value, choices));
+            }
+            other => match elem.get("fallback") {
+                Some(Value::String(_)) | None => self.warn(path, format!("{} is not supported and is left out", kind_name(other))),
+                Some(fallback) => self.element(fallback, &format!("{}.fallback", path)),
+            },
+        }
+    }
+
+    fn text_block(&mut self, elem: &Value, path: &str) -> InnerText {
+        let subtle = elem.get("isSubtle").and_then(Value::as_bool) == Some(true);
+        let hint_style = match (str_field(elem, "size"), str_field(elem, "weight"), subtle) {
+            ("extraLarge", _, false) => TextStyle::Subheader,
+            ("extraLarge", _, true) => TextStyle::SubheaderSubtle,
+            ("large", _, false) => TextStyle::Title,
+            ("large", _, true) => TextStyle::TitleSubtle,
+            ("medium", _, false) => TextStyle::Subtitle,
+            ("medium", _, true) => TextStyle::SubtitleSubtle,
+            ("small", _, false) => TextStyle::Caption,
+            ("small", _, true) => TextStyle::CaptionSubtle,
+            (_, "bolder", false) => TextStyle::Base,
+            (_, "bolder", true) => TextStyle::BaseSubtle,
+            (_, _, true) => TextStyle::BodySubtle,
+            _ => TextStyle::Default,
+        };
+        if !matches!(str_field(elem, "color"), "" | "default") {
+            self.warn(path, "text colors are left out");
+        }
+        if str_field(elem, "weight") == "lighter" {
+            self.warn(path, "lighter text is shown in the normal weight");
+        }
+        InnerText {
+            text: str_field(elem, "text").to_string(),
+            hint_style,
+            hint_warp: elem.get("wrap").and_then(Value::as_bool),
+            hint_max_lines: elem.get("maxLines").and_then(Value::as_u64).map(|lines| lines as u32),
+            hint_min_lines: None,
+            hint_align: match str_field(elem, "horizontalAlignment") {
+                "left" => TextAlign::Left,
+                "center" => TextAlign::Center,
+                "right" => TextAlign::Right,
+                _ => TextAlign::Default,
+            },
+        }
+    }
+
+    fn image(&mut self, elem: &Value, path: &str) -> Image {
+        if elem.get("selectAction").is_some() {
+            self.warn(path, "images cannot be clicked in a toast");
+        }
+        Image {
+            src: str_field(elem, "url").to_string(),
+            alt: str_field(elem, "altText").to_string(),
+            hint_crop: if str_field(elem, "style") == "person" { HintCrop::Circle } else { HintCrop::Default },
+            hint_align: match str_field(elem, "horizontalAlignment") {
+                "left" => ImageAlign::Left,
+                "center" => ImageAlign::Center,
+                "right" => ImageAlign::Right,
+                _ => ImageAlign::Default,
+            },
+        }
+    }
+
+    fn column(&mut self, column: &Value, path: &str) -> Vec<SubgroupElement> {
+        if column.get("width").is_some_and(|width| width != "stretch") {
+            self.warn(path, "the columns of a toast share the width equally");
+        }
+        let mut elems = Vec::new();
+        for (i, item) in array(column, "items").iter().enumerate().filter(|(_, item)| visible(item)) {
+            let path = format!("{}.items[{}]", path, i);
+            match kind(item) {
+                "TextBlock" => elems.push(SubgroupElement::Text_(self.text_block(item, &path))),
+                "Image" => elems.push(SubgroupElement::Image_(self.image(item, &path))),
+                other => self.warn(&path, format!("a column can only hold text and images, so the {} is left out", kind_name(other))),
+            }
+        }
+        elems
+    }
+
+    fn input(&mut self, elem: &Value, type_: InputType) {
+        let label = elem.get("label").and_then(Value::as_str);
+        let placeholder = elem.get("placeholder").and_then(Value::as_str);
+        self.actions.push(ActionsElem::Input_(Input {
+            id: str_field(elem, "id").to_string(),
+            type_,
+            place_holder_content: placeholder.map(str::to_string),
+            title: label.map(str::to_string),
+        }));
+    }
+
+    fn action(&mut self, action: &Value, path: &str) {
+        let (arguments, activation_type) = match kind(action) {
+            "Action.Submit" | "Action.Execute" => {
+                if kind(action) == "Action.Execute" {
+                    self.warn(path, "an Action.Execute is sent like an Action.Submit, without its verb");
+                }
+                let arguments = match action.get("data") {
+                    None => String::new(),
+                    Some(Value::String(data)) => data.clone(),
+                    Some(data) => data.to_string(),
+                };
+                (arguments, ActivationType::Background)
+            }
+            "Action.OpenUrl" => (str_field(action, "url").to_string(), ActivationType::Protocol),
+            other => {
+                match action.get("fallback") {
+                    Some(fallback) if fallback.is_object() => self.action(fallback, &format!("{}.fallback", path)),
+                    _ => self.warn(path, format!("{} is not supported and is left out", kind_name(other))),
+                }
+                return;
+            }
+        };
+        let hint_button_style = match str_field(action, "style") {
+            "positive" => Some("Success".to_string()),
+            "destructive" => Some("Critical".to_string()),
+            _ => None,
+        };
+        self.use_btn_style |= hint_button_style.is_some();
+        self.actions.push(ActionsElem::Action_(Action {
+            content: str_field(action, "title").to_string(),
+            arguments,
+            activation_type,
+            image_uri: action.get("iconUrl").and_then(Value::as_str).map(str::to_string),
+            hint_button_style,
+            hint_tool_tip: action.get("tooltip").and_then(Value::as_str).map(str::to_string),
+            ..Action::default()
+        }));
+    }
+
+    fn warn(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.warnings.push(Diagnostic::warning(path, message));
+    }
+}
+
+/// Whether the text needs none of the hints only text in a group has.
+fn is_plain(text: &InnerText) -> bool {
+    text.hint_style == TextStyle::Default && text.hint_align == TextAlign::Default && text.hint_max_lines.is_none()
+}
+
+fn kind(elem: &Value) -> &str {
+    str_field(elem, "type")
+}
+
+fn kind_name(kind: &str) -> String {
+    if kind.is_empty() { "an element without a type".to_string() } else { kind.to_string() }
+}
+
+fn visible(elem: &Value) -> bool {
+    elem.get("isVisible").and_then(Value::as_bool) != Some(false)
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value.get(key).and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_adaptive_card() {
+        let card = json!({
+            "type": "AdaptiveCard",
+            "version": "1.5",
+            "body": [
+                { "type": "TextBlock", "text": "Deploy finished", "size": "large" },
+                { "type": "TextBlock", "text": "3 services updated", "color": "good" },
+                { "type": "ColumnSet", "columns": [
+                    { "type": "Column", "items": [{ "type": "Image", "url": "https://ci.example.com/ann.png", "style": "person" }] },
+                    { "type": "Column", "width": "auto", "items": [{ "type": "TextBlock", "text": "Ann", "weight": "bolder" }] },
+                ] },
+                { "type": "Input.Text", "id": "comment", "placeholder": "Comment", "label": "Notes" },
+                { "type": "Input.ChoiceSet", "id": "env", "value": "prod", "isMultiSelect": true,
+                  "choices": [{ "title": "Production", "value": "prod" }, { "title": "Staging", "value": "stage" }] },
+                { "type": "Media", "sources": [] },
+            ],
+            "actions": [
+                { "type": "Action.Submit", "title": "Approve", "style": "positive", "data": { "approve": true } },
+                { "type": "Action.OpenUrl", "title": "Open", "url": "https://ci.example.com/42" },
+                { "type": "Action.ShowCard", "title": "More" },
+            ],
+        });
+        let converted = convert(&card).unwrap();
+        let toast = converted.toast;
+        let binding = &toast.binding_elems[0];
+        assert_eq!(binding.title.0, "Deploy finished");
+        assert_eq!(binding.elems[0], BindingInnerElement::Text("3 services updated".to_string()));
+        let BindingInnerElement::Group(columns) = &binding.elems[1] else { panic!("expected a group") };
+        assert!(matches!(&columns[0][0], SubgroupElement::Image_(image) if image.hint_crop == HintCrop::Circle));
+        assert!(matches!(&columns[1][0], SubgroupElement::Text_(text) if text.hint_style == TextStyle::Base));
+        assert_eq!(toast.actions[0], ActionsElem::Input_(Input {
+            id: "comment".to_string(),
+            type_: InputType::Text(None),
+            place_holder_content: Some("Comment".to_string()),
+            title: Some("Notes".to_string()),
+        }));
+        let ActionsElem::Input_(Input { type_: InputType::Selection(Some(default), choices), .. }) = &toast.actions[1] else { panic!("expected a selection") };
+        assert_eq!((default.as_str(), choices.len()), ("prod", 2));
+        let ActionsElem::Action_(approve) = &toast.actions[2] else { panic!("expected an action") };
+        assert_eq!((approve.arguments.as_str(), &approve.activation_type), (r#"{"approve":true}"#, &ActivationType::Background));
+        assert_eq!(approve.hint_button_style.as_deref(), Some("Success"));
+        assert!(toast.use_btn_style);
+        let ActionsElem::Action_(open) = &toast.actions[3] else { panic!("expected an action") };
+        assert_eq!((open.arguments.as_str(), &open.activation_type), ("https://ci.example.com/42", &ActivationType::Protocol));
+        assert_eq!(toast.actions.len(), 4);
+
+        let warnings: Vec<String> = converted.warnings.iter().map(|warning| warning.path.clone()).collect();
+        assert_eq!(warnings, ["body[1]", "body[2].columns[1]", "body[4]", "body[5]", "actions[2]"]);
+        assert!(convert(&json!({ "type": "MessageCard" })).is_err());
+    }
+}
@@ -0,0 +1,845 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use jiff::civil::Time;
+use win_toast::adaptive_card;
+use win_toast::capability::OsBuild;
+use win_toast::channel::{Channel, ChannelNotifier, ChannelPreference, ChannelRegistry, PreferenceStore, Sent, Silence};
+use win_toast::daemon::{self, Daemon};
+use win_toast::digest::{DigestConfig, DigestNotifier, DigestStore};
+use win_toast::escalation::{EscalationPolicy, EscalationStep, Escalator, WebhookHook};
+use win_toast::history::{HistoryNotifier, HistoryQuery, HistoryStore};
+use win_toast::i18n::Localizer;
+use win_toast::notification::*;
+use win_toast::notifier::{platform_notifier, DismissReason, Notifier, ToastEvent};
+use win_toast::payload::MAX_PAYLOAD_BYTES;
+use win_toast::preview::Theme;
+use win_toast::queue::{QueueConfig, SendQueue};
+use win_toast::quiet::{QuietHours, QuietNotifier, QuietPolicy, Weekday};
+use win_toast::sanitize::{self, sanitize_xml, SanitizePolicy};
+use win_toast::schedule::ScheduleStore;
+use win_toast::template::TemplateEngine;
+use win_toast::validate::{validate_xml, Diagnostic};
+
+mod run;
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+/// Build, check, preview and send Windows toast notifications.
+#[derive(Parser)]
+#[command(name = "win-toast", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// What to do with characters XML does not allow: strip, replace or escape.
+    #[arg(long, global = true, default_value = "strip")]
+    sanitize: SanitizePolicy,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the XML payload of a toast.
+    Build(ToastArgs),
+    /// Check an XML payload or a JSON/TOML toast against the toast schema.
+    Validate {
+        file: PathBuf,
+        /// Also check the toast for problems of accessibility, such as images without alternative
+        /// text, and suggest fixes.
+        #[arg(long)]
+        accessibility: bool,
+    },
+    /// Convert a toast from the legacy Windows 8 templates to ToastGeneric and print its XML.
+    Migrate {
+        file: PathBuf,
+        /// Convert to the legacy template that fits the toast best instead, for older readers.
+        #[arg(long)]
+        legacy: bool,
+    },
+    /// Render a preview of a toast in the terminal or as an HTML page.
+    Preview {
+        #[command(flatten)]
+        toast: ToastArgs,
+        /// Render an HTML page instead of a terminal preview.
+        #[arg(long)]
+        html: bool,
+        #[arg(long, value_enum, default_value_t = ThemeArg::Light)]
+        theme: ThemeArg,
+        /// Write the preview to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Show a toast. Only available on Windows.
+    Send {
+        #[command(flatten)]
+        toast: ToastArgs,
+        #[command(flatten)]
+        preferences: PreferenceArgs,
+    },
+    /// Schedule a toast to be delivered later.
+    Schedule {
+        #[command(flatten)]
+        toast: ToastArgs,
+        /// When to deliver the toast, as an RFC 3339 time such as 2024-05-01T09:30:00Z.
+        #[arg(long, conflicts_with = "after", required_unless_present = "after")]
+        at: Option<String>,
+        /// How long from now to deliver the toast, such as "10m" or "1h 30m".
+        #[arg(long = "in", id = "after")]
+        after: Option<String>,
+        #[command(flatten)]
+        store: StoreArgs,
+    },
+    /// List the scheduled toasts.
+    List(StoreArgs),
+    /// Cancel a scheduled toast.
+    Cancel {
+        id: String,
+        #[command(flatten)]
+        store: StoreArgs,
+    },
+    /// Run a command and send a toast when it exits.
+    Run(run::RunArgs),
+    /// Show toasts sent by other processes over a local socket, or a named pipe on Windows.
+    Daemon {
+        /// The socket or named pipe to listen on.
+        #[arg(long)]
+        address: Option<PathBuf>,
+        #[command(flatten)]
+        delivery: DeliveryArgs,
+    },
+    /// Show toasts posted to an HTTP server, either as JSON toasts or as webhook payloads.
+    #[cfg(feature = "http")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8787")]
+        listen: String,
+        /// The bearer token requests must carry, and the secret GitHub webhooks are signed with.
+        /// Defaults to $WIN_TOAST_TOKEN.
+        #[arg(long)]
+        token: Option<String>,
+        #[command(flatten)]
+        delivery: DeliveryArgs,
+    },
+    /// List the toasts the daemon and the server have sent, newest last.
+    History {
+        /// Only toasts sent within this long, such as "1h".
+        #[arg(long)]
+        since: Option<String>,
+        /// Only toasts with this scenario: alarm, reminder, incomingCall or urgent.
+        #[arg(long, value_parser = parse_attr::<Scenario>)]
+        scenario: Option<Scenario>,
+        /// Only toasts the user neither clicked nor closed.
+        #[arg(long)]
+        unacknowledged: bool,
+        #[command(flatten)]
+        history: HistoryArgs,
+    },
+    /// Mute, snooze or set quiet hours for a channel.
+    Channel {
+        #[command(subcommand)]
+        command: ChannelCommand,
+        #[command(flatten)]
+        preferences: PreferenceArgs,
+    },
+    /// Show a toast from the history again. Only available on Windows.
+    Reshow {
+        id: String,
+        #[command(flatten)]
+        history: HistoryArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum ChannelCommand {
+    /// List the channels with preferences.
+    List,
+    Mute {
+        channel: String,
+    },
+    Unmute {
+        channel: String,
+    },
+    /// Silence a channel for a while.
+    Snooze {
+        channel: String,
+        /// How long, such as "1h" or "30m".
+        #[arg(long = "for")]
+        duration: String,
+    },
+    /// Add quiet hours to a channel.
+    Quiet {
+        channel: String,
+        /// When the quiet hours start, such as 22:00.
+        #[arg(long, value_parser = parse_time)]
+        from: Time,
+        /// When the quiet hours end, such as 07:00. Before --from means the next day.
+        #[arg(long, value_parser = parse_time)]
+        to: Time,
+        /// A day the quiet hours start on, such as saturday. May be repeated; every day if not given.
+        #[arg(long, value_parser = parse_weekday)]
+        on: Vec<Weekday>,
+        /// An IANA time zone such as Europe/Berlin. Defaults to the system time zone.
+        #[arg(long)]
+        time_zone: Option<String>,
+    },
+    /// Forget the preferences of a channel.
+    Reset {
+        channel: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ThemeArg {
+    Light,
+    Dark,
+    System,
+}
+
+#[derive(Args)]
+struct StoreArgs {
+    /// The file scheduled toasts are kept in.
+    #[arg(long)]
+    store: Option<PathBuf>,
+}
+
+impl StoreArgs {
+    fn open(&self) -> CliResult<ScheduleStore> {
+        let path = match &self.store {
+            Some(path) => path.clone(),
+            None => data_dir().ok_or("cannot find a data directory, pass --store")?.join("schedule.json"),
+        };
+        Ok(ScheduleStore::open(path))
+    }
+}
+
+#[derive(Args)]
+struct HistoryArgs {
+    /// The file sent toasts are recorded in.
+    #[arg(long)]
+    history: Option<PathBuf>,
+}
+
+impl DeliveryArgs {
+    /// The queued notifier, escalating if asked to and recording what it is given into the
+    /// history, behind the quiet hours, the digest, the channels and the preferences of the user.
+    fn notifier(&self) -> CliResult<Arc<ChannelNotifier>> {
+        let mut notifier: Arc<dyn Notifier> = queued_notifier()?;
+        if let Some(after) = &self.escalate_after {
+            let after = humantime::parse_duration(after)?;
+            let mut steps = vec![EscalationStep::Resend { after, audio: Some((true, false, SoundSrc::Alarm)), scenario: Some(Scenario::Alarm) }];
+            if let Some(url) = &self.escalation_hook {
+                steps.push(EscalationStep::Hook { after, hook: Arc::new(WebhookHook::new(url)) });
+            }
+            let escalator = Arc::new(Escalator::new(notifier, EscalationPolicy { steps, ..EscalationPolicy::default() }));
+            escalator.spawn_pump(std::time::Duration::from_secs(1));
+            notifier = escalator;
+        }
+        notifier = Arc::new(HistoryNotifier::new(notifier, Arc::new(self.history.open()?)));
+        if let Some(path) = &self.quiet_policy {
+            let content = fs::read_to_string(path)?;
+            let policy: QuietPolicy = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")) {
+                toml::from_str(&content)?
+            } else {
+                serde_json::from_str(&content)?
+            };
+            for rule in &policy.rules {
+                rule.hours.zone()?;
+            }
+            let quiet = Arc::new(QuietNotifier::new(notifier, policy));
+            quiet.spawn_pump(std::time::Duration::from_secs(60));
+            notifier = quiet;
+        }
+        if !self.digest.is_empty() {
+            let config = DigestConfig { interval: humantime::parse_duration(&self.digest_every)?, channels: self.digest.clone() };
+            let store = DigestStore::open(data_dir().ok_or("cannot find a data directory for the digest")?.join("digest.json"));
+            let digest = Arc::new(DigestNotifier::new(notifier, config, store)?);
+            digest.spawn_pump(std::time::Duration::from_secs(10));
+            notifier = digest;
+        }
+        let registry = match &self.channels {
+            Some(path) => ChannelRegistry::from_channels(read_channels(path)?),
+            None => ChannelRegistry::default(),
+        };
+        Ok(Arc::new(ChannelNotifier::new(notifier, registry, self.preferences.open()?)))
+    }
+}
+
+#[derive(Args)]
+struct PreferenceArgs {
+    /// The file the channel preferences of the user are kept in.
+    #[arg(long)]
+    preferences: Option<PathBuf>,
+}
+
+impl PreferenceArgs {
+    fn open(&self) -> CliResult<PreferenceStore> {
+        let path = match &self.preferences {
+            Some(path) => path.clone(),
+            None => data_dir().ok_or("cannot find a data directory, pass --preferences")?.join("preferences.json"),
+        };
+        Ok(PreferenceStore::open(path))
+    }
+}
+
+impl HistoryArgs {
+    fn open(&self) -> CliResult<HistoryStore> {
+        let path = match &self.history {
+            Some(path) => path.clone(),
+            None => data_dir().ok_or("cannot find a data directory, pass --history")?.join("history.jsonl"),
+        };
+        Ok(HistoryStore::open(path))
+    }
+}
+
+/// How the daemon and the server deliver the toasts they are given.
+#[derive(Args)]
+struct DeliveryArgs {
+    #[command(flatten)]
+    history: HistoryArgs,
+    #[command(flatten)]
+    preferences: PreferenceArgs,
+    /// A JSON list of the channels toasts may be sent on, or a TOML file of [[channel]] tables.
+    /// Only the default channel exists without it.
+    #[arg(long)]
+    channels: Option<PathBuf>,
+    /// A JSON or TOML file with quiet-hour rules that hold, downgrade or drop toasts. Held toasts
+    /// are shown as a digest when the quiet hours end.
+    #[arg(long)]
+    quiet_policy: Option<PathBuf>,
+    /// A channel whose toasts are collected and shown as one summary every --digest-every. May
+    /// be repeated.
+    #[arg(long)]
+    digest: Vec<String>,
+    #[arg(long, default_value = "15m")]
+    digest_every: String,
+    /// Show urgent and alarm toasts again as a looping alarm when the user has not reacted to
+    /// them for this long, such as "5m".
+    #[arg(long)]
+    escalate_after: Option<String>,
+    /// Post urgent and alarm toasts the user still has not reacted to another while later to
+    /// this http:// URL.
+    #[arg(long, requires = "escalate_after")]
+    escalation_hook: Option<String>,
+}
+
+#[derive(Args)]
+struct ToastArgs {
+    /// Read the toast from a JSON, TOML or XML file, or an Adaptive Card, instead of the flags below.
+    #[arg(short, long, conflicts_with = "title")]
+    file: Option<PathBuf>,
+    /// Render --file as a template with the values of this JSON object, which are also the
+    /// arguments of the messages of --catalogs.
+    #[arg(long)]
+    vars: Option<PathBuf>,
+    /// A value as NAME=VALUE, taking precedence over --vars. May be repeated.
+    #[arg(long, value_parser = parse_var)]
+    var: Vec<(String, String)>,
+    /// A directory of Fluent catalogs, <locale>.ftl files or <locale> directories, for texts
+    /// written as l10n:MESSAGE-ID.
+    #[arg(long, requires = "locale")]
+    catalogs: Option<PathBuf>,
+    /// The locale to localize the toast in, such as de-AT.
+    #[arg(long, requires = "catalogs")]
+    locale: Option<String>,
+    /// The locale of the messages the catalogs of --locale do not have.
+    #[arg(long, requires = "catalogs", default_value = "en-US")]
+    fallback_locale: String,
+    /// The AppUserModelID to show the toast as. Defaults to Windows PowerShell.
+    #[arg(long)]
+    app_id: Option<String>,
+    #[arg(long, required_unless_present = "file")]
+    title: Option<String>,
+    /// A line of body text. May be repeated.
+    #[arg(long)]
+    text: Vec<String>,
+    /// Body text in Markdown, shown after --text. Headings, emphasis, tables and images keep
+    /// their look as far as toasts allow.
+    #[arg(long, conflicts_with = "file")]
+    markdown: Option<String>,
+    /// The app logo shown next to the text.
+    #[arg(long)]
+    icon: Option<String>,
+    /// Crop the app logo into a circle.
+    #[arg(long, requires = "icon")]
+    circle: bool,
+    /// The large image shown above the text.
+    #[arg(long)]
+    hero: Option<String>,
+    /// An inline image shown below the text. May be repeated.
+    #[arg(long)]
+    image: Vec<String>,
+    /// alarm, reminder, incomingCall or urgent.
+    #[arg(long, value_parser = parse_attr::<Scenario>)]
+    scenario: Option<Scenario>,
+    /// short or long.
+    #[arg(long, value_parser = parse_attr::<Duration>)]
+    duration: Option<Duration>,
+    /// A sound name such as Mail or Alarm2, or an ms-winsoundevent URI.
+    #[arg(long, value_parser = parse_sound)]
+    sound: Option<SoundSrc>,
+    /// Loop the sound. Only alarm and call sounds can loop.
+    #[arg(long = "loop", requires = "sound")]
+    loop_: bool,
+    /// Play no sound at all.
+    #[arg(long, conflicts_with = "sound")]
+    silent: bool,
+    /// A button as LABEL=ARGUMENTS, optionally followed by =Success or =Critical. Arguments that
+    /// look like a URI open it. May be repeated.
+    #[arg(long, value_parser = parse_action)]
+    action: Vec<Action>,
+    /// Color the buttons that have a style.
+    #[arg(long)]
+    button_style: bool,
+    /// An RFC 3339 time to show instead of the time the toast arrived.
+    #[arg(long)]
+    timestamp: Option<String>,
+    /// Replace the toast with the same tag and group.
+    #[arg(long)]
+    tag: Option<String>,
+    #[arg(long)]
+    group: Option<String>,
+    /// The channel to send the toast on, so the preferences of the user for it apply.
+    #[arg(long)]
+    channel: Option<String>,
+    /// Downgrade the toast to what this Windows build can show, such as 17763 for Windows 10
+    /// 1809, and report the changes.
+    #[arg(long)]
+    os_build: Option<OsBuild>,
+    /// Shorten the text, then drop groups and images until the payload fits the size limit of
+    /// Windows, and report each step.
+    #[arg(long)]
+    trim: bool,
+}
+
+impl ToastArgs {
+    fn load(&self) -> CliResult<Toast> {
+        let mut context = match &self.vars {
+            Some(vars) => serde_json::from_str(&fs::read_to_string(vars)?)?,
+            None => serde_json::Map::new(),
+        };
+        for (name, value) in &self.var {
+            context.insert(name.clone(), value.clone().into());
+        }
+        let mut toast = match &self.file {
+            Some(path) if self.vars.is_some() || !self.var.is_empty() => {
+                let mut engine = TemplateEngine::new();
+                let name = engine.register_file(path)?;
+                engine.render(&name, &context)?
+            }
+            Some(path) => read_toast(path)?,
+            None => Toast {
+                binding_elems: vec![BindingElem {
+                    icon: self.icon.as_ref().map(|src| Image {
+                        src: absolute(src),
+                        hint_crop: if self.circle { HintCrop::Circle } else { HintCrop::Default },
+                        ..Image::default()
+                    }),
+                    hero: self.hero.as_ref().map(|src| Image { src: absolute(src), ..Image::default() }),
+                    title: (self.title.clone().unwrap_or_default(), None),
+                    elems: self.text.iter().cloned().map(BindingInnerElement::Text)
+                        .chain(self.markdown_elems())
+                        .chain(self.image.iter().map(|src| BindingInnerElement::Image(Image { src: absolute(src), ..Image::default() })))
+                        .collect(),
+                    attribution: None,
+                    lang: None,
+                    template: BindingTemplate::ToastGeneric,
+                }],
+                duration: self.duration.clone().unwrap_or_default(),
+                scenario: self.scenario.clone().unwrap_or_default(),
+                audio: match (&self.sound, self.silent) {
+                    (Some(sound), _) => Some((self.loop_, false, sound.clone())),
+                    (None, true) => Some((false, true, SoundSrc::Default)),
+                    (None, false) => None,
+                },
+                actions: self.action.iter().cloned().map(ActionsElem::Action_).collect(),
+                use_btn_style: self.button_style,
+                display_timestamp: self.timestamp.clone(),
+                tag: self.tag.clone(),
+                group: self.group.clone(),
+                ..Toast::default()
+            },
+        };
+        if let (Some(dir), Some(locale)) = (&self.catalogs, &self.locale) {
+            let localizer = Localizer::load_dir(dir)?.fallback(&self.fallback_locale)?;
+            // A --var written as a JSON number, like count=3, is one, so it selects plurals;
+            // anything else, like id=007, stays the string it is.
+            for (name, value) in &self.var {
+                if let Ok(number) = serde_json::from_str::<serde_json::Number>(value) {
+                    context.insert(name.clone(), number.into());
+                }
+            }
+            toast = localizer.localize(&toast, locale, &context)?;
+        }
+        if let Some(build) = self.os_build {
+            let changes;
+            (toast, changes) = toast.downgrade_for(build);
+            report(&changes);
+        }
+        if self.trim {
+            let steps;
+            (toast, steps) = toast.trim_to(MAX_PAYLOAD_BYTES);
+            report(&steps);
+        }
+        if self.channel.is_some() {
+            toast.channel.clone_from(&self.channel);
+        }
+        if let Some(app_id) = &self.app_id {
+            toast.app_id = app_id.clone();
+        } else if toast.app_id.is_empty() {
+            toast.app_id = Toast::POWERSHELL_APP_ID.to_string();
+        }
+        Ok(toast)
+    }
+
+    /// The elements of --markdown, reporting what it cannot show.
+    fn markdown_elems(&self) -> Vec<BindingInnerElement> {
+        let Some(markdown) = &self.markdown else { return Vec::new() };
+        let mut converted = win_toast::markdown::convert(markdown);
+        report(&converted.warnings);
+        for elem in &mut converted.elems {
+            if let BindingInnerElement::Image(image) = elem {
+                image.src = absolute(&image.src);
+            }
+        }
+        converted.elems
+    }
+
+    /// Loads the toast and refuses to go on if Windows would reject it.
+    fn load_valid(&self) -> CliResult<Toast> {
+        let toast = self.load()?;
+        let diagnostics = toast.validate();
+        report(&diagnostics);
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Err("the toast is invalid".into());
+        }
+        Ok(toast)
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("win-toast: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> CliResult<ExitCode> {
+    sanitize::set_policy(cli.sanitize);
+    match cli.command {
+        Command::Build(args) => {
+            println!("{}", args.load_valid()?.to_xml());
+        }
+        Command::Migrate { file, legacy } => {
+            let toast = read_toast(&file)?;
+            let toast = if legacy {
+                let (toast, warnings) = toast.to_legacy();
+                report(&warnings);
+                toast
+            } else {
+                toast.to_generic()
+            };
+            println!("{}", toast.to_xml());
+        }
+        Command::Validate { file, accessibility } => {
+            let content = fs::read_to_string(&file)?;
+            let diagnostics = if is_xml(&file, &content) {
+                let mut diagnostics = validate_xml(&content);
+                if accessibility {
+                    if let Ok(toast) = Toast::from_xml("", &sanitize_xml(&content, cli.sanitize).0) {
+                        diagnostics.extend(toast.lint_accessibility());
+                    }
+                }
+                diagnostics
+            } else {
+                match parse_toast(&file, &content) {
+                    Ok((mut toast, mut diagnostics)) => {
+                        if toast.app_id.is_empty() {
+                            toast.app_id = Toast::POWERSHELL_APP_ID.to_string();
+                        }
+                        diagnostics.extend(toast.validate());
+                        if accessibility {
+                            diagnostics.extend(toast.lint_accessibility());
+                        }
+                        diagnostics
+                    }
+                    Err(e) => vec![Diagnostic::error("/", e.to_string())],
+                }
+            };
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic);
+            }
+            if diagnostics.iter().any(Diagnostic::is_error) {
+                return Ok(ExitCode::FAILURE);
+            }
+            if diagnostics.is_empty() {
+                eprintln!("{}: no problems found", file.display());
+            }
+        }
+        Command::Preview { toast, html, theme, output } => {
+            let toast = toast.load()?;
+            let preview = if html {
+                toast.render_html(match theme {
+                    ThemeArg::Light => Theme::Light,
+                    ThemeArg::Dark => Theme::Dark,
+                    ThemeArg::System => Theme::System,
+                })
+            } else {
+                toast.render_terminal()
+            };
+            match output {
+                Some(path) => fs::write(path, preview)?,
+                None => print!("{}", preview),
+            }
+        }
+        Command::Send { toast, preferences } => {
+            let toast = toast.load_valid()?;
+            // Whatever channel the toast names exists, so the preferences for it are respected.
+            let registry = toast.channel.iter().fold(ChannelRegistry::default(), |registry, id| registry.channel(Channel::new(id, id)));
+            let notifier = ChannelNotifier::new(Arc::from(platform_notifier()?), registry, preferences.open()?);
+            if let Sent::Silenced(_, silence) = notifier.send(&toast)? {
+                eprintln!("win-toast: not shown, the channel is {}", match silence {
+                    Silence::Muted => "muted",
+                    Silence::Snoozed => "snoozed",
+                    Silence::QuietHours => "in its quiet hours",
+                });
+            }
+        }
+        Command::Schedule { toast, at, after, store } => {
+            let toast = toast.load_valid()?;
+            let deliver_at = match (at, after) {
+                (Some(at), _) => humantime::parse_rfc3339_weak(&at)?,
+                (None, Some(after)) => SystemTime::now() + humantime::parse_duration(&after)?,
+                (None, None) => unreachable!("clap requires --at or --in"),
+            };
+            let entry = store.open()?.add(toast, deliver_at)?;
+            println!("{}", entry.id);
+        }
+        Command::List(store) => {
+            let store = store.open()?;
+            // Windows has delivered the toasts whose time has passed, which are only a record now.
+            #[cfg(windows)]
+            store.take_due(SystemTime::now())?;
+            for entry in store.list()? {
+                let title = entry.toast.binding_elems.first().map(|binding| binding.title.0.as_str()).unwrap_or("");
+                println!("{}  {}  {}", entry.id, humantime::format_rfc3339_seconds(entry.deliver_at), title);
+            }
+        }
+        Command::Cancel { id, store } => {
+            if store.open()?.cancel(&id)?.is_none() {
+                return Err(format!("no scheduled toast has the id {}", id).into());
+            }
+        }
+        Command::Run(args) => return run::run(args),
+        Command::Daemon { address, delivery } => {
+            let daemon = Daemon::bind(address.unwrap_or_else(daemon::default_address), delivery.notifier()?)?;
+            eprintln!("win-toast: listening on {}", daemon.address().display());
+            daemon.run()?;
+        }
+        #[cfg(feature = "http")]
+        Command::Serve { listen, token, delivery } => {
+            let token = token.or_else(|| std::env::var("WIN_TOAST_TOKEN").ok())
+                .ok_or("pass --token or set WIN_TOAST_TOKEN")?;
+            let server = win_toast::http::HttpServer::bind(listen, token, delivery.notifier()?)?
+                .mapper("alertmanager", win_toast::webhook::AlertmanagerMapper::default())
+                .mapper("github", win_toast::webhook::GitHubMapper::default());
+            eprintln!("win-toast: listening on http://{}", server.local_addr()?);
+            server.run()?;
+        }
+        Command::History { since, scenario, unacknowledged, history } => {
+            let query = HistoryQuery {
+                since: since.map(|since| humantime::parse_duration(&since)).transpose()?.map(|since| SystemTime::now() - since),
+                scenario,
+                unacknowledged,
+                ..HistoryQuery::default()
+            };
+            for entry in history.open()?.query(&query)? {
+                let title = entry.toast.binding_elems.first().map(|binding| binding.title.0.as_str()).unwrap_or("");
+                let outcome = match entry.outcomes.last().map(|outcome| &outcome.event) {
+                    Some(ToastEvent::Activated { .. }) => "activated",
+                    Some(ToastEvent::Dismissed { reason: DismissReason::UserCanceled, .. }) => "closed",
+                    Some(ToastEvent::Dismissed { reason: DismissReason::ApplicationHidden, .. }) => "removed",
+                    Some(ToastEvent::Dismissed { reason: DismissReason::TimedOut, .. }) => "timed out",
+                    Some(ToastEvent::Failed { .. }) => "failed",
+                    None if entry.removed_at.is_some() => "removed",
+                    None => "shown",
+                };
+                println!("{}  {}  {:<9}  {}", entry.id, humantime::format_rfc3339_seconds(entry.sent_at), outcome, title);
+            }
+        }
+        Command::Channel { command, preferences } => {
+            let store = preferences.open()?;
+            match command {
+                ChannelCommand::List => {
+                    for (channel, preference) in store.load()? {
+                        let mut states = Vec::new();
+                        if preference.muted {
+                            states.push("muted".to_string());
+                        }
+                        if let Some(until) = preference.snoozed_until.filter(|until| *until > SystemTime::now()) {
+                            states.push(format!("snoozed until {}", humantime::format_rfc3339_seconds(until)));
+                        }
+                        for hours in &preference.quiet_hours {
+                            let mut quiet = format!("quiet {}-{}", hours.start.strftime("%H:%M"), hours.end.strftime("%H:%M"));
+                            if !hours.weekdays.is_empty() {
+                                let days: Vec<String> = hours.weekdays.iter().map(|day| format!("{:?}", day).to_lowercase()).collect();
+                                quiet.push_str(&format!(" on {}", days.join(", ")));
+                            }
+                            if let Some(zone) = &hours.time_zone {
+                                quiet.push_str(&format!(" ({})", zone));
+                            }
+                            states.push(quiet);
+                        }
+                        println!("{}  {}", channel, states.join("; "));
+                    }
+                }
+                ChannelCommand::Mute { channel } => {
+                    store.update(&channel, |preference| preference.muted = true)?;
+                }
+                ChannelCommand::Unmute { channel } => {
+                    store.update(&channel, |preference| preference.muted = false)?;
+                }
+                ChannelCommand::Snooze { channel, duration } => {
+                    let until = SystemTime::now() + humantime::parse_duration(&duration)?;
+                    store.update(&channel, |preference| preference.snoozed_until = Some(until))?;
+                }
+                ChannelCommand::Quiet { channel, from, to, on, time_zone } => {
+                    let hours = QuietHours { start: from, end: to, weekdays: on, time_zone };
+                    hours.zone()?;
+                    store.update(&channel, |preference| preference.quiet_hours.push(hours))?;
+                }
+                ChannelCommand::Reset { channel } => {
+                    store.update(&channel, |preference| *preference = ChannelPreference::default())?;
+                }
+            }
+        }
+        Command::Reshow { id, history } => {
+            let notifier = HistoryNotifier::new(Arc::from(platform_notifier()?), Arc::new(history.open()?));
+            println!("{}", notifier.reshow(&id)?);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The platform notifier behind a send queue, so bursts from other processes are rate limited.
+fn queued_notifier() -> CliResult<Arc<SendQueue>> {
+    let queue = Arc::new(SendQueue::new(Arc::from(platform_notifier()?), QueueConfig::default()));
+    queue.spawn_pump(std::time::Duration::from_secs(1));
+    Ok(queue)
+}
+
+fn report(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+}
+
+fn is_xml(path: &Path, content: &str) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("xml")) || content.trim_start().starts_with('<')
+}
+
+fn read_toast(path: &Path) -> CliResult<Toast> {
+    let content = fs::read_to_string(path)?;
+    if is_xml(path, &content) {
+        return Ok(Toast::from_xml("", &content)?);
+    }
+    let (toast, warnings) = parse_toast(path, &content)?;
+    report(&warnings);
+    Ok(toast)
+}
+
+/// Parses a JSON or TOML toast, or an Adaptive Card with the warnings of its conversion.
+fn parse_toast(path: &Path, content: &str) -> CliResult<(Toast, Vec<Diagnostic>)> {
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")) {
+        return Ok((toml::from_str(content)?, Vec::new()));
+    }
+    let json: serde_json::Value = serde_json::from_str(content)?;
+    if adaptive_card::is_card(&json) {
+        let converted = adaptive_card::convert(&json)?;
+        return Ok((converted.toast, converted.warnings));
+    }
+    Ok((serde_json::from_value(json)?, Vec::new()))
+}
+
+fn parse_attr<T: std::str::FromStr<Err = win_toast::xml::XmlError>>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|e: win_toast::xml::XmlError| e.message)
+}
+
+fn parse_var(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s.split_once('=').ok_or("expected NAME=VALUE")?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+fn parse_time(s: &str) -> Result<Time, String> {
+    s.parse().map_err(|e: jiff::Error| e.to_string())
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday, String> {
+    serde_json::from_value(serde_json::Value::String(s.to_lowercase())).map_err(|_| format!("unknown weekday {:?}", s))
+}
+
+/// Reads channels from a JSON list, or from a TOML file of `[[channel]]` tables.
+fn read_channels(path: &Path) -> CliResult<Vec<Channel>> {
+    #[derive(serde::Deserialize)]
+    struct ChannelsFile {
+        channel: Vec<Channel>,
+    }
+    let content = fs::read_to_string(path)?;
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")) {
+        Ok(toml::from_str::<ChannelsFile>(&content)?.channel)
+    } else {
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+fn parse_sound(s: &str) -> Result<SoundSrc, String> {
+    if s.contains(':') {
+        return parse_attr(s);
+    }
+    parse_attr(&format!("ms-winsoundevent:Notification.{}", s))
+        .or_else(|_| parse_attr(&format!("ms-winsoundevent:Notification.Looping.{}", s)))
+        .map_err(|_| format!("unknown sound {:?}", s))
+}
+
+fn parse_action(s: &str) -> Result<Action, String> {
+    let (content, mut arguments) = s.split_once('=').ok_or("expected LABEL=ARGUMENTS")?;
+    let mut style = None;
+    for suffix in ["Success", "Critical"] {
+        if let Some(rest) = arguments.strip_suffix(suffix).and_then(|rest| rest.strip_suffix('=')) {
+            arguments = rest;
+            style = Some(suffix.to_string());
+        }
+    }
+    let is_uri = arguments.split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)));
+    Ok(Action {
+        content: content.to_string(),
+        arguments: arguments.to_string(),
+        activation_type: if is_uri { ActivationType::Protocol } else { ActivationType::Foreground },
+        hint_button_style: style,
+        ..Action::default()
+    })
+}
+
+/// Windows only loads local images from absolute paths.
+fn absolute(src: &str) -> String {
+    if src.contains("://") {
+        return src.to_string();
+    }
+    std::path::absolute(src).map(|path| path.display().to_string()).unwrap_or_else(|_| src.to_string())
+}
+
+fn data_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+    };
+    base.map(|base| base.join("win_toast"))
+}
@@ -0,0 +1,225 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use clap::Args;
+use win_toast::command::{CommandOutcome, CommandToastOptions, Tail};
+use win_toast::notification::Toast;
+use win_toast::notifier::platform_notifier;
+
+use crate::CliResult;
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// The AppUserModelID to show the toast as. Defaults to Windows PowerShell.
+    #[arg(long)]
+    app_id: Option<String>,
+    /// How many of the last stderr lines to show in the toast.
+    #[arg(long, default_value_t = 5)]
+    tail: usize,
+    /// Where to keep the full stderr output. Defaults to a file in the temp directory.
+    #[arg(long)]
+    log: Option<PathBuf>,
+    /// The hero image shown when the command succeeds.
+    #[arg(long)]
+    success_hero: Option<String>,
+    /// The hero image shown when the command fails.
+    #[arg(long)]
+    failure_hero: Option<String>,
+    /// Give the command the terminal as its stderr, for programs that drop colors or progress
+    /// bars when stderr is not a terminal. The toast then shows no stderr lines and no log is kept.
+    #[arg(long, conflicts_with_all = ["tail", "log"])]
+    inherit_stderr: bool,
+    /// The command to run and its arguments.
+    #[arg(last = true, required = true)]
+    command: Vec<String>,
+}
+
+/// Runs the command with stdout untouched and stderr copied through as it arrives, then sends
+/// the toast and exits with the exit code of the command.
+///
+/// The stderr of the command is a pipe, not the terminal, so that its last lines can be kept.
+/// Programs that check for a terminal may leave out colors and progress output; with
+/// `--inherit-stderr` they get the terminal and the toast goes without their stderr.
+pub fn run(args: RunArgs) -> CliResult<ExitCode> {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let log_path = args.log.clone()
+        .unwrap_or_else(|| std::env::temp_dir().join(format!("win-toast-run-{}-{}.log", std::process::id(), stamp)));
+
+    let start = Instant::now();
+    let mut child = Command::new(&args.command[0])
+        .args(&args.command[1..])
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(if args.inherit_stderr { Stdio::inherit() } else { Stdio::piped() })
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {}", args.command[0], e))?;
+    let interrupts = IgnoreInterrupts::new();
+    let Some(mut stderr) = child.stderr.take() else {
+        let status = child.wait()?;
+        drop(interrupts);
+        let outcome = CommandOutcome {
+            command: display_command(&args.command),
+            exit_code: status.code(),
+            duration: start.elapsed(),
+            stderr_tail: Vec::new(),
+            log: None,
+            rerun_script: write_rerun_script(&log_path, &std::env::current_dir()?, &args.command).ok(),
+        };
+        return Ok(notify(outcome, status, args));
+    };
+    let mut log = File::create(&log_path)?;
+    let tail_lines = args.tail;
+    let reader = thread::spawn(move || {
+        let mut tail = Tail::new(tail_lines);
+        let mut buf = [0; 8192];
+        let mut out = io::stderr();
+        loop {
+            match stderr.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let _ = out.write_all(&buf[..n]);
+                    let _ = out.flush();
+                    let _ = log.write_all(&buf[..n]);
+                    tail.push(&buf[..n]);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+        tail
+    });
+    let status = child.wait()?;
+    drop(interrupts);
+    let duration = start.elapsed();
+    let tail = reader.join().map_err(|_| "the stderr reader panicked")?;
+
+    let cwd = std::env::current_dir()?;
+    let rerun_script = write_rerun_script(&log_path, &cwd, &args.command).ok();
+    let outcome = CommandOutcome {
+        command: display_command(&args.command),
+        exit_code: status.code(),
+        duration,
+        stderr_tail: tail.into_lines(),
+        log: Some(log_path),
+        rerun_script,
+    };
+    Ok(notify(outcome, status, args))
+}
+
+/// Keeps Ctrl-C from ending the wrapper while the command runs, so the toast is still sent. The
+/// command gets it as usual, as long as it is set up after the command is spawned: ignored
+/// signals are inherited.
+struct IgnoreInterrupts {
+    #[cfg(unix)]
+    previous: libc::sighandler_t,
+}
+
+impl IgnoreInterrupts {
+    fn new() -> IgnoreInterrupts {
+        #[cfg(unix)]
+        {
+            IgnoreInterrupts { previous: unsafe { libc::signal(libc::SIGINT, libc::SIG_IGN) } }
+        }
+        #[cfg(windows)]
+        {
+            unsafe { windows::Win32::System::Console::SetConsoleCtrlHandler(Some(ignore_ctrl_c), true) };
+            IgnoreInterrupts {}
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            IgnoreInterrupts {}
+        }
+    }
+}
+
+impl Drop for IgnoreInterrupts {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::signal(libc::SIGINT, self.previous);
+        }
+        #[cfg(windows)]
+        unsafe {
+            windows::Win32::System::Console::SetConsoleCtrlHandler(Some(ignore_ctrl_c), false);
+        }
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn ignore_ctrl_c(ctrl_type: u32) -> windows::Win32::Foundation::BOOL {
+    use windows::Win32::System::Console::{CTRL_BREAK_EVENT, CTRL_C_EVENT};
+    (ctrl_type == CTRL_C_EVENT || ctrl_type == CTRL_BREAK_EVENT).into()
+}
+
+/// Sends the toast for the outcome and returns the code to exit with, which is 128 and the
+/// number of the signal for a command killed by one, as shells report it.
+fn notify(outcome: CommandOutcome, status: ExitStatus, args: RunArgs) -> ExitCode {
+    let toast = outcome.to_toast(&CommandToastOptions {
+        app_id: args.app_id.unwrap_or_else(|| Toast::POWERSHELL_APP_ID.to_string()),
+        success_hero: args.success_hero,
+        failure_hero: args.failure_hero,
+    });
+    if let Err(e) = platform_notifier().and_then(|notifier| notifier.show(&toast)) {
+        eprintln!("win-toast: {}", e);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return ExitCode::from((128 + signal).min(255) as u8);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = status;
+    ExitCode::from(outcome.exit_status())
+}
+
+fn display_command(command: &[String]) -> String {
+    command.iter().map(|arg| quote(arg)).collect::<Vec<_>>().join(" ")
+}
+
+/// Quotes an argument for showing, where it needs it.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "\"'&|<>^;$`*?[]()~#{}!%".contains(c)) {
+        return arg.to_string();
+    }
+    if cfg!(windows) {
+        format!("\"{}\"", arg.replace('"', "\"\""))
+    } else {
+        sh_quote(arg)
+    }
+}
+
+/// Nothing is special inside single quotes, so any argument is safe in them.
+fn sh_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// A batch file expands `%VAR%` even inside quotes, so `%` is doubled as well.
+fn cmd_quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('"', "\"\"").replace('%', "%%"))
+}
+
+/// Writes a script next to the log that runs the command again from the same directory, which
+/// the "Rerun" button opens.
+fn write_rerun_script(log_path: &Path, cwd: &Path, command: &[String]) -> io::Result<PathBuf> {
+    let quote_all = |quote: fn(&str) -> String| command.iter().map(|arg| quote(arg)).collect::<Vec<_>>().join(" ");
+    let cwd = cwd.display().to_string();
+    let (extension, script) = if cfg!(windows) {
+        ("cmd", format!("@echo off\r\ncd /d {}\r\n{}\r\npause\r\n", cmd_quote(&cwd), quote_all(cmd_quote)))
+    } else {
+        ("sh", format!("#!/bin/sh\ncd {} || exit 1\nexec {}\n", sh_quote(&cwd), quote_all(sh_quote)))
+    };
+    let path = log_path.with_extension(extension);
+    fs::write(&path, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(path)
+}
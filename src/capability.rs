@@ -0,0 +1,230 @@
+//! What each Windows build can show in a toast, and toasts downgraded to what an older build can.
+//!
+//! Windows does not reject a toast that uses a feature it does not know, it shows the toast
+//! without it or not at all. [`Toast::downgrade_for`] makes the choice explicit: it removes or
+//! replaces what the build lacks and reports each change. The [`WinRtNotifier`] downgrades every
+//! toast for the build it runs on.
+//!
+//! [`WinRtNotifier`]: crate::notifier::WinRtNotifier
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::notification::*;
+use crate::validate::Diagnostic;
+
+/// A Windows build number, such as 17763 for Windows 10 version 1809.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OsBuild(pub u32);
+
+impl OsBuild {
+    pub const WINDOWS_10_1607: OsBuild = OsBuild(14393);
+    pub const WINDOWS_10_1703: OsBuild = OsBuild(15063);
+    pub const WINDOWS_10_1709: OsBuild = OsBuild(16299);
+    pub const WINDOWS_10_1803: OsBuild = OsBuild(17134);
+    pub const WINDOWS_10_1809: OsBuild = OsBuild(17763);
+    pub const WINDOWS_10_1903: OsBuild = OsBuild(18362);
+    pub const WINDOWS_10_2004: OsBuild = OsBuild(19041);
+    pub const WINDOWS_10_22H2: OsBuild = OsBuild(19045);
+    pub const WINDOWS_11_21H2: OsBuild = OsBuild(22000);
+    pub const WINDOWS_11_22H2: OsBuild = OsBuild(22621);
+    pub const WINDOWS_11_24H2: OsBuild = OsBuild(26100);
+
+    /// The build of the running Windows, read once.
+    #[cfg(windows)]
+    pub fn current() -> Option<OsBuild> {
+        use std::sync::OnceLock;
+        use windows::System::Profile::AnalyticsInfo;
+
+        static CURRENT: OnceLock<Option<OsBuild>> = OnceLock::new();
+        *CURRENT.get_or_init(|| {
+            // The version is packed into a u64 as major.minor.build.revision, 16 bits each.
+            let version: u64 = AnalyticsInfo::VersionInfo().and_then(|info| info.DeviceFamilyVersion()).ok()?.to_string().parse().ok()?;
+            Some(OsBuild(((version >> 16) & 0xFFFF) as u32))
+        })
+    }
+}
+
+impl fmt::Display for OsBuild {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for OsBuild {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<OsBuild, Self::Err> {
+        s.parse().map(OsBuild)
+    }
+}
+
+/// A feature of toasts that only some builds have.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Groups and subgroups, and with them the text styles.
+    AdaptiveContent,
+    HeroImage,
+    Attribution,
+    ContextMenuActions,
+    /// Toasts of this crate have no progress bar, it is listed for payloads written by hand.
+    ProgressBar,
+    Header,
+    DisplayTimestamp,
+    /// The Success and Critical colors of `hint-buttonStyle`.
+    ButtonStyle,
+    ToolTip,
+    UrgentScenario,
+}
+
+/// The first build that has each capability.
+pub const CAPABILITIES: &[(Capability, OsBuild)] = &[
+    (Capability::AdaptiveContent, OsBuild::WINDOWS_10_1607),
+    (Capability::HeroImage, OsBuild::WINDOWS_10_1607),
+    (Capability::Attribution, OsBuild::WINDOWS_10_1607),
+    (Capability::ContextMenuActions, OsBuild::WINDOWS_10_1607),
+    (Capability::ProgressBar, OsBuild::WINDOWS_10_1703),
+    (Capability::Header, OsBuild::WINDOWS_10_1703),
+    (Capability::DisplayTimestamp, OsBuild::WINDOWS_10_1703),
+    (Capability::ButtonStyle, OsBuild::WINDOWS_11_21H2),
+    (Capability::ToolTip, OsBuild::WINDOWS_11_21H2),
+    (Capability::UrgentScenario, OsBuild(22546)),
+];
+
+impl Capability {
+    /// The first build that has the capability.
+    pub fn since(self) -> OsBuild {
+        CAPABILITIES.iter().find(|(capability, _)| *capability == self).map(|(_, build)| *build).expect("every capability is in the table")
+    }
+
+    pub fn supported_on(self, build: OsBuild) -> bool {
+        build >= self.since()
+    }
+}
+
+impl Toast {
+    /// The toast with what `build` cannot show removed or replaced, and a warning for each change:
+    ///
+    /// - an urgent toast becomes a reminder;
+    /// - buttons lose their style, and a button without content shows its tooltip as content;
+    /// - context menu actions, the header and the display timestamp are removed;
+    /// - the texts of groups become lines of text, and the hero image and attribution are removed.
+    pub fn downgrade_for(&self, build: OsBuild) -> (Toast, Vec<Diagnostic>) {
+        let mut toast = self.clone();
+        let mut changes = Vec::new();
+        let mut change = |capability: Capability, path: String, message: &str| {
+            changes.push(Diagnostic::warning(path, format!("{} before build {}", message, capability.since())));
+        };
+        let lacks = |capability: Capability| !capability.supported_on(build);
+
+        if toast.scenario == Scenario::Urgent && lacks(Capability::UrgentScenario) {
+            toast.scenario = Scenario::Reminder;
+            change(Capability::UrgentScenario, "scenario".to_string(), "the urgent toast is shown as a reminder");
+        }
+        if toast.header.is_some() && lacks(Capability::Header) {
+            toast.header = None;
+            change(Capability::Header, "header".to_string(), "the header is removed");
+        }
+        if toast.display_timestamp.is_some() && lacks(Capability::DisplayTimestamp) {
+            toast.display_timestamp = None;
+            change(Capability::DisplayTimestamp, "display_timestamp".to_string(), "the display timestamp is removed");
+        }
+        if toast.use_btn_style && lacks(Capability::ButtonStyle) {
+            toast.use_btn_style = false;
+            change(Capability::ButtonStyle, "use_btn_style".to_string(), "the buttons lose their style");
+        }
+        let mut index = 0;
+        toast.actions.retain_mut(|elem| {
+            let path = format!("actions[{}]", index);
+            index += 1;
+            let ActionsElem::Action_(action) = elem else { return true };
+            if action.placement == ActionPlacement::ContextMenu && lacks(Capability::ContextMenuActions) {
+                change(Capability::ContextMenuActions, path, "the context menu action is removed");
+                return false;
+            }
+            if action.hint_button_style.is_some() && lacks(Capability::ButtonStyle) {
+                action.hint_button_style = None;
+            }
+            if let Some(tool_tip) = action.hint_tool_tip.take_if(|_| lacks(Capability::ToolTip)) {
+                if action.content.is_empty() {
+                    action.content = tool_tip;
+                    change(Capability::ToolTip, path, "the tooltip is shown as the content of the button");
+                } else {
+                    change(Capability::ToolTip, path, "the tooltip is removed");
+                }
+            }
+            true
+        });
+        for (i, binding) in toast.binding_elems.iter_mut().enumerate() {
+            let path = format!("binding_elems[{}]", i);
+            if binding.hero.is_some() && lacks(Capability::HeroImage) {
+                binding.hero = None;
+                change(Capability::HeroImage, format!("{}.hero", path), "the hero image is removed");
+            }
+            if binding.attribution.is_some() && lacks(Capability::Attribution) {
+                binding.attribution = None;
+                change(Capability::Attribution, format!("{}.attribution", path), "the attribution is removed");
+            }
+            if lacks(Capability::AdaptiveContent) {
+                let mut elems = Vec::new();
+                for (j, elem) in std::mem::take(&mut binding.elems).into_iter().enumerate() {
+                    let BindingInnerElement::Group(subgroups) = elem else {
+                        elems.push(elem);
+                        continue;
+                    };
+                    change(Capability::AdaptiveContent, format!("{}.elems[{}]", path, j), "the group is shown as lines of text");
+                    elems.extend(subgroups.into_iter().flatten().filter_map(|elem| match elem {
+                        SubgroupElement::Text_(text) => Some(BindingInnerElement::Text(text.text)),
+                        SubgroupElement::Image_(_) => None,
+                    }));
+                }
+                binding.elems = elems;
+            }
+        }
+        (toast, changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_downgrade_for() {
+        assert!(Capability::Header.supported_on(OsBuild::WINDOWS_10_1809));
+        assert!(!Capability::ButtonStyle.supported_on(OsBuild::WINDOWS_10_1809));
+        let mut toast = Toast {
+            scenario: Scenario::Urgent,
+            header: Some(Header { id: "ci".to_string(), title: "CI".to_string(), ..Header::default() }),
+            ..sample_toast()
+        };
+        toast.actions.push(ActionsElem::Action_(Action {
+            image_uri: Some("file:///C:/reply.png".to_string()),
+            hint_tool_tip: Some("Reply".to_string()),
+            ..Action::default()
+        }));
+        let (current, changes) = toast.downgrade_for(OsBuild::WINDOWS_11_24H2);
+        assert_eq!((current, changes), (toast.clone(), vec![]));
+
+        let (downgraded, changes) = toast.downgrade_for(OsBuild::WINDOWS_10_1809);
+        assert_eq!(downgraded.scenario, Scenario::Reminder);
+        assert!(!downgraded.use_btn_style);
+        assert!(downgraded.header.is_some());
+        let ActionsElem::Action_(reply) = downgraded.actions.last().unwrap() else { panic!("expected an action") };
+        assert_eq!((reply.content.as_str(), &reply.hint_tool_tip), ("Reply", &None));
+        let paths: Vec<&str> = changes.iter().map(|change| change.path.as_str()).collect();
+        assert_eq!(paths, ["scenario", "use_btn_style", "actions[2]"]);
+        assert_eq!(changes[0].to_string(), "warning: scenario: the urgent toast is shown as a reminder before build 22546");
+
+        let (downgraded, changes) = toast.downgrade_for(OsBuild(10586));
+        assert_eq!(downgraded.header, None);
+        assert_eq!(downgraded.binding_elems[0].elems, vec![BindingInnerElement::Text("42 passed".to_string())]);
+        assert_eq!(downgraded.binding_elems[0].attribution, None);
+        assert!(downgraded.actions.iter().all(|elem| !matches!(elem, ActionsElem::Action_(action) if action.placement == ActionPlacement::ContextMenu)));
+        assert_eq!(changes.len(), 7);
+    }
+}
@@ -0,0 +1,324 @@
+//! Notification channels, the kinds of toasts an app sends.
+//!
+//! Like the notification channels of Android, each [`Channel`] gives the toasts sent on it
+//! defaults, and users can mute, snooze or set quiet hours for each channel on its own. What a
+//! user chose is kept in a [`PreferenceStore`], which a [`ChannelNotifier`] reads on every toast,
+//! so changes take effect right away.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{Clock, SystemClock};
+use crate::notification::*;
+use crate::notifier::{next_id, Notifier, NotifyError, ToastEvent, Tracked};
+use crate::quiet::QuietHours;
+
+/// The channel of toasts that do not name one.
+pub const DEFAULT_CHANNEL: &str = "default";
+
+/// A kind of toast, with the defaults of the toasts sent on it. A toast keeps what it sets
+/// itself; the defaults only fill in what it leaves at the default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Channel {
+    pub id: String,
+    /// The name users pick the channel by.
+    pub name: String,
+    pub description: String,
+    pub scenario: Scenario,
+    pub duration: Duration,
+    /// The elements are (loop, silent and src).
+    pub audio: Option<(bool, bool, SoundSrc)>,
+    pub header: Option<Header>,
+    pub use_btn_style: bool,
+}
+
+impl Channel {
+    pub fn new(id: impl Into<String>, name: impl Into<String>) -> Channel {
+        Channel { id: id.into(), name: name.into(), ..Channel::default() }
+    }
+
+    /// Fills in the defaults of the channel where the toast has none of its own.
+    pub fn apply(&self, toast: &mut Toast) {
+        if toast.scenario == Scenario::Default {
+            toast.scenario = self.scenario.clone();
+        }
+        if toast.duration == Duration::default() {
+            toast.duration = self.duration.clone();
+        }
+        if toast.audio.is_none() {
+            toast.audio.clone_from(&self.audio);
+        }
+        if toast.header.is_none() {
+            toast.header.clone_from(&self.header);
+        }
+        toast.use_btn_style |= self.use_btn_style;
+    }
+}
+
+/// The channels an app sends on. There is always a [`DEFAULT_CHANNEL`].
+#[derive(Clone, Debug)]
+pub struct ChannelRegistry {
+    channels: Vec<Channel>,
+}
+
+impl Default for ChannelRegistry {
+    fn default() -> ChannelRegistry {
+        ChannelRegistry { channels: vec![Channel::new(DEFAULT_CHANNEL, "General")] }
+    }
+}
+
+impl ChannelRegistry {
+    /// The channels, with a default channel added unless they have one.
+    pub fn from_channels(channels: Vec<Channel>) -> ChannelRegistry {
+        channels.into_iter().fold(ChannelRegistry::default(), ChannelRegistry::channel)
+    }
+
+    /// Adds the channel, replacing one with the same id.
+    pub fn channel(mut self, channel: Channel) -> ChannelRegistry {
+        match self.channels.iter_mut().find(|existing| existing.id == channel.id) {
+            Some(existing) => *existing = channel,
+            None => self.channels.push(channel),
+        }
+        self
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Channel> {
+        self.channels.iter().find(|channel| channel.id == id)
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+}
+
+/// Why a toast was not shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Silence {
+    Muted,
+    Snoozed,
+    QuietHours,
+}
+
+/// What a user chose for a channel.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelPreference {
+    pub muted: bool,
+    /// No toasts are shown on the channel until then.
+    pub snoozed_until: Option<SystemTime>,
+    pub quiet_hours: Vec<QuietHours>,
+}
+
+impl ChannelPreference {
+    /// Why toasts on the channel are not shown at `now`, if they are not.
+    pub fn silence(&self, now: SystemTime) -> Option<Silence> {
+        if self.muted {
+            Some(Silence::Muted)
+        } else if self.snoozed_until.is_some_and(|until| now < until) {
+            Some(Silence::Snoozed)
+        } else if self.quiet_hours.iter().any(|hours| hours.contains(now)) {
+            Some(Silence::QuietHours)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct PreferenceStore {
+    path: PathBuf,
+}
+
+impl PreferenceStore {
+    /// Opens the preferences kept in the JSON file at `path`, which is created on the first change.
+    pub fn open(path: impl Into<PathBuf>) -> PreferenceStore {
+        PreferenceStore { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The preferences by channel id. Quiet hours in an unknown time zone are an error.
+    pub fn load(&self) -> io::Result<BTreeMap<String, ChannelPreference>> {
+        let preferences: BTreeMap<String, ChannelPreference> = match fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        for (channel, preference) in &preferences {
+            for hours in &preference.quiet_hours {
+                if let Err(e) = hours.zone() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("the quiet hours of {}: {}", channel, e)));
+                }
+            }
+        }
+        Ok(preferences)
+    }
+
+    pub fn get(&self, channel: &str) -> io::Result<ChannelPreference> {
+        Ok(self.load()?.remove(channel).unwrap_or_default())
+    }
+
+    /// Changes the preference of a channel and saves it.
+    pub fn update(&self, channel: &str, change: impl FnOnce(&mut ChannelPreference)) -> io::Result<ChannelPreference> {
+        let mut preferences = self.load()?;
+        let mut preference = preferences.remove(channel).unwrap_or_default();
+        change(&mut preference);
+        if preference != ChannelPreference::default() {
+            preferences.insert(channel.to_string(), preference.clone());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&preferences)?)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(preference)
+    }
+}
+
+/// What became of a toast sent on a channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sent {
+    Shown(String),
+    /// The user silenced the channel. The id can still be updated and removed, which does nothing,
+    /// until it is one of the oldest of many silenced toasts.
+    Silenced(String, Silence),
+}
+
+impl Sent {
+    pub fn id(&self) -> &str {
+        match self {
+            Sent::Shown(id) | Sent::Silenced(id, _) => id,
+        }
+    }
+}
+
+/// A notifier that sends every toast on its channel, with the defaults of the channel and
+/// unless the user silenced it.
+///
+/// Preferences that cannot be read do not silence anything, so a broken file does not hide toasts.
+pub struct ChannelNotifier {
+    inner: Arc<dyn Notifier>,
+    registry: ChannelRegistry,
+    preferences: PreferenceStore,
+    clock: Arc<dyn Clock>,
+    /// The ids of silenced toasts.
+    silenced: Mutex<Tracked<()>>,
+}
+
+impl ChannelNotifier {
+    pub fn new(inner: Arc<dyn Notifier>, registry: ChannelRegistry, preferences: PreferenceStore) -> ChannelNotifier {
+        ChannelNotifier::with_clock(inner, registry, preferences, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(inner: Arc<dyn Notifier>, registry: ChannelRegistry, preferences: PreferenceStore, clock: Arc<dyn Clock>) -> ChannelNotifier {
+        ChannelNotifier { inner, registry, preferences, clock, silenced: Mutex::default() }
+    }
+
+    pub fn registry(&self) -> &ChannelRegistry {
+        &self.registry
+    }
+
+    pub fn preferences(&self) -> &PreferenceStore {
+        &self.preferences
+    }
+
+    pub fn send(&self, toast: &Toast) -> Result<Sent, NotifyError> {
+        let (channel, toast) = self.prepare(toast)?;
+        if let Some(silence) = self.preferences.get(&channel.id).ok().and_then(|preference| preference.silence(self.clock.now())) {
+            let id = next_id();
+            self.silenced.lock().unwrap_or_else(|e| e.into_inner()).insert(id.clone(), (), |_, _| false);
+            return Ok(Sent::Silenced(id, silence));
+        }
+        self.inner.show(&toast).map(Sent::Shown)
+    }
+
+    fn prepare(&self, toast: &Toast) -> Result<(&Channel, Toast), NotifyError> {
+        let id = toast.channel.as_deref().unwrap_or(DEFAULT_CHANNEL);
+        let channel = self.registry.get(id).ok_or_else(|| NotifyError::UnknownChannel(id.to_string()))?;
+        let mut toast = toast.clone();
+        channel.apply(&mut toast);
+        Ok((channel, toast))
+    }
+
+    fn is_silenced(&self, id: &str) -> bool {
+        self.silenced.lock().unwrap_or_else(|e| e.into_inner()).get(id).is_some()
+    }
+}
+
+impl Notifier for ChannelNotifier {
+    /// Sends the toast and returns its id, whether or not it was shown.
+    fn show(&self, toast: &Toast) -> Result<String, NotifyError> {
+        self.send(toast).map(|sent| sent.id().to_string())
+    }
+
+    fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError> {
+        if self.is_silenced(id) {
+            return Ok(());
+        }
+        let (_, toast) = self.prepare(toast)?;
+        self.inner.update(id, &toast)
+    }
+
+    fn remove(&self, id: &str) -> Result<(), NotifyError> {
+        if self.silenced.lock().unwrap_or_else(|e| e.into_inner()).remove(id).is_some() {
+            return Ok(());
+        }
+        self.inner.remove(id)
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+        self.inner.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::notifier::MockNotifier;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_channels() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("win_toast-preferences-{}.json", std::process::id()));
+        let preferences = PreferenceStore::open(&path);
+        preferences.update("chat", |preference| preference.muted = true)?;
+        // The Unix epoch was a Thursday, at midnight in UTC.
+        let hours: QuietHours = serde_json::from_str(r#"{"start":"22:00","end":"07:00","weekdays":["wednesday"],"time_zone":"UTC"}"#)?;
+        preferences.update("builds", |preference| preference.quiet_hours.push(hours))?;
+
+        let header = Header { id: "builds".to_string(), title: "Builds".to_string(), ..Header::default() };
+        let registry = ChannelRegistry::default()
+            .channel(Channel { header: Some(header.clone()), scenario: Scenario::Reminder, ..Channel::new("builds", "Builds") })
+            .channel(Channel::new("chat", "Chat"));
+        let notifier = Arc::new(MockNotifier::new());
+        let clock = Arc::new(FakeClock::default());
+        let channels = ChannelNotifier::with_clock(notifier.clone(), registry, PreferenceStore::open(&path), clock.clone());
+        let on = |channel: &str| Toast { channel: Some(channel.to_string()), scenario: Scenario::Default, ..sample_toast() };
+
+        assert!(matches!(channels.send(&on("builds")), Ok(Sent::Silenced(_, Silence::QuietHours))));
+        clock.advance(Duration::from_secs(8 * 3600));
+        assert!(matches!(channels.send(&on("builds")), Ok(Sent::Shown(_))));
+        let shown = notifier.shown().pop().unwrap();
+        assert_eq!((shown.header, shown.scenario), (Some(header), Scenario::Reminder));
+
+        let Ok(Sent::Silenced(muted, Silence::Muted)) = channels.send(&on("chat")) else { panic!("expected chat to be muted") };
+        channels.update(&muted, &on("chat")).unwrap();
+        channels.remove(&muted).unwrap();
+        assert!(matches!(channels.send(&on("alerts")), Err(NotifyError::UnknownChannel(channel)) if channel == "alerts"));
+        assert!(matches!(channels.send(&sample_toast()), Ok(Sent::Shown(_))));
+        assert_eq!(notifier.shown().len(), 2);
+        std::fs::remove_file(path)
+    }
+}
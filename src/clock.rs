@@ -0,0 +1,67 @@
+//! The time source of the parts of the crate that limit, wait or schedule, so tests can move
+//! time along instead of sleeping.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The clock of the operating system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug)]
+pub struct FakeClock {
+    now: Mutex<SystemTime>,
+}
+
+impl FakeClock {
+    pub fn new(now: SystemTime) -> FakeClock {
+        FakeClock { now: Mutex::new(now) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+}
+
+impl Default for FakeClock {
+    /// A clock standing at the Unix epoch.
+    fn default() -> FakeClock {
+        FakeClock::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Calls `tick` with the target every `interval` until the target is dropped, for the parts that
+/// act on what became due.
+pub(crate) fn spawn_pump<T>(target: &Arc<T>, interval: Duration, tick: impl Fn(&T) + Send + 'static) -> thread::JoinHandle<()>
+    where T: Send + Sync + 'static {
+    let target = Arc::downgrade(target);
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        match target.upgrade() {
+            Some(target) => tick(&target),
+            None => break,
+        }
+    })
+}
@@ -0,0 +1,218 @@
+//! Toasts reporting how a finished command went, as sent by `win-toast run`.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::notification::{self, *};
+
+/// Keeps the last lines of a stream that arrives in arbitrary chunks.
+pub struct Tail {
+    max_lines: usize,
+    lines: VecDeque<String>,
+    partial: Vec<u8>,
+}
+
+impl Tail {
+    pub fn new(max_lines: usize) -> Tail {
+        Tail { max_lines, lines: VecDeque::new(), partial: Vec::new() }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.partial);
+                self.push_line(line);
+            } else {
+                self.partial.push(byte);
+            }
+        }
+    }
+
+    fn push_line(&mut self, line: Vec<u8>) {
+        // Progress bars redraw a line with carriage returns; only the final state is interesting.
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches('\r').rsplit('\r').next().unwrap_or_default().to_string();
+        if self.max_lines == 0 {
+            return;
+        }
+        if self.lines.len() == self.max_lines {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// The last lines, including an unterminated final line.
+    pub fn into_lines(mut self) -> Vec<String> {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.push_line(line);
+        }
+        self.lines.into()
+    }
+}
+
+/// How a command ended.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandOutcome {
+    /// The command line as the user would type it.
+    pub command: String,
+    /// The exit code, or `None` if the command was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    pub stderr_tail: Vec<String>,
+    /// The file the full stderr output was written to.
+    pub log: Option<PathBuf>,
+    /// A script that runs the command again in the same directory.
+    pub rerun_script: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CommandToastOptions {
+    pub app_id: String,
+    pub success_hero: Option<String>,
+    pub failure_hero: Option<String>,
+}
+
+impl CommandOutcome {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// The code for a wrapper to exit with. Only its low byte reaches the parent, so a failure
+    /// whose low byte is zero, such as a crash with 0xC0000000 on Windows, exits with 1.
+    pub fn exit_status(&self) -> u8 {
+        match self.exit_code {
+            Some(0) => 0,
+            Some(code) => ((code & 0xFF) as u8).max(1),
+            None => 1,
+        }
+    }
+
+    pub fn to_toast(&self, options: &CommandToastOptions) -> Toast {
+        let (title, hero) = match self.exit_code {
+            Some(0) => (format!("✔ {} succeeded", self.command), &options.success_hero),
+            // Negative codes are NTSTATUS values like 0xC0000005, which are known by their hex.
+            Some(code) if code < 0 => (format!("✘ {} failed with exit code {:#010X}", self.command, code), &options.failure_hero),
+            Some(code) => (format!("✘ {} failed with exit code {}", self.command, code), &options.failure_hero),
+            None => (format!("✘ {} was terminated", self.command), &options.failure_hero),
+        };
+        let mut elems = vec![BindingInnerElement::Text(format!("Finished in {}", format_duration(self.duration)))];
+        if !self.stderr_tail.is_empty() {
+            let lines = self.stderr_tail.iter()
+                .map(|line| SubgroupElement::Text_(InnerText {
+                    text: line.clone(),
+                    hint_style: TextStyle::CaptionSubtle,
+                    ..InnerText::default()
+                }))
+                .collect();
+            elems.push(BindingInnerElement::Group(vec![lines]));
+        }
+        let mut actions = Vec::new();
+        if let Some(script) = &self.rerun_script {
+            actions.push(ActionsElem::Action_(Action {
+                content: "Rerun".to_string(),
+                arguments: file_uri(script),
+                activation_type: ActivationType::Protocol,
+                hint_button_style: (!self.success()).then(|| "Critical".to_string()),
+                ..Action::default()
+            }));
+        }
+        if let Some(log) = &self.log {
+            actions.push(ActionsElem::Action_(Action {
+                content: "Open log".to_string(),
+                arguments: file_uri(log),
+                activation_type: ActivationType::Protocol,
+                hint_button_style: self.success().then(|| "Success".to_string()),
+                ..Action::default()
+            }));
+        }
+        Toast {
+            app_id: options.app_id.clone(),
+            binding_elems: vec![BindingElem {
+                hero: hero.as_ref().map(|src| Image { src: src.clone(), ..Image::default() }),
+                title: (title, Some(2)),
+                elems,
+                ..BindingElem::default()
+            }],
+            duration: if self.success() { notification::Duration::Default } else { notification::Duration::Long },
+            actions,
+            use_btn_style: true,
+            ..Toast::default()
+        }
+    }
+}
+
+/// Formats a duration the way people say it, such as "1h 2m" or "12.3s".
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+pub(crate) fn file_uri(path: &Path) -> String {
+    let path = path.display().to_string().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_status() {
+        let outcome = |exit_code| CommandOutcome {
+            command: "cargo build".to_string(),
+            exit_code,
+            duration: Duration::from_secs(3),
+            stderr_tail: Vec::new(),
+            log: None,
+            rerun_script: None,
+        };
+        assert_eq!(outcome(Some(0)).exit_status(), 0);
+        assert_eq!(outcome(Some(2)).exit_status(), 2);
+        assert_eq!(outcome(Some(256)).exit_status(), 1);
+        assert_eq!(outcome(None).exit_status(), 1);
+        let crashed = outcome(Some(0xC0000005_u32 as i32));
+        assert_eq!(crashed.exit_status(), 5);
+        assert!(!crashed.success());
+        let toast = crashed.to_toast(&CommandToastOptions::default());
+        assert_eq!(toast.binding_elems[0].title.0, "✘ cargo build failed with exit code 0xC0000005");
+    }
+
+    #[test]
+    fn test_command_toast() {
+        let mut tail = Tail::new(2);
+        tail.push(b"compiling\nerror[E0308]: mismatched types\n  --> src/main.rs");
+        tail.push(b":4:5\n10%\r100%");
+        let outcome = CommandOutcome {
+            command: "cargo build".to_string(),
+            exit_code: Some(101),
+            duration: std::time::Duration::from_secs(75),
+            stderr_tail: tail.into_lines(),
+            log: Some("/tmp/build.log".into()),
+            rerun_script: Some("/tmp/build.sh".into()),
+        };
+        assert_eq!(outcome.stderr_tail, vec!["  --> src/main.rs:4:5", "100%"]);
+        let toast = outcome.to_toast(&CommandToastOptions {
+            app_id: Toast::POWERSHELL_APP_ID.to_string(),
+            success_hero: Some("success.png".to_string()),
+            failure_hero: Some("failure.png".to_string()),
+        });
+        assert!(toast.validate().is_empty());
+        let xml = toast.to_xml();
+        assert!(xml.contains("<image src=\"failure.png\" placement=\"hero\"/>"));
+        assert!(xml.contains("<text hint-maxLines=\"2\">✘ cargo build failed with exit code 101</text><text>Finished in 1m 15s</text>"));
+        assert!(xml.contains("<action content=\"Rerun\" arguments=\"file:///tmp/build.sh\" activationType=\"protocol\" hint-buttonStyle=\"Critical\"/>"));
+        assert!(xml.contains("<action content=\"Open log\" arguments=\"file:///tmp/build.log\" activationType=\"protocol\"/>"));
+    }
+}
@@ -0,0 +1,128 @@
+//! A panic hook that tells the user the program crashed, for programs without a console.
+
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::command::file_uri;
+use crate::notification::{self, *};
+use crate::notifier::{platform_notifier, Notifier};
+
+/// Shows a toast with the panic message and location whenever a thread panics, with an "Open
+/// crash report" button for a report written to the temp directory.
+///
+/// The hook installed before runs first, so the usual message still reaches stderr. Nothing is
+/// shown where there is no notification platform, but the report is still written.
+pub fn install_panic_hook(app_id: &str) {
+    let notifier: Option<Arc<dyn Notifier>> = platform_notifier().ok().map(Arc::from);
+    install(app_id.to_string(), notifier);
+}
+
+/// Like [`install_panic_hook`], showing the toast through the notifier.
+pub fn install_panic_hook_with(app_id: &str, notifier: Arc<dyn Notifier>) {
+    install(app_id.to_string(), Some(notifier));
+}
+
+fn install(app_id: String, notifier: Option<Arc<dyn Notifier>>) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        // A panic in here would abort the process, so everything below only reports failures.
+        let report = report(info);
+        let path = write_report(&report);
+        if let Some(notifier) = &notifier {
+            let _ = notifier.show(&crash_toast(&app_id, info, path));
+        }
+    }));
+}
+
+fn message<'a>(info: &'a PanicHookInfo) -> &'a str {
+    match info.payload().downcast_ref::<&str>() {
+        Some(message) => message,
+        None => info.payload().downcast_ref::<String>().map(String::as_str).unwrap_or("Box<dyn Any>"),
+    }
+}
+
+fn program_name() -> String {
+    std::env::current_exe().ok()
+        .and_then(|exe| exe.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "The program".to_string())
+}
+
+fn report(info: &PanicHookInfo) -> String {
+    let mut report = String::new();
+    let thread = std::thread::current();
+    let _ = writeln!(report, "{} panicked in thread '{}'", program_name(), thread.name().unwrap_or("<unnamed>"));
+    if let Some(location) = info.location() {
+        let _ = writeln!(report, "at {}", location);
+    }
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default();
+    let _ = writeln!(report, "time: {} seconds since the Unix epoch", seconds);
+    let _ = writeln!(report, "\n{}\n\nbacktrace:\n{}", message(info), Backtrace::force_capture());
+    report
+}
+
+fn write_report(report: &str) -> Option<PathBuf> {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis()).unwrap_or_default();
+    let path = std::env::temp_dir().join(format!("{}-crash-{}-{}.txt", program_name(), std::process::id(), stamp));
+    std::fs::write(&path, report).ok().map(|_| path)
+}
+
+fn crash_toast(app_id: &str, info: &PanicHookInfo, report: Option<PathBuf>) -> Toast {
+    Toast {
+        app_id: if app_id.is_empty() { Toast::POWERSHELL_APP_ID.to_string() } else { app_id.to_string() },
+        binding_elems: vec![BindingElem {
+            title: (format!("{} crashed", program_name()), Some(1)),
+            elems: vec![BindingInnerElement::Text(message(info).to_string())],
+            attribution: info.location().map(|location| location.to_string()),
+            ..BindingElem::default()
+        }],
+        duration: notification::Duration::Long,
+        actions: report.iter().map(|path| ActionsElem::Action_(Action {
+            content: "Open crash report".to_string(),
+            arguments: file_uri(path),
+            activation_type: ActivationType::Protocol,
+            ..Action::default()
+        })).collect(),
+        ..Toast::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifier::MockNotifier;
+    use crate::tests::lock_global_state;
+
+    #[test]
+    fn test_panic_hook() {
+        let _global_state = lock_global_state();
+        let notifier = Arc::new(MockNotifier::new());
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        install_panic_hook_with("crash.test", notifier.clone());
+        let line = line!() + 1;
+        let result = std::panic::catch_unwind(|| panic!("disk {} is gone", "D:"));
+        std::panic::set_hook(previous);
+        assert!(result.is_err());
+
+        // Tests failing on other threads meanwhile have crash toasts of their own.
+        let shown: Vec<Toast> = notifier.shown().into_iter()
+            .filter(|toast| toast.binding_elems[0].elems.contains(&BindingInnerElement::Text("disk D: is gone".to_string())))
+            .collect();
+        assert_eq!(shown.len(), 1);
+        let binding = &shown[0].binding_elems[0];
+        assert!(binding.title.0.ends_with(" crashed"));
+        assert_eq!(binding.elems, vec![BindingInnerElement::Text("disk D: is gone".to_string())]);
+        assert!(binding.attribution.as_ref().unwrap().starts_with(&format!("{}:{}:", file!(), line)));
+        let ActionsElem::Action_(action) = &shown[0].actions[0] else { panic!("expected an action") };
+        assert_eq!(action.content, "Open crash report");
+        let report = action.arguments.trim_start_matches("file://");
+        let report = if cfg!(windows) { report.trim_start_matches('/') } else { report };
+        assert!(std::fs::read_to_string(report).unwrap().contains("disk D: is gone"));
+        std::fs::remove_file(report).unwrap();
+    }
+}
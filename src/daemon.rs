@@ -0,0 +1,606 @@
+//! A local daemon that shows toasts on behalf of other processes, such as services without a
+//! UI session.
+//!
+//! Clients connect to a Unix domain socket, or a named pipe on Windows, which only the user
+//! running the daemon may connect to from the same machine, and write one JSON [`Request`] per
+//! line. Every request is answered with one [`Response`] line. Requests are queued and delivered
+//! one at a time through the [`Notifier`] the daemon was started with.
+//! After a `subscribe` request the connection also receives an `event` response for everything
+//! that happens to the toasts the daemon showed.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use crate::notification::Toast;
+use crate::notifier::{prepare, Notifier, ToastEvent};
+use self::transport::{Listener, Stream};
+
+/// A line sent to the daemon, such as `{"op":"remove","id":"1f2c-3"}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    Show { toast: Toast },
+    Update { id: String, toast: Toast },
+    Remove { id: String },
+    /// Streams the events of every toast shown from now on to this connection. A connection that
+    /// falls far behind reading them is unsubscribed.
+    Subscribe,
+}
+
+/// A line sent by the daemon, such as `{"type":"ok","id":"1f2c-3"}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Ok {
+        /// The id of the toast a `show` request delivered.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    },
+    Error { message: String },
+    Event { event: ToastEvent },
+}
+
+/// Where the daemon listens unless told otherwise: the named pipe `\\.\pipe\win_toast` on
+/// Windows, and `win_toast.sock` in the runtime directory of the user elsewhere.
+pub fn default_address() -> PathBuf {
+    if cfg!(windows) {
+        return PathBuf::from(r"\\.\pipe\win_toast");
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => Path::new(&dir).join("win_toast.sock"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_else(|_| "default".to_string());
+            std::env::temp_dir().join(format!("win_toast-{}.sock", user))
+        }
+    }
+}
+
+struct Job {
+    request: Request,
+    reply: mpsc::Sender<Response>,
+}
+
+/// The most responses waiting to be written to a subscribed connection. A subscriber that falls
+/// further behind is dropped, so one that stopped reading does not hold up the others.
+const MAX_PENDING: usize = 256;
+
+type Subscriber = mpsc::SyncSender<Response>;
+
+pub struct Daemon {
+    listener: Listener,
+    address: PathBuf,
+    notifier: Arc<dyn Notifier>,
+}
+
+impl Daemon {
+    /// Starts listening on the address. A socket left behind by a daemon that is gone is
+    /// replaced, but one a daemon still listens on is not.
+    pub fn bind(address: impl AsRef<Path>, notifier: Arc<dyn Notifier>) -> io::Result<Daemon> {
+        let address = address.as_ref().to_path_buf();
+        Ok(Daemon { listener: Listener::bind(&address)?, address, notifier })
+    }
+
+    pub fn address(&self) -> &Path {
+        &self.address
+    }
+
+    /// Serves clients until accepting a connection fails.
+    pub fn run(mut self) -> io::Result<()> {
+        let (jobs, queue) = mpsc::channel();
+        let notifier = self.notifier.clone();
+        thread::spawn(move || deliver(&*notifier, queue));
+
+        let subscribed: Arc<Mutex<Vec<Subscriber>>> = Arc::default();
+        let events = self.notifier.subscribe();
+        let subscribers = subscribed.clone();
+        thread::spawn(move || {
+            for event in events {
+                let response = Response::Event { event };
+                // Connections that went away or stopped reading are dropped from the list.
+                subscribers.lock().unwrap_or_else(|e| e.into_inner())
+                    .retain(|subscriber| subscriber.try_send(response.clone()).is_ok());
+            }
+        });
+
+        loop {
+            let stream = self.listener.accept()?;
+            let (jobs, subscribed) = (jobs.clone(), subscribed.clone());
+            thread::spawn(move || serve(stream, jobs, subscribed));
+        }
+    }
+}
+
+fn serve(mut stream: Stream, jobs: mpsc::Sender<Job>, subscribed: Arc<Mutex<Vec<Subscriber>>>) -> io::Result<()> {
+    // Once the connection subscribes, a thread of its own writes the answers along with the events.
+    let mut outbox: Option<Subscriber> = None;
+    for line in BufReader::new(stream.try_clone()?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str(&line) {
+            Err(e) => Response::Error { message: format!("invalid request: {}", e) },
+            Ok(Request::Subscribe) if outbox.is_none() => {
+                let (subscriber, pending) = mpsc::sync_channel(MAX_PENDING);
+                let mut writer = stream.try_clone()?;
+                thread::spawn(move || {
+                    for response in pending {
+                        if write_line(&mut writer, &response).is_err() {
+                            break;
+                        }
+                    }
+                });
+                // Holding the list while queueing the answer keeps events from arriving before it,
+                // and the answer is queued without waiting as the channel is empty.
+                let mut subscribed = subscribed.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = subscriber.send(Response::Ok { id: None });
+                subscribed.push(subscriber.clone());
+                outbox = Some(subscriber);
+                continue;
+            }
+            Ok(Request::Subscribe) => Response::Ok { id: None },
+            Ok(request) => {
+                let (reply, response) = mpsc::channel();
+                let _ = jobs.send(Job { request, reply });
+                response.recv().unwrap_or_else(|_| Response::Error { message: "the daemon is shutting down".to_string() })
+            }
+        };
+        match &outbox {
+            Some(outbox) => {
+                if outbox.send(response).is_err() {
+                    break;
+                }
+            }
+            None => write_line(&mut stream, &response)?,
+        }
+    }
+    Ok(())
+}
+
+fn deliver(notifier: &dyn Notifier, queue: mpsc::Receiver<Job>) {
+    for Job { request, reply } in queue {
+        let result = match request {
+            Request::Show { toast } => prepare(toast).and_then(|toast| notifier.show(&toast).map_err(|e| e.to_string())).map(Some),
+            Request::Update { id, toast } => prepare(toast).and_then(|toast| notifier.update(&id, &toast).map_err(|e| e.to_string())).map(|_| None),
+            Request::Remove { id } => notifier.remove(&id).map_err(|e| e.to_string()).map(|_| None),
+            Request::Subscribe => unreachable!("subscriptions are handled by the connection"),
+        };
+        let _ = reply.send(match result {
+            Ok(id) => Response::Ok { id },
+            Err(message) => Response::Error { message },
+        });
+    }
+}
+
+fn write_line(writer: &mut Stream, response: &Response) -> io::Result<()> {
+    let mut line = serde_json::to_vec(response)?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+/// A connection to a [`Daemon`].
+pub struct Client {
+    reader: BufReader<Stream>,
+    writer: Stream,
+}
+
+impl Client {
+    pub fn connect(address: impl AsRef<Path>) -> io::Result<Client> {
+        let stream = transport::connect(address.as_ref())?;
+        Ok(Client { writer: stream.try_clone()?, reader: BufReader::new(stream) })
+    }
+
+    /// Shows a toast and returns its id.
+    pub fn show(&mut self, toast: &Toast) -> io::Result<String> {
+        self.request(&Request::Show { toast: toast.clone() })?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the daemon did not return an id"))
+    }
+
+    pub fn update(&mut self, id: &str, toast: &Toast) -> io::Result<()> {
+        self.request(&Request::Update { id: id.to_string(), toast: toast.clone() }).map(|_| ())
+    }
+
+    pub fn remove(&mut self, id: &str) -> io::Result<()> {
+        self.request(&Request::Remove { id: id.to_string() }).map(|_| ())
+    }
+
+    /// Turns the connection into a stream of the events of the toasts shown from now on.
+    pub fn subscribe(mut self) -> io::Result<Events> {
+        self.request(&Request::Subscribe)?;
+        Ok(Events { reader: self.reader })
+    }
+
+    fn request(&mut self, request: &Request) -> io::Result<Option<String>> {
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.writer.flush()?;
+        match read_response(&mut self.reader)? {
+            Some(Response::Ok { id }) => Ok(id),
+            Some(Response::Error { message }) => Err(io::Error::other(message)),
+            Some(Response::Event { .. }) => Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected event")),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+fn read_response(reader: &mut BufReader<Stream>) -> io::Result<Option<Response>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+/// The events a subscribed [`Client`] receives. Ends when the daemon goes away.
+pub struct Events {
+    reader: BufReader<Stream>,
+}
+
+impl Iterator for Events {
+    type Item = io::Result<ToastEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match read_response(&mut self.reader) {
+                Ok(Some(Response::Event { event })) => return Some(Ok(event)),
+                Ok(Some(_)) => {}
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+mod transport {
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+
+    pub type Stream = UnixStream;
+
+    pub struct Listener {
+        listener: UnixListener,
+        path: PathBuf,
+    }
+
+    impl Listener {
+        pub fn bind(path: &Path) -> io::Result<Listener> {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("a daemon already listens on {}", path.display())));
+            }
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            // The socket is bound in a directory only the user can enter and moved into place
+            // once only the user can connect to it, whatever the umask.
+            let staging = path.with_extension(format!("{}.tmp", std::process::id()));
+            fs::DirBuilder::new().mode(0o700).create(&staging)?;
+            let bound = bind_private(&staging.join("sock"), path);
+            let _ = fs::remove_file(staging.join("sock"));
+            fs::remove_dir(&staging)?;
+            Ok(Listener { listener: bound?, path: path.to_path_buf() })
+        }
+
+        pub fn accept(&mut self) -> io::Result<Stream> {
+            self.listener.accept().map(|(stream, _)| stream)
+        }
+    }
+
+    fn bind_private(staging: &Path, path: &Path) -> io::Result<UnixListener> {
+        let listener = UnixListener::bind(staging)?;
+        fs::set_permissions(staging, fs::Permissions::from_mode(0o600))?;
+        fs::rename(staging, path)?;
+        Ok(listener)
+    }
+
+    impl Drop for Listener {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    pub fn connect(path: &Path) -> io::Result<Stream> {
+        UnixStream::connect(path)
+    }
+}
+
+#[cfg(windows)]
+mod transport {
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, Read, Write};
+    use std::mem;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::{CloseHandle, BOOL, ERROR_BROKEN_PIPE, ERROR_IO_PENDING, ERROR_PIPE_CONNECTED, HANDLE, INVALID_HANDLE_VALUE};
+    use windows::Win32::Security::*;
+    use windows::Win32::Storage::FileSystem::{ReadFile, WriteFile, FILE_ALL_ACCESS, FILE_FLAGS_AND_ATTRIBUTES, FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED, PIPE_ACCESS_DUPLEX};
+    use windows::Win32::System::IO::{GetOverlappedResult, OVERLAPPED};
+    use windows::Win32::System::Pipes::*;
+    use windows::Win32::System::Threading::{CreateEventW, GetCurrentProcess, OpenProcessToken};
+
+    const ERROR_PIPE_BUSY: i32 = 231;
+    const SECURITY_DESCRIPTOR_REVISION: u32 = 1;
+
+    /// An end of a named pipe opened for overlapped I/O. Windows runs the synchronous reads and
+    /// writes of one pipe handle one after the other, so a connection blocked reading the next
+    /// request would keep events from being written to it.
+    pub struct Stream {
+        pipe: Arc<File>,
+    }
+
+    impl Stream {
+        pub fn try_clone(&self) -> io::Result<Stream> {
+            Ok(Stream { pipe: self.pipe.clone() })
+        }
+
+        fn handle(&self) -> HANDLE {
+            HANDLE(self.pipe.as_raw_handle() as isize)
+        }
+    }
+
+    impl Read for Stream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(u32::MAX as usize) as u32;
+            let handle = self.handle();
+            match overlapped(handle, |overlapped| unsafe { ReadFile(handle, Some(buf.as_mut_ptr().cast()), len, None, Some(overlapped)) }) {
+                Err(e) if e.raw_os_error() == Some(ERROR_BROKEN_PIPE.0 as i32) => Ok(0),
+                result => result.map(|read| read as usize),
+            }
+        }
+    }
+
+    impl Write for Stream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = buf.len().min(u32::MAX as usize) as u32;
+            let handle = self.handle();
+            overlapped(handle, |overlapped| unsafe { WriteFile(handle, Some(buf.as_ptr().cast()), len, None, Some(overlapped)) })
+                .map(|written| written as usize)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Starts an operation with its own event and waits for it to complete.
+    fn overlapped(handle: HANDLE, start: impl FnOnce(*mut OVERLAPPED) -> BOOL) -> io::Result<u32> {
+        let event = unsafe { CreateEventW(None, true, false, PCWSTR::null()) }.map_err(io::Error::from)?;
+        let mut overlapped = OVERLAPPED { hEvent: event, ..OVERLAPPED::default() };
+        let mut transferred = 0;
+        let result = if start(&mut overlapped).as_bool() || io::Error::last_os_error().raw_os_error() == Some(ERROR_IO_PENDING.0 as i32) {
+            if unsafe { GetOverlappedResult(handle, &overlapped, &mut transferred, true) }.as_bool() {
+                Ok(transferred)
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        } else {
+            Err(io::Error::last_os_error())
+        };
+        unsafe { CloseHandle(event) };
+        result
+    }
+
+    /// A named pipe server. The next instance of the pipe is created as soon as a client takes
+    /// the current one, so clients rarely find the pipe busy.
+    pub struct Listener {
+        name: Vec<u16>,
+        next: File,
+    }
+
+    impl Listener {
+        pub fn bind(path: &Path) -> io::Result<Listener> {
+            let name: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+            let next = create(&name, FILE_FLAG_FIRST_PIPE_INSTANCE)?;
+            Ok(Listener { name, next })
+        }
+
+        pub fn accept(&mut self) -> io::Result<Stream> {
+            let pipe = std::mem::replace(&mut self.next, create(&self.name, FILE_FLAGS_AND_ATTRIBUTES(0))?);
+            let handle = HANDLE(pipe.as_raw_handle() as isize);
+            match overlapped(handle, |overlapped| unsafe { ConnectNamedPipe(handle, Some(overlapped)) }) {
+                Err(e) if e.raw_os_error() != Some(ERROR_PIPE_CONNECTED.0 as i32) => Err(e),
+                _ => Ok(Stream { pipe: Arc::new(pipe) }),
+            }
+        }
+    }
+
+    /// Creates an instance of the pipe that only the user running the daemon can open, like the
+    /// 0600 socket elsewhere, and only from this machine.
+    fn create(name: &[u16], flags: FILE_FLAGS_AND_ATTRIBUTES) -> io::Result<File> {
+        let mut security = OwnerOnly::new()?;
+        let handle = unsafe {
+            CreateNamedPipeW(
+                PCWSTR(name.as_ptr()),
+                PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED | flags,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                4096,
+                4096,
+                0,
+                Some(&security.attributes()),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { File::from_raw_handle(handle.0 as _) })
+    }
+
+    /// A security descriptor whose access control list only allows the user of the process.
+    struct OwnerOnly {
+        descriptor: Box<SECURITY_DESCRIPTOR>,
+        // The descriptor points into the list, so it lives as long.
+        _acl: Vec<u32>,
+    }
+
+    impl OwnerOnly {
+        fn new() -> io::Result<OwnerOnly> {
+            let mut token = HANDLE::default();
+            check(unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) })?;
+            let mut length = 0;
+            unsafe { GetTokenInformation(token, TokenUser, None, 0, &mut length) };
+            let mut user = vec![0u64; (length as usize).div_ceil(8)];
+            let queried = check(unsafe { GetTokenInformation(token, TokenUser, Some(user.as_mut_ptr().cast()), length, &mut length) });
+            unsafe { CloseHandle(token) };
+            queried?;
+            let sid = unsafe { (*user.as_ptr().cast::<TOKEN_USER>()).User.Sid };
+
+            let size = mem::size_of::<ACL>() + mem::size_of::<ACCESS_ALLOWED_ACE>() + unsafe { GetLengthSid(sid) } as usize;
+            let mut acl = vec![0u32; size.div_ceil(4)];
+            let list = acl.as_mut_ptr().cast::<ACL>();
+            check(unsafe { InitializeAcl(list, (acl.len() * 4) as u32, ACL_REVISION.0) })?;
+            check(unsafe { AddAccessAllowedAce(list, ACL_REVISION.0, FILE_ALL_ACCESS.0, sid) })?;
+            let mut descriptor = Box::<SECURITY_DESCRIPTOR>::default();
+            let pointer = PSECURITY_DESCRIPTOR((&mut *descriptor as *mut SECURITY_DESCRIPTOR).cast());
+            check(unsafe { InitializeSecurityDescriptor(pointer, SECURITY_DESCRIPTOR_REVISION) })?;
+            check(unsafe { SetSecurityDescriptorDacl(pointer, true, Some(list), false) })?;
+            Ok(OwnerOnly { descriptor, _acl: acl })
+        }
+
+        fn attributes(&mut self) -> SECURITY_ATTRIBUTES {
+            SECURITY_ATTRIBUTES {
+                nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: (&mut *self.descriptor as *mut SECURITY_DESCRIPTOR).cast(),
+                bInheritHandle: false.into(),
+            }
+        }
+    }
+
+    fn check(result: BOOL) -> io::Result<()> {
+        if result.as_bool() { Ok(()) } else { Err(io::Error::last_os_error()) }
+    }
+
+    pub fn connect(path: &Path) -> io::Result<Stream> {
+        let mut attempts = 0;
+        loop {
+            match OpenOptions::new().read(true).write(true).custom_flags(FILE_FLAG_OVERLAPPED.0).open(path) {
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) && attempts < 50 => {
+                    attempts += 1;
+                    thread::sleep(Duration::from_millis(20));
+                }
+                result => return result.map(|pipe| Stream { pipe: Arc::new(pipe) }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::notification::*;
+    use crate::notifier::MockNotifier;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_subscriber_receives_events_without_writing() {
+        let address: PathBuf = if cfg!(windows) {
+            format!(r"\\.\pipe\win_toast-events-{}", std::process::id()).into()
+        } else {
+            std::env::temp_dir().join(format!("win_toast-events-{}.sock", std::process::id()))
+        };
+        let notifier = Arc::new(MockNotifier::new());
+        let daemon = Daemon::bind(&address, notifier.clone()).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&address).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        thread::spawn(move || daemon.run());
+
+        let mut events = Client::connect(&address).unwrap().subscribe().unwrap();
+        let toast = Toast {
+            binding_elems: vec![BindingElem { title: ("Deployed".to_string(), None), ..BindingElem::default() }],
+            ..Toast::default()
+        };
+        let id = Client::connect(&address).unwrap().show(&toast).unwrap();
+        notifier.activate(&id, "open", BTreeMap::new());
+
+        // The subscriber only reads, so the event must arrive while the daemon waits for it to write.
+        let (sender, received) = mpsc::channel();
+        thread::spawn(move || sender.send(events.next()));
+        let event = received.recv_timeout(Duration::from_secs(10)).expect("the event did not arrive");
+        assert_eq!(event.unwrap().unwrap(), ToastEvent::Activated { id, arguments: "open".to_string(), user_input: BTreeMap::new() });
+    }
+
+    #[test]
+    fn test_stalled_subscriber_is_dropped() {
+        let address: PathBuf = if cfg!(windows) {
+            format!(r"\\.\pipe\win_toast-stalled-{}", std::process::id()).into()
+        } else {
+            std::env::temp_dir().join(format!("win_toast-stalled-{}.sock", std::process::id()))
+        };
+        let notifier = Arc::new(MockNotifier::new());
+        let daemon = Daemon::bind(&address, notifier.clone()).unwrap();
+        thread::spawn(move || daemon.run());
+
+        // Subscribes and reads the answer, but none of the events.
+        let mut stalled = transport::connect(&address).unwrap();
+        stalled.write_all(b"{\"op\":\"subscribe\"}\n").unwrap();
+        BufReader::new(stalled.try_clone().unwrap()).read_line(&mut String::new()).unwrap();
+        let events = Client::connect(&address).unwrap().subscribe().unwrap();
+
+        let (sender, received) = mpsc::channel();
+        thread::spawn(move || events.for_each(|event| sender.send(event.is_ok()).unwrap()));
+        // Far more than the stalled connection takes before it is dropped, each one read by the
+        // other subscriber before the next is sent.
+        let arguments = "x".repeat(16 * 1024);
+        for _ in 0..MAX_PENDING + 64 {
+            notifier.activate("1", &arguments, BTreeMap::new());
+            assert_eq!(received.recv_timeout(Duration::from_secs(10)), Ok(true), "the other subscriber was held up");
+        }
+        drop(stalled);
+    }
+
+    #[test]
+    fn test_daemon() {
+        let address = if cfg!(windows) {
+            format!(r"\\.\pipe\win_toast-test-{}", std::process::id()).into()
+        } else {
+            std::env::temp_dir().join(format!("win_toast-test-{}.sock", std::process::id()))
+        };
+        let notifier = Arc::new(MockNotifier::new());
+        let daemon = Daemon::bind(&address, notifier.clone()).unwrap();
+        std::thread::spawn(move || daemon.run());
+
+        let mut events = Client::connect(&address).unwrap().subscribe().unwrap();
+        let mut client = Client::connect(&address).unwrap();
+        let id = client.show(&sample_toast()).unwrap();
+        let mut updated = sample_toast();
+        updated.binding_elems[0].title.0 = "Build finished".to_string();
+        client.update(&id, &updated).unwrap();
+        assert_eq!(notifier.current(), vec![(id.clone(), updated)]);
+        assert!(client.show(&Toast::default()).unwrap_err().to_string().contains("a toast needs a binding"));
+        assert!(client.remove("missing").is_err());
+
+        notifier.activate(&id, "open", BTreeMap::from([("reply".to_string(), "thanks".to_string())]));
+        assert_eq!(events.next().unwrap().unwrap(), ToastEvent::Activated {
+            id: id.clone(),
+            arguments: "open".to_string(),
+            user_input: BTreeMap::from([("reply".to_string(), "thanks".to_string())]),
+        });
+        client.remove(&id).unwrap();
+        assert!(notifier.current().is_empty());
+        assert_eq!(notifier.shown().len(), 2);
+    }
+}
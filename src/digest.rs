@@ -0,0 +1,523 @@
+//! Digests of low-priority toasts.
+//!
+//! A [`DigestNotifier`] does not show the toasts on the channels it is given right away. It
+//! keeps them in a [`DigestStore`], so they survive a restart, and [`DigestNotifier::tick`] shows
+//! them every interval as one summary per app, with buttons to show them all one by one or to mute
+//! the channels for the rest of the day. The store keeps the summaries too, until they are
+//! opened, dismissed or expire. Windows only tells the process that showed a toast about clicks on
+//! it, so after a restart the app passes the arguments it is launched with to
+//! [`DigestNotifier::handle_activation`] for the buttons of earlier summaries to work. A pump
+//! thread started with [`DigestNotifier::spawn_pump`] calls `tick` periodically.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+
+use crate::channel::DEFAULT_CHANNEL;
+use crate::clock::{self, Clock, SystemClock};
+use crate::notification::*;
+use crate::notifier::{next_id, DismissReason, Notifier, NotifyError, Subscribers, ToastEvent};
+
+/// The arguments of the "Open all" button of a digest, followed by `?id=` and the id of the digest.
+pub const OPEN_ALL: &str = "win_toast:digest/open-all";
+/// The arguments of the "Mute for today" button of a digest, followed by `?id=` and the id of the
+/// digest.
+pub const MUTE_TODAY: &str = "win_toast:digest/mute-today";
+
+/// How long the buttons of a digest work, which is as long as Windows keeps it in the action
+/// center by default.
+const DIGEST_EXPIRY: Duration = Duration::from_secs(3 * 24 * 3600);
+
+#[derive(Clone, Debug)]
+pub struct DigestConfig {
+    /// How often the collected toasts are shown.
+    pub interval: Duration,
+    /// The channels whose toasts are collected. Toasts without a channel are on
+    /// [`DEFAULT_CHANNEL`].
+    pub channels: Vec<String>,
+}
+
+impl Default for DigestConfig {
+    fn default() -> DigestConfig {
+        DigestConfig { interval: Duration::from_secs(15 * 60), channels: Vec::new() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CollectedToast {
+    pub id: String,
+    pub toast: Toast,
+    pub collected_at: SystemTime,
+}
+
+/// A digest on screen or in the action center, with the toasts "Open all" shows.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShownDigest {
+    /// The id in the arguments of its buttons.
+    pub id: String,
+    /// The id the inner notifier returned, which its events carry.
+    pub toast_id: String,
+    pub toasts: Vec<Toast>,
+    pub expires_at: SystemTime,
+}
+
+/// What a digest notifier keeps across restarts.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigestBuffer {
+    pub collected: Vec<CollectedToast>,
+    pub last_sent: Option<SystemTime>,
+    /// The toasts of the digest channels are dropped until then.
+    pub muted_until: Option<SystemTime>,
+    /// The digests that were neither opened nor dismissed yet, and have not expired.
+    pub shown: Vec<ShownDigest>,
+}
+
+pub struct DigestStore {
+    path: PathBuf,
+}
+
+impl DigestStore {
+    /// Opens the buffer kept in the JSON file at `path`, which is created on the first change.
+    pub fn open(path: impl Into<PathBuf>) -> DigestStore {
+        DigestStore { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn load(&self) -> io::Result<DigestBuffer> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(DigestBuffer::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, buffer: &DigestBuffer) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(buffer)?)?;
+        fs::rename(tmp, &self.path)
+    }
+}
+
+/// What became of a toast shown through a [`DigestNotifier`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Digested {
+    /// The toast is not on a digest channel and was shown as usual.
+    Shown(String),
+    /// The toast waits for the next digest.
+    Collected(String),
+    /// The digest channels are muted for today and the toast was dropped.
+    Muted(String),
+}
+
+impl Digested {
+    pub fn id(&self) -> &str {
+        match self {
+            Digested::Shown(id) | Digested::Collected(id) | Digested::Muted(id) => id,
+        }
+    }
+}
+
+struct State {
+    buffer: DigestBuffer,
+}
+
+struct Shared {
+    inner: Arc<dyn Notifier>,
+    config: DigestConfig,
+    clock: Arc<dyn Clock>,
+    store: DigestStore,
+    state: Mutex<State>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Handles the buttons of a digest. Returns whether the arguments were those of one.
+    fn clicked(&self, arguments: &str) -> bool {
+        let Some((action, id)) = parse_arguments(arguments) else { return false };
+        let mut state = self.lock();
+        let digest = take_digest(&mut state.buffer, |digest| digest.id == id);
+        if action == MUTE_TODAY {
+            state.buffer.muted_until = Some(end_of_day(self.clock.now()));
+            state.buffer.collected.clear();
+        } else if digest.is_none() {
+            return true;
+        }
+        let _ = self.store.save(&state.buffer);
+        drop(state);
+        if let (OPEN_ALL, Some(digest)) = (action, digest) {
+            for toast in digest.toasts {
+                let _ = self.inner.show(&toast);
+            }
+        }
+        true
+    }
+
+    /// A digest clicked in any way is done with.
+    fn activated(&self, toast_id: &str, arguments: &str) {
+        if !self.clicked(arguments) {
+            self.done_with(toast_id);
+        }
+    }
+
+    /// Forgets a digest the user clicked or closed. One that timed out is still in the action
+    /// center.
+    fn done_with(&self, toast_id: &str) {
+        let mut state = self.lock();
+        if take_digest(&mut state.buffer, |digest| digest.toast_id == toast_id).is_some() {
+            let _ = self.store.save(&state.buffer);
+        }
+    }
+}
+
+/// The button and the digest id of the arguments of a digest button.
+fn parse_arguments(arguments: &str) -> Option<(&str, &str)> {
+    let (action, id) = arguments.split_once("?id=")?;
+    [OPEN_ALL, MUTE_TODAY].into_iter().find(|known| *known == action).map(|action| (action, id))
+}
+
+fn take_digest(buffer: &mut DigestBuffer, matches: impl Fn(&ShownDigest) -> bool) -> Option<ShownDigest> {
+    let index = buffer.shown.iter().position(matches)?;
+    Some(buffer.shown.remove(index))
+}
+
+fn unsaved(e: io::Error) -> NotifyError {
+    NotifyError::Platform(format!("the digest could not be saved: {}", e))
+}
+
+/// The next midnight in the system time zone.
+fn end_of_day(now: SystemTime) -> SystemTime {
+    let Ok(now) = Timestamp::try_from(now) else { return now };
+    let today = now.to_zoned(TimeZone::system()).date();
+    today.tomorrow().and_then(|tomorrow| tomorrow.to_zoned(TimeZone::system()))
+        .map(|midnight| SystemTime::from(midnight.timestamp()))
+        .unwrap_or_else(|_| SystemTime::from(now) + Duration::from_secs(24 * 3600))
+}
+
+pub struct DigestNotifier {
+    shared: Arc<Shared>,
+    subscribers: Subscribers,
+}
+
+impl DigestNotifier {
+    /// Collects into the buffer kept in the store, picking up what was collected before.
+    pub fn new(inner: Arc<dyn Notifier>, config: DigestConfig, store: DigestStore) -> io::Result<DigestNotifier> {
+        DigestNotifier::with_clock(inner, config, store, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(inner: Arc<dyn Notifier>, config: DigestConfig, store: DigestStore, clock: Arc<dyn Clock>) -> io::Result<DigestNotifier> {
+        let buffer = store.load()?;
+        let events = inner.subscribe();
+        let shared = Arc::new(Shared { inner, config, clock, store, state: Mutex::new(State { buffer }) });
+        let subscribers = Subscribers::default();
+        let (handler, forward) = (shared.clone(), subscribers.clone());
+        thread::spawn(move || {
+            for event in events {
+                match &event {
+                    ToastEvent::Activated { id, arguments, .. } => handler.activated(id, arguments),
+                    ToastEvent::Dismissed { id, reason: DismissReason::UserCanceled | DismissReason::ApplicationHidden } => handler.done_with(id),
+                    _ => {}
+                }
+                forward.send(event);
+            }
+        });
+        Ok(DigestNotifier { shared, subscribers })
+    }
+
+    pub fn submit(&self, toast: &Toast) -> Result<Digested, NotifyError> {
+        let channel = toast.channel.as_deref().unwrap_or(DEFAULT_CHANNEL);
+        if !self.shared.config.channels.iter().any(|digest| digest == channel) {
+            return self.shared.inner.show(toast).map(Digested::Shown);
+        }
+        let now = self.shared.clock.now();
+        let id = next_id();
+        let mut state = self.shared.lock();
+        if state.buffer.muted_until.is_some_and(|until| now < until) {
+            return Ok(Digested::Muted(id));
+        }
+        state.buffer.collected.push(CollectedToast { id: id.clone(), toast: toast.clone(), collected_at: now });
+        self.shared.store.save(&state.buffer).map_err(unsaved)?;
+        Ok(Digested::Collected(id))
+    }
+
+    /// Handles the arguments the app was launched with, for the buttons of a digest shown before
+    /// a restart. Returns whether they were those of a digest button.
+    pub fn handle_activation(&self, arguments: &str) -> bool {
+        self.shared.clicked(arguments)
+    }
+
+    /// How many toasts wait for the next digest.
+    pub fn len(&self) -> usize {
+        self.shared.lock().buffer.collected.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Shows the collected toasts if the interval has passed since the last digest, as one
+    /// summary per app. Returns how many summaries were shown.
+    pub fn tick(&self) -> usize {
+        let now = self.shared.clock.now();
+        let collected = {
+            let mut state = self.shared.lock();
+            let due = state.buffer.last_sent.is_none_or(|last| now.duration_since(last).unwrap_or_default() >= self.shared.config.interval);
+            if !due || state.buffer.collected.is_empty() {
+                return 0;
+            }
+            state.buffer.last_sent = Some(now);
+            std::mem::take(&mut state.buffer.collected)
+        };
+        let mut by_app: BTreeMap<String, Vec<CollectedToast>> = BTreeMap::new();
+        for item in collected {
+            by_app.entry(item.toast.app_id.clone()).or_default().push(item);
+        }
+        let mut shown = Vec::new();
+        let mut unsent = Vec::new();
+        for (app_id, items) in by_app {
+            let id = next_id();
+            match self.shared.inner.show(&digest_toast(&app_id, &id, &items)) {
+                Ok(toast_id) => shown.push(ShownDigest {
+                    id,
+                    toast_id,
+                    toasts: items.into_iter().map(|item| item.toast).collect(),
+                    expires_at: now + DIGEST_EXPIRY,
+                }),
+                Err(_) => unsent.extend(items),
+            }
+        }
+        let count = shown.len();
+        let mut state = self.shared.lock();
+        state.buffer.shown.retain(|digest| digest.expires_at > now);
+        state.buffer.shown.extend(shown);
+        // What could not be shown goes into the next digest.
+        state.buffer.collected.splice(0..0, unsent);
+        let _ = self.shared.store.save(&state.buffer);
+        count
+    }
+
+    /// Calls [`tick`](DigestNotifier::tick) every `interval` until the notifier is dropped.
+    pub fn spawn_pump(self: &Arc<Self>, interval: Duration) -> thread::JoinHandle<()> {
+        clock::spawn_pump(self, interval, |notifier| {
+            notifier.tick();
+        })
+    }
+}
+
+/// A summary with a row for each of the first five toasts, giving the title, the first line of
+/// text and the time each came.
+fn digest_toast(app_id: &str, id: &str, items: &[CollectedToast]) -> Toast {
+    let mut elems = Vec::new();
+    for item in items.iter().take(5) {
+        let binding = item.toast.binding_elems.first();
+        let title = binding.map(|binding| binding.title.0.clone()).unwrap_or_default();
+        let text = binding.and_then(|binding| binding.elems.iter().find_map(|elem| match elem {
+            BindingInnerElement::Text(text) => Some(text.clone()),
+            _ => None,
+        }));
+        let mut content = vec![SubgroupElement::Text_(InnerText { text: title, hint_style: TextStyle::Base, ..InnerText::default() })];
+        if let Some(text) = text {
+            content.push(SubgroupElement::Text_(InnerText { text, hint_style: TextStyle::CaptionSubtle, ..InnerText::default() }));
+        }
+        let time = Timestamp::try_from(item.collected_at)
+            .map(|at| at.to_zoned(TimeZone::system()).strftime("%H:%M").to_string())
+            .unwrap_or_default();
+        let time = vec![SubgroupElement::Text_(InnerText {
+            text: time,
+            hint_style: TextStyle::CaptionSubtle,
+            hint_align: TextAlign::Right,
+            ..InnerText::default()
+        })];
+        elems.push(BindingInnerElement::Group(vec![content, time]));
+    }
+    if items.len() > 5 {
+        elems.push(BindingInnerElement::Text(format!("and {} more", items.len() - 5)));
+    }
+    let button = |content: &str, action: &str| ActionsElem::Action_(Action {
+        content: content.to_string(),
+        arguments: format!("{}?id={}", action, id),
+        ..Action::default()
+    });
+    Toast {
+        app_id: app_id.to_string(),
+        binding_elems: vec![BindingElem {
+            title: (format!("{} notifications", items.len()), None),
+            elems,
+            ..BindingElem::default()
+        }],
+        actions: vec![button("Open all", OPEN_ALL), button("Mute for today", MUTE_TODAY)],
+        ..Toast::default()
+    }
+}
+
+impl Notifier for DigestNotifier {
+    /// Submits the toast and returns its id, whatever became of it.
+    fn show(&self, toast: &Toast) -> Result<String, NotifyError> {
+        self.submit(toast).map(|digested| digested.id().to_string())
+    }
+
+    /// Replaces a collected toast, or updates a toast that was shown.
+    fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError> {
+        let mut state = self.shared.lock();
+        match state.buffer.collected.iter_mut().find(|item| item.id == id) {
+            Some(item) => {
+                item.toast = toast.clone();
+                self.shared.store.save(&state.buffer).map_err(unsaved)
+            }
+            None => {
+                drop(state);
+                self.shared.inner.update(id, toast)
+            }
+        }
+    }
+
+    /// Drops a collected toast, or removes a toast that was shown.
+    fn remove(&self, id: &str) -> Result<(), NotifyError> {
+        let mut state = self.shared.lock();
+        let before = state.buffer.collected.len();
+        state.buffer.collected.retain(|item| item.id != id);
+        if state.buffer.collected.len() < before {
+            return self.shared.store.save(&state.buffer).map_err(unsaved);
+        }
+        drop(state);
+        self.shared.inner.remove(id)
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+        self.subscribers.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::notifier::MockNotifier;
+    use crate::tests::sample_toast;
+
+    fn news(title: &str) -> Toast {
+        Toast {
+            channel: Some("news".to_string()),
+            binding_elems: vec![BindingElem { title: (title.to_string(), None), ..BindingElem::default() }],
+            ..Toast::default()
+        }
+    }
+
+    /// The arguments of a button of the digest last shown.
+    fn button(notifier: &MockNotifier, content: &str) -> (String, String) {
+        let (id, toast) = notifier.current().pop().unwrap();
+        let arguments = toast.actions.iter().find_map(|action| match action {
+            ActionsElem::Action_(action) if action.content == content => Some(action.arguments.clone()),
+            _ => None,
+        });
+        (id, arguments.unwrap())
+    }
+
+    #[test]
+    fn test_shown_digests_are_kept_until_done_with() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("win_toast-digest-shown-{}.json", std::process::id()));
+        let clock = Arc::new(FakeClock::default());
+        let config = DigestConfig { interval: Duration::from_secs(600), channels: vec!["news".to_string()] };
+        let open = |notifier: &Arc<MockNotifier>| DigestNotifier::with_clock(notifier.clone(), config.clone(), DigestStore::open(&path), clock.clone());
+
+        let notifier = Arc::new(MockNotifier::new());
+        let digest = open(&notifier)?;
+        let events = digest.subscribe();
+        digest.submit(&news("a")).unwrap();
+        assert_eq!(digest.tick(), 1);
+        let (_, open_all) = button(&notifier, "Open all");
+        assert!(open_all.starts_with(&format!("{}?id=", OPEN_ALL)));
+        clock.advance(Duration::from_secs(600));
+        digest.submit(&news("b")).unwrap();
+        assert_eq!(digest.tick(), 1);
+        let (closed, _) = button(&notifier, "Open all");
+        notifier.dismiss(&closed, DismissReason::UserCanceled);
+        events.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(DigestStore::open(&path).load()?.shown.len(), 1);
+        drop(digest);
+
+        // "Open all" still works when the app is launched with its arguments after a restart, and
+        // the digest is forgotten once opened.
+        let notifier = Arc::new(MockNotifier::new());
+        let digest = open(&notifier)?;
+        assert!(!digest.handle_activation("open"));
+        assert!(digest.handle_activation(&open_all));
+        assert_eq!(notifier.shown().pop().unwrap().binding_elems[0].title.0, "a");
+        assert!(DigestStore::open(&path).load()?.shown.is_empty());
+        assert!(digest.handle_activation(&open_all));
+        assert_eq!(notifier.shown().len(), 1);
+
+        // One nobody reacts to expires.
+        clock.advance(Duration::from_secs(600));
+        digest.submit(&news("c")).unwrap();
+        digest.tick();
+        clock.advance(DIGEST_EXPIRY);
+        digest.submit(&news("d")).unwrap();
+        digest.tick();
+        let shown = DigestStore::open(&path).load()?.shown;
+        assert_eq!(shown.iter().map(|digest| digest.toasts[0].binding_elems[0].title.0.as_str()).collect::<Vec<_>>(), vec!["d"]);
+        fs::remove_file(path)
+    }
+
+    #[test]
+    fn test_digest() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("win_toast-digest-{}.json", std::process::id()));
+        let notifier = Arc::new(MockNotifier::new());
+        let clock = Arc::new(FakeClock::default());
+        let config = DigestConfig { interval: Duration::from_secs(600), channels: vec!["news".to_string()] };
+        let news = |title: &str| Toast {
+            channel: Some("news".to_string()),
+            binding_elems: vec![BindingElem { title: (title.to_string(), None), ..BindingElem::default() }],
+            ..sample_toast()
+        };
+
+        let digest = DigestNotifier::with_clock(notifier.clone(), config.clone(), DigestStore::open(&path), clock.clone())?;
+        assert!(matches!(digest.submit(&sample_toast()), Ok(Digested::Shown(_))));
+        assert!(matches!(digest.submit(&news("a")), Ok(Digested::Collected(_))));
+        drop(digest);
+        // The buffer is picked up again after a restart.
+        let digest = DigestNotifier::with_clock(notifier.clone(), config, DigestStore::open(&path), clock.clone())?;
+        let events = digest.subscribe();
+        digest.submit(&news("b")).unwrap();
+        assert_eq!(digest.len(), 2);
+        assert_eq!(digest.tick(), 1);
+        let (summary_id, summary) = notifier.current().pop().unwrap();
+        assert_eq!(summary.binding_elems[0].title.0, "2 notifications");
+        assert!(matches!(&summary.binding_elems[0].elems[1], BindingInnerElement::Group(subgroups) if subgroups.len() == 2));
+
+        notifier.activate(&summary_id, &button(&notifier, "Open all").1, BTreeMap::new());
+        events.recv_timeout(Duration::from_secs(5)).unwrap();
+        let titles: Vec<String> = notifier.shown().iter().rev().take(2).map(|toast| toast.binding_elems[0].title.0.clone()).collect();
+        assert_eq!(titles, vec!["b", "a"]);
+        digest.submit(&news("c")).unwrap();
+        assert_eq!(digest.tick(), 0);
+        clock.advance(Duration::from_secs(600));
+        assert_eq!(digest.tick(), 1);
+        let (summary_id, mute_today) = button(&notifier, "Mute for today");
+        notifier.activate(&summary_id, &mute_today, BTreeMap::new());
+        events.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(digest.submit(&news("d")), Ok(Digested::Muted(_))));
+        clock.advance(Duration::from_secs(24 * 3600));
+        assert!(matches!(digest.submit(&news("e")), Ok(Digested::Collected(_))));
+        std::fs::remove_file(path)
+    }
+}
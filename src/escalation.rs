@@ -0,0 +1,417 @@
+//! Escalation of urgent toasts nobody reacts to.
+//!
+//! An [`Escalator`] watches the toasts shown through it whose scenario the policy covers. When
+//! one is neither clicked nor closed by the user in time, the steps of the policy are taken in
+//! turn: it is shown again in place of the last one, louder or with a more insistent scenario,
+//! and finally an [`EscalationHook`] can tell someone or something else, such as a webhook. The
+//! steps are taken by [`Escalator::tick`], which a pump thread started with
+//! [`Escalator::spawn_pump`] calls periodically.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serde_json::json;
+
+use crate::clock::{self, Clock, SystemClock};
+use crate::notification::*;
+use crate::notifier::{next_id, DismissReason, Notifier, NotifyError, Subscribers, ToastEvent, Tracked};
+
+/// Tells someone about a toast the user did not react to.
+///
+/// Closures taking the id and the toast can be used as hooks too.
+pub trait EscalationHook: Send + Sync {
+    /// Called with the id the escalator knows the toast by and its content as last shown.
+    fn escalate(&self, id: &str, toast: &Toast) -> Result<(), String>;
+}
+
+impl<F> EscalationHook for F
+    where F: Fn(&str, &Toast) -> Result<(), String> + Send + Sync {
+    fn escalate(&self, id: &str, toast: &Toast) -> Result<(), String> {
+        self(id, toast)
+    }
+}
+
+/// How long [`WebhookHook`] waits to connect, and for each write and read.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A hook that posts `{"id":…,"toast":…}` as JSON to a plain `http://` URL, with a bearer token
+/// if one is given. Any 2xx status counts as delivered.
+#[derive(Clone, Debug)]
+pub struct WebhookHook {
+    pub url: String,
+    pub token: Option<String>,
+}
+
+impl WebhookHook {
+    pub fn new(url: impl Into<String>) -> WebhookHook {
+        WebhookHook { url: url.into(), token: None }
+    }
+
+    fn post(&self, body: &str) -> io::Result<u16> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not an http:// URL", self.url));
+        let rest = self.url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+        if authority.is_empty() {
+            return Err(invalid());
+        }
+        let address = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+        let mut stream = connect(&address)?;
+        stream.set_read_timeout(Some(WEBHOOK_TIMEOUT))?;
+        stream.set_write_timeout(Some(WEBHOOK_TIMEOUT))?;
+        let mut head = format!("POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            path, authority, body.len());
+        if let Some(token) = &self.token {
+            head.push_str(&format!("Authorization: Bearer {}\r\n", token));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body.as_bytes())?;
+        let mut reply = String::new();
+        stream.take(1 << 16).read_to_string(&mut reply)?;
+        reply.split_whitespace().nth(1).and_then(|status| status.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed HTTP reply"))
+    }
+}
+
+/// Connects to the first address the host resolves to that answers in time.
+fn connect(address: &str) -> io::Result<TcpStream> {
+    let mut last = io::Error::new(io::ErrorKind::NotFound, format!("{} resolves to no address", address));
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, WEBHOOK_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last = e,
+        }
+    }
+    Err(last)
+}
+
+impl EscalationHook for WebhookHook {
+    fn escalate(&self, id: &str, toast: &Toast) -> Result<(), String> {
+        let body = json!({ "id": id, "toast": toast }).to_string();
+        match self.post(&body) {
+            Ok(status) if (200..300).contains(&status) => Ok(()),
+            Ok(status) => Err(format!("{} answered with status {}", self.url, status)),
+            Err(e) => Err(format!("{} could not be reached: {}", self.url, e)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum EscalationStep {
+    /// Shows the toast again in place of the last one. The toast is given the audio and the
+    /// scenario that are set.
+    Resend {
+        /// How long after the previous step, or after the toast was shown.
+        after: Duration,
+        /// The elements are (loop, silent and src), as in [`Toast::audio`].
+        audio: Option<(bool, bool, SoundSrc)>,
+        scenario: Option<Scenario>,
+    },
+    Hook {
+        /// How long after the previous step, or after the toast was shown.
+        after: Duration,
+        hook: Arc<dyn EscalationHook>,
+    },
+}
+
+impl EscalationStep {
+    fn after(&self) -> Duration {
+        match self {
+            EscalationStep::Resend { after, .. } | EscalationStep::Hook { after, .. } => *after,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EscalationPolicy {
+    /// The toasts with these scenarios are escalated, others are only passed on.
+    pub scenarios: Vec<Scenario>,
+    pub steps: Vec<EscalationStep>,
+}
+
+impl Default for EscalationPolicy {
+    /// Urgent and alarm toasts are shown again as a looping alarm after five minutes.
+    fn default() -> EscalationPolicy {
+        EscalationPolicy {
+            scenarios: vec![Scenario::Urgent, Scenario::Alarm],
+            steps: vec![EscalationStep::Resend {
+                after: Duration::from_secs(300),
+                audio: Some((true, false, SoundSrc::Alarm)),
+                scenario: Some(Scenario::Alarm),
+            }],
+        }
+    }
+}
+
+struct Escalation {
+    /// The content as last shown.
+    toast: Toast,
+    /// The next step to take.
+    step: usize,
+    due: SystemTime,
+}
+
+#[derive(Default)]
+struct State {
+    /// The current ids of the inner notifier for the ids of the escalator, linked to those of
+    /// replaced toasts too. Toasts still escalating are never forgotten.
+    inner_ids: Tracked<String>,
+    escalations: HashMap<String, Escalation>,
+    /// How many toasts the inner notifier is showing.
+    showing: usize,
+}
+
+impl State {
+    fn shown(&mut self, id: String, inner_id: String) {
+        let escalations = &self.escalations;
+        self.inner_ids.insert(id.clone(), inner_id.clone(), |id, _| escalations.contains_key(id));
+        self.inner_ids.link(&id, inner_id);
+    }
+}
+
+pub struct Escalator {
+    inner: Arc<dyn Notifier>,
+    policy: EscalationPolicy,
+    clock: Arc<dyn Clock>,
+    state: Arc<Mutex<State>>,
+    /// Notified when the inner notifier has shown a toast.
+    shown: Arc<Condvar>,
+    subscribers: Subscribers,
+}
+
+impl Escalator {
+    pub fn new(inner: Arc<dyn Notifier>, policy: EscalationPolicy) -> Escalator {
+        Escalator::with_clock(inner, policy, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(inner: Arc<dyn Notifier>, policy: EscalationPolicy, clock: Arc<dyn Clock>) -> Escalator {
+        let events = inner.subscribe();
+        let state: Arc<Mutex<State>> = Arc::default();
+        let subscribers = Subscribers::default();
+        let shown: Arc<Condvar> = Arc::default();
+        let (watched, forward, showing) = (state.clone(), subscribers.clone(), shown.clone());
+        // The escalation stops before subscribers learn of the event, so they never see a resend
+        // that follows it. An event of an unknown id waits while toasts are being shown, so the
+        // event of one finds its id even when it comes before show returns; the events of toasts
+        // shown past the escalator are passed on as they are.
+        thread::spawn(move || {
+            for event in events {
+                let mut state = watched.lock().unwrap_or_else(|e| e.into_inner());
+                let id = loop {
+                    if let Some(id) = state.inner_ids.by_inner(event.id()) {
+                        break Some(id.clone());
+                    }
+                    if state.showing == 0 {
+                        break None;
+                    }
+                    state = showing.wait(state).unwrap_or_else(|e| e.into_inner());
+                };
+                let Some(id) = id else {
+                    drop(state);
+                    forward.send(event);
+                    continue;
+                };
+                if matches!(event, ToastEvent::Activated { .. } | ToastEvent::Dismissed { reason: DismissReason::UserCanceled, .. }) {
+                    state.escalations.remove(&id);
+                }
+                drop(state);
+                forward.send(event.with_id(id));
+            }
+        });
+        Escalator { inner, policy, clock, state, shown, subscribers }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Shows the toast through the inner notifier outside the lock and records what became of it
+    /// under the lock, before the events waiting for the toast are let through.
+    fn show_inner(&self, toast: &Toast, record: impl FnOnce(&mut State, String)) -> Result<(), NotifyError> {
+        self.lock().showing += 1;
+        let result = self.inner.show(toast);
+        let mut state = self.lock();
+        state.showing -= 1;
+        let result = result.map(|inner_id| record(&mut state, inner_id));
+        drop(state);
+        self.shown.notify_all();
+        result
+    }
+
+    /// Whether the toast with the id still has steps ahead of it.
+    pub fn is_escalating(&self, id: &str) -> bool {
+        self.lock().escalations.contains_key(id)
+    }
+
+    /// Takes the steps that are due by now. Returns how many were taken. A step that fails is
+    /// reported to subscribers as [`ToastEvent::Failed`].
+    pub fn tick(&self) -> usize {
+        let now = self.clock.now();
+        let due: Vec<(String, Toast, usize)> = self.lock().escalations.iter()
+            .filter(|(_, escalation)| escalation.due <= now)
+            .map(|(id, escalation)| (id.clone(), escalation.toast.clone(), escalation.step))
+            .collect();
+        let mut taken = 0;
+        for (id, mut toast, step) in due {
+            match &self.policy.steps[step] {
+                EscalationStep::Resend { audio, scenario, .. } => {
+                    if audio.is_some() {
+                        toast.audio = audio.clone();
+                    }
+                    if let Some(scenario) = scenario {
+                        toast.scenario = scenario.clone();
+                    }
+                    // The user may have reacted since the due steps were collected.
+                    if !self.is_escalating(&id) {
+                        continue;
+                    }
+                    if let Err(e) = self.show_inner(&toast, |state, inner_id| state.shown(id.clone(), inner_id)) {
+                        self.subscribers.send(ToastEvent::Failed { id: id.clone(), message: e.to_string() });
+                    }
+                }
+                EscalationStep::Hook { hook, .. } => {
+                    if !self.is_escalating(&id) {
+                        continue;
+                    }
+                    if let Err(message) = hook.escalate(&id, &toast) {
+                        self.subscribers.send(ToastEvent::Failed { id: id.clone(), message: format!("the escalation hook failed: {}", message) });
+                    }
+                }
+            }
+            taken += 1;
+            let mut state = self.lock();
+            // The user may have reacted while the step was taken.
+            let Some(escalation) = state.escalations.get_mut(&id) else { continue };
+            match self.policy.steps.get(step + 1) {
+                Some(next) => *escalation = Escalation { toast, step: step + 1, due: now + next.after() },
+                None => {
+                    state.escalations.remove(&id);
+                }
+            }
+        }
+        taken
+    }
+
+    /// Calls [`tick`](Escalator::tick) every `interval` until the escalator is dropped.
+    pub fn spawn_pump(self: &Arc<Self>, interval: Duration) -> thread::JoinHandle<()> {
+        clock::spawn_pump(self, interval, |escalator| {
+            escalator.tick();
+        })
+    }
+
+    fn inner_id(&self, id: &str) -> Result<String, NotifyError> {
+        self.lock().inner_ids.get(id).cloned().ok_or_else(|| NotifyError::NotFound(id.to_string()))
+    }
+}
+
+impl Notifier for Escalator {
+    /// Shows the toast, escalating it if the policy covers its scenario. A toast escalated
+    /// without a tag is tagged with its id, so each resend replaces the one before.
+    fn show(&self, toast: &Toast) -> Result<String, NotifyError> {
+        let id = next_id();
+        let escalates = self.policy.scenarios.contains(&toast.scenario) && !self.policy.steps.is_empty();
+        let mut toast = toast.clone();
+        if escalates && toast.tag.is_none() {
+            toast.tag = Some(id.clone());
+        }
+        let escalation = escalates.then(|| Escalation { toast: toast.clone(), step: 0, due: self.clock.now() + self.policy.steps[0].after() });
+        self.show_inner(&toast, |state, inner_id| {
+            if let Some(escalation) = escalation {
+                state.escalations.insert(id.clone(), escalation);
+            }
+            state.shown(id.clone(), inner_id);
+        })?;
+        Ok(id)
+    }
+
+    /// Updates the toast, which is resent with the new content from now on.
+    fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError> {
+        let mut toast = toast.clone();
+        if let Some(escalation) = self.lock().escalations.get_mut(id) {
+            if toast.tag.is_none() {
+                toast.tag.clone_from(&escalation.toast.tag);
+            }
+            escalation.toast = toast.clone();
+        }
+        self.inner.update(&self.inner_id(id)?, &toast)
+    }
+
+    /// Removes the toast and stops escalating it.
+    fn remove(&self, id: &str) -> Result<(), NotifyError> {
+        self.lock().escalations.remove(id);
+        self.inner.remove(&self.inner_id(id)?)
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+        self.subscribers.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::notifier::{ClickedOnShow, MockNotifier};
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_event_before_show_returns() {
+        let escalator = Escalator::new(Arc::new(ClickedOnShow::default()), EscalationPolicy::default());
+        let events = escalator.subscribe();
+        let id = escalator.show(&Toast { scenario: Scenario::Urgent, ..Toast::default() }).unwrap();
+        let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, ToastEvent::Activated { id: id.clone(), arguments: "open".to_string(), user_input: BTreeMap::new() });
+        assert!(!escalator.is_escalating(&id));
+    }
+
+    #[test]
+    fn test_escalator() {
+        let notifier = Arc::new(MockNotifier::new());
+        let clock = Arc::new(FakeClock::default());
+        let escalated = Arc::new(Mutex::new(Vec::new()));
+        let hook = escalated.clone();
+        let escalator = Escalator::with_clock(notifier.clone(), EscalationPolicy {
+            steps: vec![
+                EscalationStep::Resend { after: Duration::from_secs(60), audio: Some((true, false, SoundSrc::Alarm2)), scenario: Some(Scenario::Alarm) },
+                EscalationStep::Hook {
+                    after: Duration::from_secs(60),
+                    hook: Arc::new(move |id: &str, _: &Toast| {
+                        hook.lock().unwrap().push(id.to_string());
+                        Ok(())
+                    }),
+                },
+            ],
+            ..EscalationPolicy::default()
+        }, clock.clone());
+        let events = escalator.subscribe();
+        let urgent = Toast { scenario: Scenario::Urgent, ..sample_toast() };
+
+        let ignored = escalator.show(&urgent).unwrap();
+        let calm = escalator.show(&sample_toast()).unwrap();
+        assert!(escalator.is_escalating(&ignored) && !escalator.is_escalating(&calm));
+        assert_eq!(escalator.tick(), 0);
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(escalator.tick(), 1);
+        let resent = notifier.shown().pop().unwrap();
+        assert_eq!((resent.scenario, resent.audio, resent.tag), (Scenario::Alarm, Some((true, false, SoundSrc::Alarm2)), Some(ignored.clone())));
+        assert_eq!(notifier.current().len(), 2);
+
+        let clicked = escalator.show(&urgent).unwrap();
+        notifier.activate(&notifier.current()[2].0, "", BTreeMap::new());
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)).unwrap().id(), clicked);
+        assert!(!escalator.is_escalating(&clicked));
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(escalator.tick(), 1);
+        assert_eq!(*escalated.lock().unwrap(), vec![ignored.clone()]);
+        assert!(!escalator.is_escalating(&ignored));
+    }
+}
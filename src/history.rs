@@ -0,0 +1,354 @@
+//! A local record of the toasts sent and what became of them, so it can be told afterwards
+//! whether a user was notified.
+//!
+//! This is what Windows keeps in `ToastNotificationHistory`, but it works with any notifier and
+//! keeps toasts after they leave the action center. [`HistoryNotifier`] records everything shown
+//! through it into a [`HistoryStore`], a JSON Lines file that is only ever appended to.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{Clock, SystemClock};
+use crate::logging;
+use crate::notification::{Scenario, Toast};
+use crate::notifier::{DismissReason, Notifier, NotifyError, Subscribers, ToastEvent};
+
+/// Something that happened to a recorded toast, and when.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub at: SystemTime,
+    pub event: ToastEvent,
+}
+
+/// A toast as it was sent, with everything that happened to it since.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The id the notifier showed the toast under.
+    pub id: String,
+    pub sent_at: SystemTime,
+    /// The content of the toast, as last updated.
+    pub toast: Toast,
+    pub updated_at: Option<SystemTime>,
+    /// When the app removed the toast.
+    pub removed_at: Option<SystemTime>,
+    /// The events of the toast, oldest first.
+    pub outcomes: Vec<Outcome>,
+}
+
+impl HistoryEntry {
+    /// Whether the user saw to the toast, by clicking it or closing it. A toast that timed out
+    /// or that the app removed was not acknowledged.
+    pub fn acknowledged(&self) -> bool {
+        self.outcomes.iter().any(|outcome| matches!(outcome.event,
+            ToastEvent::Activated { .. } | ToastEvent::Dismissed { reason: DismissReason::UserCanceled, .. }))
+    }
+}
+
+/// Which entries [`HistoryStore::query`] returns. Every condition that is set must hold.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryQuery {
+    /// Sent at or after this time.
+    pub since: Option<SystemTime>,
+    /// Sent before this time.
+    pub until: Option<SystemTime>,
+    pub app_id: Option<String>,
+    pub tag: Option<String>,
+    pub group: Option<String>,
+    pub scenario: Option<Scenario>,
+    /// Only toasts the user has not acknowledged.
+    pub unacknowledged: bool,
+}
+
+impl HistoryQuery {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        self.since.is_none_or(|since| entry.sent_at >= since)
+            && self.until.is_none_or(|until| entry.sent_at < until)
+            && self.app_id.as_ref().is_none_or(|app_id| entry.toast.app_id == *app_id)
+            && self.tag.as_ref().is_none_or(|tag| entry.toast.tag.as_ref() == Some(tag))
+            && self.group.as_ref().is_none_or(|group| entry.toast.group.as_ref() == Some(group))
+            && self.scenario.as_ref().is_none_or(|scenario| entry.toast.scenario == *scenario)
+            && !(self.unacknowledged && entry.acknowledged())
+    }
+}
+
+/// A line of the history file.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum Record {
+    Sent { id: String, at: SystemTime, toast: Toast },
+    Updated { id: String, at: SystemTime, toast: Toast },
+    Removed { id: String, at: SystemTime },
+    Event { at: SystemTime, event: ToastEvent },
+}
+
+pub struct HistoryStore {
+    path: PathBuf,
+    /// Held while appending, so the lines of concurrent writers do not interleave.
+    append: Mutex<()>,
+}
+
+impl HistoryStore {
+    /// Opens the history kept in the JSON Lines file at `path`, which is created on the first
+    /// toast recorded.
+    pub fn open(path: impl Into<PathBuf>) -> HistoryStore {
+        HistoryStore { path: path.into(), append: Mutex::new(()) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record_sent(&self, id: &str, toast: &Toast, at: SystemTime) -> io::Result<()> {
+        self.append(&Record::Sent { id: id.to_string(), at, toast: toast.clone() })
+    }
+
+    pub fn record_updated(&self, id: &str, toast: &Toast, at: SystemTime) -> io::Result<()> {
+        self.append(&Record::Updated { id: id.to_string(), at, toast: toast.clone() })
+    }
+
+    pub fn record_removed(&self, id: &str, at: SystemTime) -> io::Result<()> {
+        self.append(&Record::Removed { id: id.to_string(), at })
+    }
+
+    pub fn record_event(&self, event: &ToastEvent, at: SystemTime) -> io::Result<()> {
+        self.append(&Record::Event { at, event: event.clone() })
+    }
+
+    /// Every recorded toast, oldest first. Lines that cannot be read, such as one cut off by a
+    /// crash, are skipped.
+    pub fn entries(&self) -> io::Result<Vec<HistoryEntry>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut entries: Vec<HistoryEntry> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            let Ok(record) = serde_json::from_str::<Record>(&line?) else { continue };
+            let (id, at) = match &record {
+                Record::Sent { id, at, .. } | Record::Updated { id, at, .. } | Record::Removed { id, at } => (id.as_str(), *at),
+                Record::Event { event, at } => (event.id(), *at),
+            };
+            if let Record::Sent { id, toast, .. } = record {
+                // Ids are only unique within a process, so a later toast with the same id is a new one.
+                index.insert(id.clone(), entries.len());
+                entries.push(HistoryEntry { id, sent_at: at, toast, updated_at: None, removed_at: None, outcomes: Vec::new() });
+                continue;
+            }
+            let Some(entry) = index.get(id).map(|&i| &mut entries[i]) else { continue };
+            match record {
+                Record::Updated { toast, .. } => {
+                    entry.toast = toast;
+                    entry.updated_at = Some(at);
+                }
+                Record::Removed { .. } => entry.removed_at = Some(at),
+                Record::Event { event, .. } => entry.outcomes.push(Outcome { at, event }),
+                Record::Sent { .. } => unreachable!("handled above"),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The most recent toast recorded with the id.
+    pub fn get(&self, id: &str) -> io::Result<Option<HistoryEntry>> {
+        Ok(self.entries()?.into_iter().rev().find(|entry| entry.id == id))
+    }
+
+    /// The entries the query matches, oldest first.
+    pub fn query(&self, query: &HistoryQuery) -> io::Result<Vec<HistoryEntry>> {
+        Ok(self.entries()?.into_iter().filter(|entry| query.matches(entry)).collect())
+    }
+
+    /// Forgets the toasts sent before `before`, returning how many there were.
+    pub fn prune(&self, before: SystemTime) -> io::Result<usize> {
+        let _guard = self.append.lock().unwrap_or_else(|e| e.into_inner());
+        let (old, kept): (Vec<HistoryEntry>, Vec<HistoryEntry>) = self.entries()?
+            .into_iter()
+            .partition(|entry| entry.sent_at < before);
+        if old.is_empty() {
+            return Ok(0);
+        }
+        let mut data = Vec::new();
+        for entry in kept {
+            let mut records = vec![Record::Sent { id: entry.id.clone(), at: entry.sent_at, toast: entry.toast.clone() }];
+            if let Some(at) = entry.updated_at {
+                records.push(Record::Updated { id: entry.id.clone(), at, toast: entry.toast });
+            }
+            if let Some(at) = entry.removed_at {
+                records.push(Record::Removed { id: entry.id.clone(), at });
+            }
+            records.extend(entry.outcomes.into_iter().map(|outcome| Record::Event { at: outcome.at, event: outcome.event }));
+            for record in records {
+                serde_json::to_writer(&mut data, &record)?;
+                data.push(b'\n');
+            }
+        }
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(old.len())
+    }
+
+    fn append(&self, record: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let _guard = self.append.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(&line)
+    }
+}
+
+/// A notifier that records everything shown through it, and the events of those toasts, into a
+/// [`HistoryStore`].
+///
+/// A toast that could not be recorded is still shown, since failing would only tempt the caller
+/// to show it again. What could not be written to the store is logged as a warning instead.
+pub struct HistoryNotifier {
+    inner: Arc<dyn Notifier>,
+    store: Arc<HistoryStore>,
+    clock: Arc<dyn Clock>,
+    subscribers: Subscribers,
+    /// Held while a toast is shown and recorded, so its events are recorded after it.
+    sending: Arc<Mutex<()>>,
+}
+
+impl HistoryNotifier {
+    pub fn new(inner: Arc<dyn Notifier>, store: Arc<HistoryStore>) -> HistoryNotifier {
+        HistoryNotifier::with_clock(inner, store, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(inner: Arc<dyn Notifier>, store: Arc<HistoryStore>, clock: Arc<dyn Clock>) -> HistoryNotifier {
+        let events = inner.subscribe();
+        let subscribers = Subscribers::default();
+        let sending: Arc<Mutex<()>> = Arc::default();
+        let (recorder, forward, now, sent) = (store.clone(), subscribers.clone(), clock.clone(), sending.clone());
+        // Events reach subscribers only once recorded, so what they see is already in the store.
+        thread::spawn(move || {
+            for event in events {
+                let guard = sent.lock().unwrap_or_else(|e| e.into_inner());
+                if let Err(e) = recorder.record_event(&event, now.now()) {
+                    logging::history_failed(event.id(), &e);
+                }
+                drop(guard);
+                forward.send(event);
+            }
+        });
+        HistoryNotifier { inner, store, clock, subscribers, sending }
+    }
+
+    pub fn store(&self) -> &HistoryStore {
+        &self.store
+    }
+
+    /// Shows a recorded toast again, as it was last updated, and returns the id of the new toast.
+    pub fn reshow(&self, id: &str) -> Result<String, NotifyError> {
+        let entry = self.store.get(id)
+            .map_err(|e| NotifyError::Platform(format!("the history could not be read: {}", e)))?
+            .ok_or_else(|| NotifyError::NotFound(id.to_string()))?;
+        self.show(&entry.toast)
+    }
+}
+
+impl Notifier for HistoryNotifier {
+    fn show(&self, toast: &Toast) -> Result<String, NotifyError> {
+        let _guard = self.sending.lock().unwrap_or_else(|e| e.into_inner());
+        let id = self.inner.show(toast)?;
+        if let Err(e) = self.store.record_sent(&id, toast, self.clock.now()) {
+            logging::history_failed(&id, &e);
+        }
+        Ok(id)
+    }
+
+    fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError> {
+        self.inner.update(id, toast)?;
+        if let Err(e) = self.store.record_updated(id, toast, self.clock.now()) {
+            logging::history_failed(id, &e);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<(), NotifyError> {
+        self.inner.remove(id)?;
+        if let Err(e) = self.store.record_removed(id, self.clock.now()) {
+            logging::history_failed(id, &e);
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+        self.subscribers.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::notifier::{ClickedOnShow, MockNotifier};
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_event_before_show_returns() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("win_toast-history-early-{}.jsonl", std::process::id()));
+        let store = Arc::new(HistoryStore::open(&path));
+        let notifier = HistoryNotifier::new(Arc::new(ClickedOnShow::default()), store.clone());
+        let events = notifier.subscribe();
+        let id = notifier.show(&Toast::default()).unwrap();
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)).unwrap().id(), id);
+        let entry = store.get(&id)?.unwrap();
+        assert_eq!(entry.outcomes.iter().map(|outcome| outcome.event.id()).collect::<Vec<_>>(), vec![id.as_str()]);
+        fs::remove_file(path)
+    }
+
+    #[test]
+    fn test_history() -> std::io::Result<()> {
+        let path = std::env::temp_dir().join(format!("win_toast-history-{}.jsonl", std::process::id()));
+        let notifier = Arc::new(MockNotifier::new());
+        let clock = Arc::new(FakeClock::default());
+        let history = HistoryNotifier::with_clock(notifier.clone(), Arc::new(HistoryStore::open(&path)), clock.clone());
+        let events = history.subscribe();
+        let urgent = Toast { scenario: Scenario::Urgent, ..sample_toast() };
+
+        let old = history.show(&urgent).unwrap();
+        clock.advance(Duration::from_secs(7200));
+        let clicked = history.show(&urgent).unwrap();
+        let ignored = history.show(&urgent).unwrap();
+        history.show(&sample_toast()).unwrap();
+        notifier.activate(&clicked, "open", BTreeMap::new());
+        notifier.dismiss(&ignored, DismissReason::TimedOut);
+        for _ in 0..2 {
+            events.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+
+        let last_hour = HistoryQuery {
+            since: Some(clock.now() - Duration::from_secs(3600)),
+            scenario: Some(Scenario::Urgent),
+            unacknowledged: true,
+            ..HistoryQuery::default()
+        };
+        let unacknowledged = history.store().query(&last_hour)?;
+        assert_eq!(unacknowledged.iter().map(|entry| entry.id.as_str()).collect::<Vec<_>>(), vec![ignored.as_str()]);
+        assert_eq!(unacknowledged[0].outcomes[0].event, ToastEvent::Dismissed { id: ignored.clone(), reason: DismissReason::TimedOut });
+        assert!(history.store().get(&clicked)?.unwrap().acknowledged());
+
+        let again = history.reshow(&old).unwrap();
+        assert_eq!(notifier.shown().last(), Some(&urgent));
+        assert_eq!(history.store().prune(clock.now())?, 1);
+        assert_eq!(history.store().entries()?.len(), 4);
+        assert_eq!(history.store().get(&again)?.unwrap().toast, urgent);
+        std::fs::remove_file(path)
+    }
+}
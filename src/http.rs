@@ -0,0 +1,398 @@
+//! An embedded HTTP server that turns requests into toasts, so CI and monitoring tools can post
+//! straight to a desktop.
+//!
+//! Every request needs an `Authorization: Bearer <token>` header, except webhooks whose mapper
+//! takes a signature instead, such as GitHub deliveries signed with the token as the secret in
+//! `X-Hub-Signature-256`. The endpoints are:
+//!
+//! - `POST /toasts` with a [`Toast`] as JSON shows it and answers `{"ids":["…"]}`.
+//! - `PUT /toasts/{id}` with a [`Toast`] as JSON replaces the toast with the id.
+//! - `DELETE /toasts/{id}` removes the toast with the id.
+//! - `POST /hooks/{name}` passes the payload to the [`WebhookMapper`] registered under the name
+//!   and shows the toasts it returns, answering with their ids like `POST /toasts`.
+//!
+//! Errors are answered as `{"error":"…"}`. Only HTTP/1.1 without keep-alive and with a
+//! `Content-Length` is spoken, which is all webhook senders need.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use crate::notification::Toast;
+use crate::notifier::{prepare, Notifier, NotifyError};
+use crate::webhook::{WebhookAuth, WebhookMapper};
+
+/// The largest request accepted, headers included.
+const MAX_REQUEST: u64 = 1 << 20;
+
+/// The most connections handled at once. Further ones are answered 503 straight away.
+const MAX_CONNECTIONS: usize = 64;
+
+/// How long reading a request or writing a reply may stall before the connection is dropped.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct HttpServer {
+    listener: TcpListener,
+    token: String,
+    notifier: Arc<dyn Notifier>,
+    mappers: HashMap<String, Box<dyn WebhookMapper>>,
+}
+
+impl HttpServer {
+    /// Starts listening on the address. Requests must carry the token, which cannot be empty.
+    pub fn bind(address: impl ToSocketAddrs, token: impl Into<String>, notifier: Arc<dyn Notifier>) -> io::Result<HttpServer> {
+        let token = token.into();
+        if token.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the token is empty"));
+        }
+        Ok(HttpServer { listener: TcpListener::bind(address)?, token, notifier, mappers: HashMap::new() })
+    }
+
+    /// Serves `POST /hooks/{name}` with the mapper.
+    pub fn mapper(mut self, name: &str, mapper: impl WebhookMapper + 'static) -> HttpServer {
+        self.mappers.insert(name.to_string(), Box::new(mapper));
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves requests until accepting a connection fails, each connection on its own thread.
+    pub fn run(self) -> io::Result<()> {
+        let HttpServer { listener, token, notifier, mappers } = self;
+        let routes = Arc::new(Routes { token, notifier, mappers });
+        let open = Arc::new(AtomicUsize::new(0));
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let _ = stream.set_read_timeout(Some(TIMEOUT));
+            let _ = stream.set_write_timeout(Some(TIMEOUT));
+            if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                open.fetch_sub(1, Ordering::SeqCst);
+                let _ = Reply::error(503, "too many connections").write(&stream);
+                continue;
+            }
+            let connection = Connection(open.clone());
+            let routes = routes.clone();
+            thread::spawn(move || {
+                let _connection = connection;
+                let reply = match read_request(&stream) {
+                    Ok(request) => routes.handle(&request),
+                    Err(reply) => reply,
+                };
+                let _ = reply.write(&stream);
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Counts a connection as open until dropped, even if handling it panics.
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+struct Reply {
+    status: u16,
+    body: Value,
+}
+
+impl Reply {
+    fn ok(body: Value) -> Reply {
+        Reply { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Reply {
+        Reply { status, body: json!({ "error": message.into() }) }
+    }
+
+    fn write(&self, mut stream: &TcpStream) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            411 => "Length Required",
+            413 => "Payload Too Large",
+            422 => "Unprocessable Entity",
+            429 => "Too Many Requests",
+            503 => "Service Unavailable",
+            _ => "Bad Gateway",
+        };
+        let body = self.body.to_string();
+        let mut head = format!("HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status, reason, body.len());
+        if self.status == 401 {
+            head.push_str("WWW-Authenticate: Bearer\r\n");
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body.as_bytes())?;
+        stream.flush()
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, Reply> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST));
+    let mut line = String::new();
+    let malformed = |_| Reply::error(400, "malformed request");
+    reader.read_line(&mut line).map_err(malformed)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(Reply::error(400, "malformed request line"));
+    };
+    let (method, path) = (method.to_string(), target.split('?').next().unwrap_or_default().to_string());
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(malformed)? == 0 {
+            return Err(Reply::error(400, "the headers are cut off"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').ok_or_else(|| Reply::error(400, "malformed header"))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    let mut request = Request { method, path, headers, body: Vec::new() };
+    if request.header("Transfer-Encoding").is_some() {
+        return Err(Reply::error(411, "a Content-Length is required, Transfer-Encoding is not supported"));
+    }
+    let length: u64 = match request.header("Content-Length") {
+        Some(length) => length.parse().map_err(|_| Reply::error(400, "malformed Content-Length"))?,
+        None => 0,
+    };
+    if length > reader.get_ref().limit() {
+        return Err(Reply::error(413, "the request is too large"));
+    }
+    request.body.resize(length as usize, 0);
+    reader.read_exact(&mut request.body).map_err(malformed)?;
+    Ok(request)
+}
+
+struct Routes {
+    token: String,
+    notifier: Arc<dyn Notifier>,
+    mappers: HashMap<String, Box<dyn WebhookMapper>>,
+}
+
+impl Routes {
+    fn handle(&self, request: &Request) -> Reply {
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        let auth = match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["hooks", name]) => self.mappers.get(*name).map(|mapper| mapper.auth()).unwrap_or_default(),
+            _ => WebhookAuth::Bearer,
+        };
+        let bearer = request.header("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), self.token.as_bytes()));
+        let signed = auth == WebhookAuth::HubSignature256 && request.header("X-Hub-Signature-256")
+            .is_some_and(|value| verify_hub_signature(self.token.as_bytes(), &request.body, value));
+        if !bearer && !signed {
+            return Reply::error(401, match auth {
+                WebhookAuth::Bearer => "a valid bearer token is required",
+                WebhookAuth::HubSignature256 => "a valid bearer token or X-Hub-Signature-256 is required",
+            });
+        }
+        let result = match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["toasts"]) => parse_toast(&request.body).and_then(|toast| self.show(vec![toast])),
+            ("PUT", ["toasts", id]) => parse_toast(&request.body).and_then(|toast| self.update(id, toast)),
+            ("DELETE", ["toasts", id]) => self.notifier.remove(id).map(|_| json!({})).map_err(notify_error),
+            ("POST", ["hooks", name]) => match self.mappers.get(*name) {
+                Some(mapper) => serde_json::from_slice(&request.body)
+                    .map_err(|e| Reply::error(400, format!("invalid JSON: {}", e)))
+                    .and_then(|payload| mapper.map(&payload).map_err(|e| Reply::error(422, e.message)))
+                    .and_then(|toasts| self.show(toasts)),
+                None => Err(Reply::error(404, format!("there is no hook named {:?}", name))),
+            },
+            (_, ["toasts"] | ["toasts", _] | ["hooks", _]) => Err(Reply::error(405, format!("{} is not allowed here", request.method))),
+            _ => Err(Reply::error(404, format!("there is nothing at {}", request.path))),
+        };
+        result.map(Reply::ok).unwrap_or_else(|reply| reply)
+    }
+
+    /// Checks every toast before showing any of them, so a bad payload shows nothing.
+    fn show(&self, toasts: Vec<Toast>) -> Result<Value, Reply> {
+        let toasts = toasts.into_iter().map(prepare).collect::<Result<Vec<_>, _>>()
+            .map_err(|message| Reply::error(422, message))?;
+        let ids = toasts.iter().map(|toast| self.notifier.show(toast)).collect::<Result<Vec<_>, _>>()
+            .map_err(notify_error)?;
+        Ok(json!({ "ids": ids }))
+    }
+
+    fn update(&self, id: &str, toast: Toast) -> Result<Value, Reply> {
+        let toast = prepare(toast).map_err(|message| Reply::error(422, message))?;
+        self.notifier.update(id, &toast).map_err(notify_error)?;
+        Ok(json!({}))
+    }
+}
+
+fn parse_toast(body: &[u8]) -> Result<Toast, Reply> {
+    serde_json::from_slice(body).map_err(|e| Reply::error(400, format!("invalid toast: {}", e)))
+}
+
+fn notify_error(e: NotifyError) -> Reply {
+    match e {
+        NotifyError::NotFound(_) => Reply::error(404, e.to_string()),
+        NotifyError::QueueFull => Reply::error(429, e.to_string()),
+        NotifyError::UnknownChannel(_) => Reply::error(422, e.to_string()),
+        _ => Reply::error(502, e.to_string()),
+    }
+}
+
+/// Compares without returning early, so the time taken does not tell how much of a guess was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Whether the value of `X-Hub-Signature-256` is `sha256=` and the HMAC-SHA256 of the body in hex.
+fn verify_hub_signature(secret: &[u8], body: &[u8], value: &str) -> bool {
+    let Some(hex) = value.strip_prefix("sha256=").filter(|hex| hex.is_ascii() && hex.len() % 2 == 0) else {
+        return false;
+    };
+    let Ok(signature) = (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16)).collect::<Result<Vec<u8>, _>>() else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::notifier::MockNotifier;
+    use crate::tests::sample_toast;
+    use crate::webhook::GitHubMapper;
+
+    fn hub_signature(secret: &[u8], body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(body);
+        let hex: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("sha256={}", hex)
+    }
+
+    #[test]
+    fn test_verify_hub_signature() {
+        // The example of the GitHub documentation on validating webhook deliveries.
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(verify_hub_signature(b"It's a Secret to Everybody", b"Hello, World!", signature));
+        assert!(!verify_hub_signature(b"It's a Secret to Everybody", b"Hello, World?", signature));
+        assert!(!verify_hub_signature(b"It's a Secret to Everybody", b"Hello, World!", &signature[..signature.len() - 2]));
+        assert!(!verify_hub_signature(b"It's a Secret to Everybody", b"Hello, World!", "sha256=é7"));
+        assert!(!verify_hub_signature(b"It's a Secret to Everybody", b"Hello, World!", &signature[7..]));
+    }
+
+    #[test]
+    fn test_signed_github_delivery() {
+        let notifier = Arc::new(MockNotifier::new());
+        let server = HttpServer::bind("127.0.0.1:0", "webhook secret", notifier.clone()).unwrap()
+            .mapper("github", GitHubMapper::default())
+            .mapper("plain", |_: &Value| Ok(Vec::new()));
+        let address = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        let post = |path: &str, signature: &str, body: &str| -> u16 {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "POST {} HTTP/1.1\r\nX-GitHub-Event: workflow_run\r\nX-Hub-Signature-256: {}\r\nContent-Length: {}\r\n\r\n{}",
+                path, signature, body.len(), body).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response[9..12].parse().unwrap()
+        };
+
+        let run = r#"{"action":"completed","workflow_run":{"name":"CI","conclusion":"success","html_url":"https://github.com/acme/app/actions/runs/2"}}"#;
+        let signature = hub_signature(b"webhook secret", run.as_bytes());
+        assert_eq!(post("/hooks/github", &signature, run), 200);
+        assert_eq!(notifier.current()[0].1.binding_elems[0].title.0, "✔ CI succeeded");
+        assert_eq!(post("/hooks/github", &signature, &run.replace("success", "failure")), 401);
+        assert_eq!(post("/hooks/github", &hub_signature(b"guess", run.as_bytes()), run), 401);
+        // Mappers that do not take signatures still need the bearer token.
+        assert_eq!(post("/hooks/plain", &hub_signature(b"webhook secret", b"{}"), "{}"), 401);
+
+        // The chunks are not sent, so the server does not close with unread data.
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "POST /hooks/github HTTP/1.1\r\nX-Hub-Signature-256: {}\r\nTransfer-Encoding: chunked\r\n\r\n", signature).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 411 Length Required\r\n"));
+        assert_eq!(notifier.current().len(), 1);
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let server = HttpServer::bind("127.0.0.1:0", "token", Arc::new(MockNotifier::new())).unwrap();
+        let address = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        let idle: Vec<TcpStream> = (0..MAX_CONNECTIONS).map(|_| TcpStream::connect(address).unwrap()).collect();
+        let mut response = String::new();
+        TcpStream::connect(address).unwrap().read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        drop(idle);
+    }
+
+    #[test]
+    fn test_http_server() {
+        let notifier = Arc::new(MockNotifier::new());
+        let server = HttpServer::bind("127.0.0.1:0", "secret", notifier.clone()).unwrap()
+            .mapper("github", GitHubMapper::default());
+        let address = server.local_addr().unwrap();
+        std::thread::spawn(move || server.run());
+        let request = |method: &str, path: &str, token: &str, body: &str| -> (u16, serde_json::Value) {
+            let mut stream = std::net::TcpStream::connect(address).unwrap();
+            write!(stream, "{} {} HTTP/1.1\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\n\r\n{}",
+                method, path, token, body.len(), body).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            (head[9..12].parse().unwrap(), serde_json::from_str(body).unwrap())
+        };
+
+        let toast = serde_json::to_string(&sample_toast()).unwrap();
+        assert_eq!(request("POST", "/toasts", "wrong", &toast).0, 401);
+        let (status, body) = request("POST", "/toasts", "secret", &toast);
+        assert_eq!(status, 200);
+        let id = body["ids"][0].as_str().unwrap().to_string();
+        assert_eq!(request("PUT", &format!("/toasts/{}", id), "secret", &toast).0, 200);
+        assert_eq!(request("POST", "/toasts", "secret", "{}").0, 422);
+        assert_eq!(request("DELETE", &format!("/toasts/{}", id), "secret", "").0, 200);
+        assert_eq!(request("DELETE", &format!("/toasts/{}", id), "secret", "").0, 404);
+
+        let run = r#"{"action":"completed","repository":{"full_name":"acme/app"},"workflow_run":{"name":"CI","run_number":7,
+            "conclusion":"failure","head_branch":"main","html_url":"https://github.com/acme/app/actions/runs/1"}}"#;
+        let (status, body) = request("POST", "/hooks/github", "secret", run);
+        assert_eq!((status, body["ids"].as_array().unwrap().len()), (200, 1));
+        assert_eq!(notifier.current()[0].1.binding_elems[0].title.0, "✘ CI #7 failed");
+        assert_eq!(request("POST", "/hooks/gitlab", "secret", run).0, 404);
+    }
+}
@@ -0,0 +1,382 @@
+//! Localized toasts, with their text looked up in message catalogs.
+//!
+//! Any text of a toast that an app writes as `l10n:` followed by a message id, such as
+//! `l10n:unread-messages`, is replaced by the message in the locale of the user. An attribute of
+//! a message is written as `l10n:message.attribute`. The catalogs are tried in the order of
+//! [`Localizer::fallbacks`], so a message missing in `de-AT` is taken from `de`, and then from
+//! the fallback locale.
+//!
+//! Catalogs in the [Fluent](https://projectfluent.org) format are built in as [`FluentCatalog`],
+//! whose select expressions pick the plural form for a number:
+//!
+//! ```text
+//! unread-messages = { $count ->
+//!     [one] One unread message
+//!    *[other] { $count } unread messages
+//! }
+//! ```
+//!
+//! Other formats such as gettext plug in by implementing [`Catalog`].
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use fluent_langneg::{negotiate_languages, NegotiationStrategy};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use unic_langid::LanguageIdentifier;
+
+use crate::notification::*;
+
+/// Marks a text as the id of a message.
+pub const KEY_PREFIX: &str = "l10n:";
+
+/// An error in a catalog, or a message that cannot be formatted in any of the locales.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalizeError {
+    pub message: String,
+}
+
+impl LocalizeError {
+    fn new(message: impl Into<String>) -> LocalizeError {
+        LocalizeError { message: message.into() }
+    }
+}
+
+impl fmt::Display for LocalizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for LocalizeError {}
+
+/// The messages of one locale.
+pub trait Catalog: Send + Sync {
+    fn locale(&self) -> &LanguageIdentifier;
+
+    /// Formats the message with the key with the arguments, or returns `None` when the catalog
+    /// does not have it, so the next locale is tried.
+    fn format(&self, key: &str, args: &Map<String, Value>) -> Option<Result<String, String>>;
+}
+
+/// A catalog of Fluent resources.
+pub struct FluentCatalog {
+    locale: LanguageIdentifier,
+    bundle: FluentBundle<FluentResource>,
+}
+
+impl FluentCatalog {
+    pub fn new(locale: &str) -> Result<FluentCatalog, LocalizeError> {
+        let locale = parse_locale(locale)?;
+        let mut bundle = FluentBundle::new_concurrent(vec![locale.clone()]);
+        // The Unicode isolation marks around arguments would show up in the toast.
+        bundle.set_use_isolating(false);
+        bundle.add_builtins().map_err(|e| LocalizeError::new(e.to_string()))?;
+        Ok(FluentCatalog { locale, bundle })
+    }
+
+    /// Adds the messages of a resource, which must not redefine a message already added.
+    pub fn add(&mut self, source: &str) -> Result<(), LocalizeError> {
+        let resource = FluentResource::try_new(source.to_string()).map_err(|(_, errors)| {
+            LocalizeError::new(format!("the {} catalog does not parse: {}", self.locale, join(errors)))
+        })?;
+        self.bundle.add_resource(resource)
+            .map_err(|errors| LocalizeError::new(format!("the {} catalog is invalid: {}", self.locale, join(errors))))
+    }
+}
+
+impl Catalog for FluentCatalog {
+    fn locale(&self) -> &LanguageIdentifier {
+        &self.locale
+    }
+
+    fn format(&self, key: &str, args: &Map<String, Value>) -> Option<Result<String, String>> {
+        let (id, attribute) = match key.split_once('.') {
+            Some((id, attribute)) => (id, Some(attribute)),
+            None => (key, None),
+        };
+        let message = self.bundle.get_message(id)?;
+        let pattern = match attribute {
+            Some(attribute) => message.get_attribute(attribute)?.value(),
+            None => message.value()?,
+        };
+        let args: FluentArgs = args.iter()
+            .filter_map(|(name, value)| {
+                let value = match value {
+                    Value::Null => return None,
+                    Value::Number(number) => number.as_f64().map(FluentValue::from).unwrap_or(FluentValue::None),
+                    // Strings stay as written, such as "007"; only numbers select plurals.
+                    Value::String(text) => FluentValue::from(text.as_str()),
+                    value => FluentValue::from(value.to_string()),
+                };
+                Some((name.as_str(), value))
+            })
+            .collect();
+        let mut errors = Vec::new();
+        let text = self.bundle.format_pattern(pattern, Some(&args), &mut errors).into_owned();
+        Some(if errors.is_empty() { Ok(text) } else { Err(join(errors)) })
+    }
+}
+
+/// The catalogs of an app, localizing its toasts.
+#[derive(Default)]
+pub struct Localizer {
+    catalogs: Vec<Box<dyn Catalog>>,
+    fallback: Option<LanguageIdentifier>,
+}
+
+impl Localizer {
+    pub fn new() -> Localizer {
+        Localizer::default()
+    }
+
+    /// Loads the Fluent catalogs in the directory, each either a `<locale>.ftl` file or a
+    /// `<locale>` directory of `.ftl` files, like `fr.ftl` or `pt-BR/orders.ftl`.
+    pub fn load_dir(dir: &Path) -> Result<Localizer, LocalizeError> {
+        let cannot_read = |path: &Path, e: std::io::Error| LocalizeError::new(format!("{} cannot be read: {}", path.display(), e));
+        let mut entries = fs::read_dir(dir).and_then(|entries| entries.map(|entry| entry.map(|entry| entry.path())).collect::<Result<Vec<_>, _>>())
+            .map_err(|e| cannot_read(dir, e))?;
+        entries.sort();
+        let mut localizer = Localizer::new();
+        for path in entries {
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+            let sources = if path.is_dir() {
+                let mut files = fs::read_dir(&path).and_then(|files| files.map(|file| file.map(|file| file.path())).collect::<Result<Vec<_>, _>>())
+                    .map_err(|e| cannot_read(&path, e))?;
+                files.retain(|file| is_ftl(file));
+                files.sort();
+                files
+            } else if is_ftl(&path) {
+                vec![path.clone()]
+            } else {
+                continue;
+            };
+            let mut catalog = FluentCatalog::new(locale)?;
+            for file in sources {
+                catalog.add(&fs::read_to_string(&file).map_err(|e| cannot_read(&file, e))?)?;
+            }
+            localizer = localizer.catalog(catalog);
+        }
+        Ok(localizer)
+    }
+
+    /// Adds the catalog. Catalogs of the same locale are tried in the order they were added.
+    pub fn catalog(mut self, catalog: impl Catalog + 'static) -> Localizer {
+        self.catalogs.push(Box::new(catalog));
+        self
+    }
+
+    /// The locale to take the messages from that no better match has.
+    pub fn fallback(mut self, locale: &str) -> Result<Localizer, LocalizeError> {
+        self.fallback = Some(parse_locale(locale)?);
+        Ok(self)
+    }
+
+    /// The locales of the catalogs.
+    pub fn locales(&self) -> Vec<&LanguageIdentifier> {
+        let mut locales: Vec<&LanguageIdentifier> = Vec::new();
+        for catalog in &self.catalogs {
+            if !locales.contains(&catalog.locale()) {
+                locales.push(catalog.locale());
+            }
+        }
+        locales
+    }
+
+    /// The locales the messages for `locale` are looked up in, best match first.
+    pub fn fallbacks(&self, locale: &LanguageIdentifier) -> Vec<LanguageIdentifier> {
+        let available = self.locales();
+        let mut fallbacks: Vec<LanguageIdentifier> =
+            negotiate_languages(&[locale], &available, None, NegotiationStrategy::Filtering).into_iter().map(|locale| (**locale).clone()).collect();
+        if let Some(fallback) = &self.fallback {
+            if !fallbacks.contains(fallback) {
+                fallbacks.push(fallback.clone());
+            }
+        }
+        fallbacks
+    }
+
+    /// Replaces the message ids in the toast by the messages for `locale`, formatted with the
+    /// arguments, and sets the language of each binding to the locale most of its messages are
+    /// from, the better match on a tie, or to the best match when it has none.
+    pub fn localize(&self, toast: &Toast, locale: &str, args: &impl Serialize) -> Result<Toast, LocalizeError> {
+        let requested = parse_locale(locale)?;
+        let args = match serde_json::to_value(args) {
+            Ok(Value::Object(args)) => args,
+            Ok(Value::Null) => Map::new(),
+            Ok(_) => return Err(LocalizeError::new("the arguments are not an object")),
+            Err(e) => return Err(LocalizeError::new(e.to_string())),
+        };
+        let fallbacks = self.fallbacks(&requested);
+        let mut toast = toast.clone();
+        // The number of messages of each binding from each of the fallbacks.
+        let mut resolved = vec![vec![0; fallbacks.len()]; toast.binding_elems.len()];
+        for (binding, text) in texts_mut(&mut toast) {
+            if let Some(key) = text.strip_prefix(KEY_PREFIX) {
+                let (i, message) = self.format(&fallbacks, key, &args)?;
+                if let Some(binding) = binding {
+                    resolved[binding][i] += 1;
+                }
+                *text = message;
+            }
+        }
+        for (binding, counts) in toast.binding_elems.iter_mut().zip(resolved) {
+            // The first of the locales with the most messages; max_by_key would take the last.
+            let most = counts.iter().copied().max().unwrap_or(0);
+            let lang = fallbacks.iter().zip(&counts).find(|(_, count)| **count == most).map_or(&requested, |(locale, _)| locale);
+            binding.lang = Some(lang.to_string());
+        }
+        Ok(toast)
+    }
+
+    /// The message and the index of the locale in `fallbacks` it is from.
+    fn format(&self, fallbacks: &[LanguageIdentifier], key: &str, args: &Map<String, Value>) -> Result<(usize, String), LocalizeError> {
+        for (i, locale) in fallbacks.iter().enumerate() {
+            for catalog in self.catalogs.iter().filter(|catalog| catalog.locale() == locale) {
+                match catalog.format(key, args) {
+                    Some(Ok(text)) => return Ok((i, text)),
+                    Some(Err(e)) => return Err(LocalizeError::new(format!("the message {} in {} cannot be formatted: {}", key, locale, e))),
+                    None => {}
+                }
+            }
+        }
+        let locales: Vec<String> = fallbacks.iter().map(ToString::to_string).collect();
+        Err(LocalizeError::new(format!("there is no message {} in {}", key, if locales.is_empty() { "any catalog".to_string() } else { locales.join(", ") })))
+    }
+}
+
+/// The texts of the toast users read, with the index of the binding of those in one.
+fn texts_mut(toast: &mut Toast) -> Vec<(Option<usize>, &mut String)> {
+    let mut texts = Vec::new();
+    for (i, binding) in toast.binding_elems.iter_mut().enumerate() {
+        let mut binding_texts = vec![&mut binding.title.0];
+        binding_texts.extend(binding.icon.iter_mut().chain(binding.hero.iter_mut()).map(|image| &mut image.alt));
+        for elem in &mut binding.elems {
+            match elem {
+                BindingInnerElement::Text(text) => binding_texts.push(text),
+                BindingInnerElement::Image(image) => binding_texts.push(&mut image.alt),
+                BindingInnerElement::Group(subgroups) => {
+                    for elem in subgroups.iter_mut().flatten() {
+                        match elem {
+                            SubgroupElement::Text_(text) => binding_texts.push(&mut text.text),
+                            SubgroupElement::Image_(image) => binding_texts.push(&mut image.alt),
+                        }
+                    }
+                }
+            }
+        }
+        binding_texts.extend(binding.attribution.as_mut());
+        texts.extend(binding_texts.into_iter().map(|text| (Some(i), text)));
+    }
+    let mut other_texts = Vec::new();
+    for elem in &mut toast.actions {
+        match elem {
+            ActionsElem::Action_(action) => {
+                other_texts.push(&mut action.content);
+                other_texts.extend(action.hint_tool_tip.as_mut());
+            }
+            ActionsElem::Input_(input) => {
+                other_texts.extend(input.title.as_mut());
+                other_texts.extend(input.place_holder_content.as_mut());
+                if let InputType::Selection(_, selections) = &mut input.type_ {
+                    other_texts.extend(selections.iter_mut().map(|(_, title)| title));
+                }
+            }
+        }
+    }
+    other_texts.extend(toast.header.as_mut().map(|header| &mut header.title));
+    texts.extend(other_texts.into_iter().map(|text| (None, text)));
+    texts
+}
+
+fn parse_locale(locale: &str) -> Result<LanguageIdentifier, LocalizeError> {
+    locale.parse().map_err(|_| LocalizeError::new(format!("{:?} is not a locale", locale)))
+}
+
+fn is_ftl(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "ftl")
+}
+
+fn join(errors: Vec<impl fmt::Display>) -> String {
+    errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn catalog(locale: &str, source: &str) -> FluentCatalog {
+        let mut catalog = FluentCatalog::new(locale).unwrap();
+        catalog.add(source).unwrap();
+        catalog
+    }
+
+    #[test]
+    fn test_string_args_and_resolved_lang() {
+        let localizer = Localizer::new()
+            .catalog(catalog("en-US", "order = Order { $id } for { $name }\nitems = { $count ->\n    [one] One item\n   *[other] { $count } items\n}\n"))
+            .catalog(catalog("fr", "greeting = Bonjour\n"))
+            .fallback("en-US").unwrap();
+        let toast = Toast {
+            binding_elems: vec![BindingElem {
+                title: ("l10n:order".to_string(), None),
+                elems: vec![BindingInnerElement::Text("l10n:items".to_string())],
+                ..BindingElem::default()
+            }],
+            ..Toast::default()
+        };
+
+        let localized = localizer.localize(&toast, "fr", &json!({ "id": "007", "name": "NaN", "count": 1 })).unwrap();
+        assert_eq!(localized.binding_elems[0].title.0, "Order 007 for NaN");
+        assert_eq!(localized.binding_elems[0].elems, vec![BindingInnerElement::Text("One item".to_string())]);
+        // Both messages are from the fallback, though French is the better match.
+        assert_eq!(localized.binding_elems[0].lang.as_deref(), Some("en-US"));
+
+        let greeting = Toast { binding_elems: vec![BindingElem { title: ("l10n:greeting".to_string(), None), ..BindingElem::default() }], ..Toast::default() };
+        assert_eq!(localizer.localize(&greeting, "fr", &()).unwrap().binding_elems[0].lang.as_deref(), Some("fr"));
+    }
+
+    #[test]
+    fn test_localize() {
+        let catalog = |locale: &str, source: &str| {
+            let mut catalog = FluentCatalog::new(locale).unwrap();
+            catalog.add(source).unwrap();
+            catalog
+        };
+        let localizer = Localizer::new()
+            .catalog(catalog("en-US", "unread = { $count ->\n    [one] One unread message\n   *[other] { $count } unread messages\n}\nreply = Reply\n    .tooltip = Reply to { $name }\n"))
+            .catalog(catalog("de", "unread = { $count ->\n    [one] Eine ungelesene Nachricht\n   *[other] { $count } ungelesene Nachrichten\n}\n"))
+            .fallback("en-US").unwrap();
+        let toast = Toast {
+            binding_elems: vec![BindingElem { title: ("l10n:unread".to_string(), None), ..BindingElem::default() }],
+            actions: vec![ActionsElem::Action_(Action {
+                content: "l10n:reply".to_string(),
+                hint_tool_tip: Some("l10n:reply.tooltip".to_string()),
+                ..Action::default()
+            })],
+            ..Toast::default()
+        };
+
+        let english = localizer.localize(&toast, "en-US", &json!({ "count": 1, "name": "Ann" })).unwrap();
+        assert_eq!(english.binding_elems[0].title.0, "One unread message");
+        assert_eq!(english.binding_elems[0].lang.as_deref(), Some("en-US"));
+        let ActionsElem::Action_(action) = &english.actions[0] else { panic!("expected an action") };
+        assert_eq!((action.content.as_str(), action.hint_tool_tip.as_deref()), ("Reply", Some("Reply to Ann")));
+
+        let german = localizer.localize(&toast, "de-AT", &json!({ "count": "3", "name": "Ann" })).unwrap();
+        assert_eq!(german.binding_elems[0].title.0, "3 ungelesene Nachrichten");
+        assert_eq!(german.binding_elems[0].lang.as_deref(), Some("de"));
+        let ActionsElem::Action_(action) = &german.actions[0] else { panic!("expected an action") };
+        assert_eq!(action.content, "Reply");
+
+        assert!(localizer.localize(&toast, "de", &json!({ "count": 3 })).unwrap_err().message.contains("reply.tooltip"));
+        let missing = Toast { binding_elems: vec![BindingElem { title: ("l10n:nope".to_string(), None), ..BindingElem::default() }], ..Toast::default() };
+        assert_eq!(localizer.localize(&missing, "fr", &()).unwrap_err().message, "there is no message nope in en-US");
+    }
+}
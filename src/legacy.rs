@@ -0,0 +1,146 @@
+//! The legacy toast templates of Windows 8, `ToastText01` to `ToastText04` and
+//! `ToastImageAndText01` to `ToastImageAndText04`, and the conversion of toasts between them
+//! and `ToastGeneric`.
+//!
+//! A legacy binding is kept in the same fields as a generic one: its first text is the title,
+//! the other texts are text elements and its image is the icon. Reading a legacy payload and
+//! converting it with [`Toast::to_generic`] migrates it.
+
+use crate::notification::*;
+use crate::validate::Diagnostic;
+
+impl BindingTemplate {
+    pub fn is_legacy(&self) -> bool {
+        *self != BindingTemplate::ToastGeneric
+    }
+
+    pub fn has_image(&self) -> bool {
+        matches!(self, BindingTemplate::ToastImageAndText01 | BindingTemplate::ToastImageAndText02
+            | BindingTemplate::ToastImageAndText03 | BindingTemplate::ToastImageAndText04)
+    }
+
+    /// The number of texts a legacy template holds, the title included.
+    pub fn text_count(&self) -> Option<usize> {
+        match self {
+            BindingTemplate::ToastGeneric => None,
+            BindingTemplate::ToastText01 | BindingTemplate::ToastImageAndText01 => Some(1),
+            BindingTemplate::ToastText02 | BindingTemplate::ToastText03
+            | BindingTemplate::ToastImageAndText02 | BindingTemplate::ToastImageAndText03 => Some(2),
+            BindingTemplate::ToastText04 | BindingTemplate::ToastImageAndText04 => Some(3),
+        }
+    }
+
+    /// The legacy template for a number of texts, the title included, with or without an image.
+    fn legacy(texts: usize, image: bool) -> BindingTemplate {
+        match (texts, image) {
+            (0 | 1, false) => BindingTemplate::ToastText01,
+            (2, false) => BindingTemplate::ToastText02,
+            (_, false) => BindingTemplate::ToastText04,
+            (0 | 1, true) => BindingTemplate::ToastImageAndText01,
+            (2, true) => BindingTemplate::ToastImageAndText02,
+            (_, true) => BindingTemplate::ToastImageAndText04,
+        }
+    }
+}
+
+impl Toast {
+    /// The toast with its bindings in `ToastGeneric`, which shows everything a legacy template does.
+    pub fn to_generic(&self) -> Toast {
+        let mut toast = self.clone();
+        for binding in &mut toast.binding_elems {
+            binding.template = BindingTemplate::ToastGeneric;
+        }
+        toast
+    }
+
+    /// The toast with every generic binding in the legacy template that fits it best, without
+    /// what no legacy template holds, and a warning for each part left out.
+    pub fn to_legacy(&self) -> (Toast, Vec<Diagnostic>) {
+        let mut toast = self.clone();
+        let mut warnings = Vec::new();
+        for (i, binding) in toast.binding_elems.iter_mut().enumerate() {
+            if !binding.template.is_legacy() {
+                let texts = 1 + binding.elems.iter().filter(|elem| matches!(elem, BindingInnerElement::Text(_))).count();
+                binding.template = BindingTemplate::legacy(texts, binding.icon.is_some());
+            }
+            warnings.extend(left_out(binding).into_iter().map(|(field, message)| Diagnostic::warning(format!("binding_elems[{}].{}", i, field), message)));
+            let texts = binding.template.text_count().unwrap_or_default();
+            if !binding.template.has_image() {
+                binding.icon = None;
+            }
+            binding.hero = None;
+            binding.attribution = None;
+            binding.elems.retain(|elem| matches!(elem, BindingInnerElement::Text(_)));
+            binding.elems.truncate(texts - 1);
+        }
+        (toast, warnings)
+    }
+}
+
+/// What a binding has that its legacy template leaves out, as the field and why.
+pub(crate) fn left_out(binding: &BindingElem) -> Vec<(String, String)> {
+    let template = binding.template;
+    let Some(texts) = template.text_count() else { return Vec::new() };
+    let name = template.attr();
+    let mut left_out = Vec::new();
+    if binding.icon.is_some() && !template.has_image() {
+        left_out.push(("icon".to_string(), format!("{} has no image", name)));
+    }
+    if binding.hero.is_some() {
+        left_out.push(("hero".to_string(), format!("{} has no hero image", name)));
+    }
+    let mut count = 1;
+    for (i, elem) in binding.elems.iter().enumerate() {
+        let field = format!("elems[{}]", i);
+        match elem {
+            BindingInnerElement::Text(_) => {
+                count += 1;
+                if count > texts && texts == 1 {
+                    left_out.push((field, format!("{} shows only the title", name)));
+                } else if count > texts {
+                    left_out.push((field, format!("{} shows only {} texts", name, texts)));
+                }
+            }
+            BindingInnerElement::Image(_) => left_out.push((field, format!("{} has no inline images", name))),
+            BindingInnerElement::Group(_) => left_out.push((field, format!("{} has no groups", name))),
+        }
+    }
+    if binding.attribution.is_some() {
+        left_out.push(("attribution".to_string(), format!("{} has no attribution", name)));
+    }
+    left_out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_legacy_templates() {
+        let xml = r#"<toast><visual><binding template="ToastImageAndText04"><image id="1" src="file:///C:/logo.png"/><text id="2">Line one</text><text id="1">Heading</text><text id="3">Line two</text></binding></visual></toast>"#;
+        let toast = Toast::from_xml(Toast::POWERSHELL_APP_ID, xml).unwrap();
+        let binding = &toast.binding_elems[0];
+        assert_eq!(binding.template, BindingTemplate::ToastImageAndText04);
+        assert_eq!(binding.title.0, "Heading");
+        assert_eq!(binding.elems, vec![BindingInnerElement::Text("Line one".into()), BindingInnerElement::Text("Line two".into())]);
+        assert_eq!(binding.icon.as_ref().unwrap().src, "file:///C:/logo.png");
+        assert_eq!(toast.to_xml(), r#"<toast><visual><binding template="ToastImageAndText04"><image id="1" src="file:///C:/logo.png"/><text id="1">Heading</text><text id="2">Line one</text><text id="3">Line two</text></binding></visual></toast>"#);
+        assert!(toast.validate().is_empty());
+
+        let generic = toast.to_generic();
+        assert!(generic.to_xml().starts_with(r#"<toast><visual><binding template="ToastGeneric"><image src="file:///C:/logo.png" placement="appLogoOverride"/><text>Heading</text>"#));
+        assert_eq!(generic.to_legacy(), (toast, vec![]));
+
+        let (legacy, warnings) = sample_toast().to_legacy();
+        assert_eq!(legacy.binding_elems[0].template, BindingTemplate::ToastImageAndText01);
+        assert_eq!(legacy.binding_elems[0].attribution, None);
+        let warnings: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, [
+            "warning: binding_elems[0].elems[0]: ToastImageAndText01 has no groups",
+            "warning: binding_elems[0].attribution: ToastImageAndText01 has no attribution",
+        ]);
+        let texts = Toast { binding_elems: vec![BindingElem { template: BindingTemplate::ToastText02, ..sample_toast().binding_elems[0].clone() }], ..sample_toast() };
+        assert_eq!(texts.validate().iter().filter(|diagnostic| diagnostic.path.starts_with("binding_elems[0].")).count(), 3);
+    }
+}
@@ -1,11 +1,55 @@
+pub mod accessibility;
+pub mod adaptive_card;
+pub mod capability;
+pub mod channel;
+pub mod clock;
+pub mod command;
+pub mod crash;
+pub mod daemon;
+pub mod digest;
+pub mod escalation;
+pub mod history;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "i18n")]
+pub mod i18n;
+pub mod legacy;
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub mod logger;
+pub mod logging;
+#[cfg(feature = "markdown")]
+pub mod markdown;
 pub mod notification;
+pub mod notifier;
+pub mod payload;
+pub mod preview;
+pub mod queue;
+pub mod quiet;
+pub mod sanitize;
+pub mod schedule;
+#[cfg(feature = "template")]
+pub mod template;
+pub mod validate;
+pub mod webhook;
+pub mod xml;
+
+pub use crash::install_panic_hook;
 
 #[cfg(test)]
 mod tests {
     use std::path::Path;
+    use std::sync::{Mutex, MutexGuard};
     use crate::notification::*;
 
+    /// Taken by the tests that change what the whole process shares, such as the redactor and the
+    /// panic hook, so they run one at a time.
+    pub(crate) fn lock_global_state() -> MutexGuard<'static, ()> {
+        static GLOBAL_STATE: Mutex<()> = Mutex::new(());
+        GLOBAL_STATE.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     #[test]
+    #[cfg(windows)]
     fn test_struct_toast() -> windows::core::Result<()> {
         let toast = Toast {
             app_id: Toast::POWERSHELL_APP_ID.to_string(),
@@ -24,6 +68,9 @@ mod tests {
                 }),
                 title: ("hello".to_string(), Some(1)),
                 elems: vec![BindingInnerElement::Text("Hello, win_toast.".into())],
+                attribution: None,
+                lang: None,
+                template: BindingTemplate::ToastGeneric,
             }],
             duration: Duration::Short,
             scenario: Scenario::Default,
@@ -31,6 +78,11 @@ mod tests {
             actions: vec![],
             use_btn_style: false,
             display_timestamp: None,
+            tag: None,
+            group: None,
+            header: None,
+            channel: None,
+            suppress_popup: false,
         };
         toast.show()?;
         std::thread::sleep(std::time::Duration::from_millis(10));
@@ -38,10 +90,70 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn test_text_xml_toast() -> windows::core::Result<()> {
         Toast::show_with_xml(Toast::POWERSHELL_APP_ID, r#"
         "#)?;
         std::thread::sleep(std::time::Duration::from_millis(10));
         Ok(())
     }
+
+    pub(crate) fn sample_toast() -> Toast {
+        Toast {
+            app_id: Toast::POWERSHELL_APP_ID.to_string(),
+            binding_elems: vec![BindingElem {
+                hero: None,
+                icon: Some(Image {
+                    alt: "logo".to_string(),
+                    src: Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/test/chick.jpeg").display().to_string(),
+                    hint_crop: HintCrop::Circle,
+                    hint_align: ImageAlign::Default,
+                }),
+                title: ("Build <finished>".to_string(), None),
+                elems: vec![BindingInnerElement::Group(vec![vec![SubgroupElement::Text_(InnerText {
+                    text: "42 passed".to_string(),
+                    hint_style: TextStyle::BaseSubtle,
+                    hint_warp: None,
+                    hint_max_lines: None,
+                    hint_min_lines: None,
+                    hint_align: TextAlign::Right,
+                })]])],
+                attribution: Some("via ci.example.com".to_string()),
+                lang: Some("en-US".to_string()),
+                template: BindingTemplate::ToastGeneric,
+            }],
+            duration: Duration::Default,
+            scenario: Scenario::Reminder,
+            audio: None,
+            actions: vec![
+                ActionsElem::Action_(Action {
+                    content: "Open".to_string(),
+                    arguments: "open".to_string(),
+                    activation_type: ActivationType::Foreground,
+                    placement: ActionPlacement::Default,
+                    image_uri: None,
+                    hint_input_id: None,
+                    hint_button_style: Some("Success".to_string()),
+                    hint_tool_tip: None,
+                }),
+                ActionsElem::Action_(Action {
+                    content: "Settings".to_string(),
+                    arguments: "settings".to_string(),
+                    activation_type: ActivationType::Foreground,
+                    placement: ActionPlacement::ContextMenu,
+                    image_uri: None,
+                    hint_input_id: None,
+                    hint_button_style: None,
+                    hint_tool_tip: None,
+                }),
+            ],
+            use_btn_style: true,
+            display_timestamp: None,
+            tag: None,
+            group: None,
+            header: None,
+            channel: None,
+            suppress_popup: false,
+        }
+    }
 }
@@ -0,0 +1,459 @@
+//! Toasts for log records, so errors of desktop tools reach the user instead of a log file.
+//!
+//! [`ToastLogger`] is a logger for the `log` facade and [`ToastLayer`] a `tracing-subscriber`
+//! layer. Both show records at or above a level as toasts, with the source file and line as the
+//! attribution. At most [`LogToastConfig::max_toasts`] are shown per window; the records over the
+//! limit are counted and shown as one summary toast, such as "12 more errors", as soon as the
+//! window lets another toast through, whether or not more records come.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{self, Clock, SystemClock};
+use crate::notification::{self, *};
+use crate::notifier::Notifier;
+
+/// The level of a record, ordered from the least to the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Trace => "trace",
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warning",
+            Level::Error => "error",
+        }
+    }
+
+    /// Errors break through Focus Assist with a sound, warnings play the default sound and the
+    /// rest arrive silently.
+    fn scenario_and_audio(self) -> (Scenario, Option<(bool, bool, SoundSrc)>) {
+        match self {
+            Level::Error => (Scenario::Urgent, Some((false, false, SoundSrc::Default))),
+            Level::Warn => (Scenario::Default, None),
+            _ => (Scenario::Default, Some((false, true, SoundSrc::Default))),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogToastConfig {
+    /// The app id the toasts are shown as. Left empty, they are shown as Windows PowerShell.
+    pub app_id: String,
+    /// Records below this level are not shown, unless a target rule says otherwise.
+    pub level: Level,
+    /// Levels for targets and their children, such as `("hyper", None)` to show nothing from
+    /// hyper. The rule with the longest matching target wins.
+    pub targets: Vec<(String, Option<Level>)>,
+    /// How many toasts are shown per window before records are coalesced into a summary.
+    pub max_toasts: usize,
+    pub window: Duration,
+}
+
+impl Default for LogToastConfig {
+    fn default() -> LogToastConfig {
+        LogToastConfig {
+            app_id: String::new(),
+            level: Level::Error,
+            targets: Vec::new(),
+            max_toasts: 3,
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+impl LogToastConfig {
+    /// The level a record of the target needs to be shown, or `None` if the target is off.
+    fn level_for(&self, target: &str) -> Option<Level> {
+        self.targets.iter()
+            .filter(|(prefix, _)| target == prefix || target.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.starts_with("::")))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(Some(self.level), |(_, level)| *level)
+    }
+}
+
+/// How often the toasters check whether the summary of the records over the limit can be shown.
+const SUMMARY_TICK: Duration = Duration::from_secs(1);
+
+/// The records over the limit that the next summary reports.
+#[derive(Default)]
+struct Limiter {
+    sent: VecDeque<SystemTime>,
+    suppressed: BTreeMap<Level, usize>,
+    last_suppressed: Option<String>,
+}
+
+thread_local! {
+    /// Set while a toast is being shown, so records logged by the notifier itself are ignored.
+    static SHOWING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// What the logger and the layer have in common.
+struct Toaster {
+    config: LogToastConfig,
+    notifier: Arc<dyn Notifier>,
+    clock: Arc<dyn Clock>,
+    limiter: Mutex<Limiter>,
+}
+
+impl Toaster {
+    /// A toaster with a pump that shows the summary once the window allows, until it is dropped.
+    fn spawn(config: LogToastConfig, notifier: Arc<dyn Notifier>, clock: Arc<dyn Clock>) -> Arc<Toaster> {
+        let toaster = Arc::new(Toaster { config, notifier, clock, limiter: Mutex::default() });
+        clock::spawn_pump(&toaster, SUMMARY_TICK, Toaster::tick);
+        toaster
+    }
+
+    /// Locks the limiter, forgetting the toasts that fell out of the window.
+    fn limiter(&self, now: SystemTime) -> MutexGuard<'_, Limiter> {
+        let mut limiter = self.limiter.lock().unwrap_or_else(|e| e.into_inner());
+        let window = self.config.window;
+        while limiter.sent.front().is_some_and(|sent| now.duration_since(*sent).unwrap_or_default() >= window) {
+            limiter.sent.pop_front();
+        }
+        limiter
+    }
+
+    fn enabled(&self, target: &str, level: Level) -> bool {
+        self.config.level_for(target).is_some_and(|min| level >= min) && !SHOWING.with(Cell::get)
+    }
+
+    fn record(&self, level: Level, target: &str, message: &str, file: Option<&str>, line: Option<u32>) {
+        if !self.enabled(target, level) {
+            return;
+        }
+        let now = self.clock.now();
+        let mut toasts = Vec::new();
+        {
+            let mut limiter = self.limiter(now);
+            toasts.extend(self.due_summary(&mut limiter, now));
+            if limiter.sent.len() < self.config.max_toasts {
+                toasts.push(self.record_toast(level, target, message, file, line));
+                limiter.sent.push_back(now);
+            } else {
+                *limiter.suppressed.entry(level).or_default() += 1;
+                limiter.last_suppressed = Some(message.to_string());
+            }
+        }
+        self.show(toasts);
+    }
+
+    /// Shows the pending summary if the window allows another toast.
+    fn tick(&self) {
+        let now = self.clock.now();
+        let summary = self.due_summary(&mut self.limiter(now), now);
+        self.show(summary.into_iter().collect());
+    }
+
+    fn due_summary(&self, limiter: &mut Limiter, now: SystemTime) -> Option<Toast> {
+        if limiter.suppressed.is_empty() || limiter.sent.len() >= self.config.max_toasts {
+            return None;
+        }
+        let summary = self.summary(limiter);
+        limiter.sent.push_back(now);
+        Some(summary)
+    }
+
+    /// Shows the pending summary even over the limit, for a program exiting before the window
+    /// allows it.
+    fn flush(&self) {
+        let summary = {
+            let mut limiter = self.limiter.lock().unwrap_or_else(|e| e.into_inner());
+            if limiter.suppressed.is_empty() {
+                return;
+            }
+            let summary = self.summary(&mut limiter);
+            limiter.sent.push_back(self.clock.now());
+            summary
+        };
+        self.show(vec![summary]);
+    }
+
+    fn show(&self, toasts: Vec<Toast>) {
+        SHOWING.with(|showing| showing.set(true));
+        for toast in toasts {
+            let _ = self.notifier.show(&toast);
+        }
+        SHOWING.with(|showing| showing.set(false));
+    }
+
+    fn record_toast(&self, level: Level, target: &str, message: &str, file: Option<&str>, line: Option<u32>) -> Toast {
+        let mut lines = message.lines();
+        let title = lines.next().unwrap_or_default().to_string();
+        let rest: Vec<&str> = lines.collect();
+        let attribution = match (file, line) {
+            (Some(file), Some(line)) => format!("{} · {}:{}", target, file, line),
+            (Some(file), None) => format!("{} · {}", target, file),
+            _ => target.to_string(),
+        };
+        self.toast(level, title, (!rest.is_empty()).then(|| rest.join("\n")), attribution)
+    }
+
+    fn summary(&self, limiter: &mut Limiter) -> Toast {
+        let suppressed = std::mem::take(&mut limiter.suppressed);
+        let level = *suppressed.keys().next_back().expect("a summary needs suppressed records");
+        let title = suppressed.iter().rev()
+            .map(|(level, count)| format!("{} more {}{}", count, level.name(), if *count == 1 { "" } else { "s" }))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let last = limiter.last_suppressed.take().map(|message| format!("Last: {}", message));
+        self.toast(level, title, last, "Too many to show one by one".to_string())
+    }
+
+    fn toast(&self, level: Level, title: String, text: Option<String>, attribution: String) -> Toast {
+        let (scenario, audio) = level.scenario_and_audio();
+        Toast {
+            app_id: if self.config.app_id.is_empty() { Toast::POWERSHELL_APP_ID.to_string() } else { self.config.app_id.clone() },
+            binding_elems: vec![BindingElem {
+                title: (title, Some(2)),
+                elems: text.into_iter().map(BindingInnerElement::Text).collect(),
+                attribution: Some(attribution),
+                ..BindingElem::default()
+            }],
+            duration: if level == Level::Error { notification::Duration::Long } else { notification::Duration::Default },
+            scenario,
+            audio,
+            ..Toast::default()
+        }
+    }
+}
+
+/// A `log` logger that shows records as toasts.
+#[cfg(feature = "log")]
+pub struct ToastLogger {
+    toaster: Arc<Toaster>,
+}
+
+#[cfg(feature = "log")]
+impl ToastLogger {
+    pub fn new(config: LogToastConfig, notifier: Arc<dyn Notifier>) -> ToastLogger {
+        ToastLogger { toaster: Toaster::spawn(config, notifier, Arc::new(SystemClock)) }
+    }
+
+    /// Uses another clock for the rate limit, such as a [`FakeClock`](crate::clock::FakeClock).
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> ToastLogger {
+        ToastLogger { toaster: Toaster::spawn(self.toaster.config.clone(), self.toaster.notifier.clone(), clock) }
+    }
+
+    /// Makes this the global logger.
+    pub fn install(self) -> Result<(), log::SetLoggerError> {
+        let max_level = self.toaster.config.targets.iter().filter_map(|(_, level)| *level)
+            .chain(Some(self.toaster.config.level))
+            .min()
+            .unwrap_or(Level::Error);
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(match max_level {
+            Level::Trace => log::LevelFilter::Trace,
+            Level::Debug => log::LevelFilter::Debug,
+            Level::Info => log::LevelFilter::Info,
+            Level::Warn => log::LevelFilter::Warn,
+            Level::Error => log::LevelFilter::Error,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(feature = "log")]
+impl From<log::Level> for Level {
+    fn from(level: log::Level) -> Level {
+        match level {
+            log::Level::Trace => Level::Trace,
+            log::Level::Debug => Level::Debug,
+            log::Level::Info => Level::Info,
+            log::Level::Warn => Level::Warn,
+            log::Level::Error => Level::Error,
+        }
+    }
+}
+
+#[cfg(feature = "log")]
+impl log::Log for ToastLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.toaster.enabled(metadata.target(), metadata.level().into())
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.toaster.record(record.level().into(), record.target(), &record.args().to_string(), record.file(), record.line());
+    }
+
+    fn flush(&self) {
+        self.toaster.flush();
+    }
+}
+
+/// A `tracing-subscriber` layer that shows events as toasts.
+///
+/// Fields other than the message are appended as `name=value`. Call
+/// [`flush`](ToastLayer::flush) on exit to show the summary of the events over the limit.
+#[cfg(feature = "tracing")]
+#[derive(Clone)]
+pub struct ToastLayer {
+    toaster: Arc<Toaster>,
+}
+
+#[cfg(feature = "tracing")]
+impl ToastLayer {
+    pub fn new(config: LogToastConfig, notifier: Arc<dyn Notifier>) -> ToastLayer {
+        ToastLayer { toaster: Toaster::spawn(config, notifier, Arc::new(SystemClock)) }
+    }
+
+    /// Uses another clock for the rate limit, such as a [`FakeClock`](crate::clock::FakeClock).
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> ToastLayer {
+        ToastLayer { toaster: Toaster::spawn(self.toaster.config.clone(), self.toaster.notifier.clone(), clock) }
+    }
+
+    /// Shows the summary of the events over the limit, if there are any.
+    pub fn flush(&self) {
+        self.toaster.flush();
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl From<&tracing::Level> for Level {
+    fn from(level: &tracing::Level) -> Level {
+        match *level {
+            tracing::Level::TRACE => Level::Trace,
+            tracing::Level::DEBUG => Level::Debug,
+            tracing::Level::INFO => Level::Info,
+            tracing::Level::WARN => Level::Warn,
+            _ => Level::Error,
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for ToastLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = metadata.level().into();
+        if !self.toaster.enabled(metadata.target(), level) {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        if !visitor.fields.is_empty() {
+            message = format!("{}\n{}", message, visitor.fields.join(" "));
+        }
+        self.toaster.record(level, metadata.target(), &message, metadata.file(), metadata.line());
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: Vec<String>,
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for MessageVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push(format!("{}={}", field.name(), value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::notifier::MockNotifier;
+
+    #[test]
+    fn test_summary_after_the_burst() {
+        let notifier = Arc::new(MockNotifier::new());
+        let clock = Arc::new(FakeClock::default());
+        let toaster = Toaster::spawn(LogToastConfig { max_toasts: 1, ..LogToastConfig::default() }, notifier.clone(), clock.clone());
+        for i in 0..3 {
+            toaster.record(Level::Error, "app", &format!("failure {}", i), None, None);
+        }
+        assert_eq!(notifier.shown().len(), 1);
+        clock.advance(Duration::from_secs(61));
+        let start = Instant::now();
+        while notifier.shown().len() < 2 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(notifier.shown().pop().unwrap().binding_elems[0].title.0, "2 more errors");
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn test_toast_logger() {
+        use log::Log;
+
+        let notifier = Arc::new(MockNotifier::new());
+        let clock = Arc::new(FakeClock::default());
+        let logger = ToastLogger::new(LogToastConfig {
+            level: Level::Warn,
+            targets: vec![("app::noisy".to_string(), None)],
+            max_toasts: 2,
+            ..LogToastConfig::default()
+        }, notifier.clone()).with_clock(clock.clone());
+        let log = |level: log::Level, target: &str, message: &str| {
+            logger.log(&log::Record::builder().level(level).target(target).args(format_args!("{}", message))
+                .file(Some("src/main.rs")).line(Some(7)).build());
+        };
+        log(log::Level::Info, "app", "starting");
+        log(log::Level::Error, "app::noisy::retry", "ignored");
+        for i in 0..5 {
+            log(log::Level::Error, "app::net", &format!("connection {} refused", i));
+        }
+        assert_eq!(notifier.shown().len(), 2);
+        let first = &notifier.shown()[0];
+        assert_eq!(first.scenario, Scenario::Urgent);
+        assert_eq!(first.binding_elems[0].attribution.as_deref(), Some("app::net · src/main.rs:7"));
+
+        clock.advance(std::time::Duration::from_secs(61));
+        log(log::Level::Warn, "app", "disk almost full");
+        let titles: Vec<String> = notifier.shown().iter().map(|toast| toast.binding_elems[0].title.0.clone()).collect();
+        assert_eq!(titles[2..], ["3 more errors", "disk almost full"]);
+        assert_eq!(notifier.shown()[2].binding_elems[0].elems, vec![BindingInnerElement::Text("Last: connection 4 refused".to_string())]);
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn test_toast_layer() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let notifier = Arc::new(MockNotifier::new());
+        let layer = ToastLayer::new(LogToastConfig { max_toasts: 1, ..LogToastConfig::default() }, notifier.clone());
+        let line = line!() + 3;
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer.clone()), || {
+            tracing::warn!("not shown");
+            tracing::error!(path = "C:\\data", "cannot save");
+            tracing::error!("over the limit");
+        });
+        layer.flush();
+        let shown = notifier.shown();
+        assert_eq!(shown.len(), 2);
+        assert_eq!(shown[0].binding_elems[0].title.0, "cannot save");
+        assert_eq!(shown[0].binding_elems[0].elems, vec![BindingInnerElement::Text("path=C:\\data".to_string())]);
+        assert_eq!(shown[0].binding_elems[0].attribution, Some(format!("{} · {}:{}", module_path!(), file!(), line)));
+        assert_eq!(shown[1].binding_elems[0].title.0, "1 more error");
+    }
+}
@@ -0,0 +1,177 @@
+//! Instrumentation of building and sending toasts.
+//!
+//! Nothing is written to stdout. With the `tracing` feature enabled, debug-level spans named
+//! `build` and `send` are opened around each stage and the payload is dumped as a debug event;
+//! with only the `log` feature enabled, the same information is emitted as debug records under
+//! the `win_toast` target. Failures to write the toast history are emitted as warnings. Without
+//! either feature all of this compiles away.
+
+use std::io;
+use std::sync::RwLock;
+use std::time::Instant;
+
+use crate::capability::OsBuild;
+use crate::validate::Diagnostic;
+
+type Redactor = Box<dyn Fn(&str) -> String + Send + Sync>;
+
+static REDACTOR: RwLock<Option<Redactor>> = RwLock::new(None);
+
+/// Installs a hook that is applied to every message body before it appears in a log.
+///
+/// The hook receives the content of each `<text>` element and returns what should be logged instead.
+pub fn set_redactor<F>(redactor: F)
+    where F: Fn(&str) -> String + Send + Sync + 'static {
+    *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(redactor));
+}
+
+/// Removes the hook installed by [`set_redactor`], so message bodies are logged as they are.
+pub fn clear_redactor() {
+    *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// A redactor that replaces every character except whitespace with `*`.
+pub fn mask(text: &str) -> String {
+    text.chars().map(|c| if c.is_whitespace() { c } else { '*' }).collect()
+}
+
+pub(crate) fn has_redactor() -> bool {
+    REDACTOR.read().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+pub(crate) fn redact(text: &str) -> String {
+    match REDACTOR.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(redactor) => redactor(text),
+        None => text.to_string(),
+    }
+}
+
+/// Whether a payload dump would be emitted, so callers can skip serializing it otherwise.
+pub(crate) fn payload_enabled() -> bool {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::enabled!(target: "win_toast", tracing::Level::DEBUG)
+    }
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    {
+        log::log_enabled!(target: "win_toast", log::Level::Debug)
+    }
+    #[cfg(not(any(feature = "log", feature = "tracing")))]
+    {
+        false
+    }
+}
+
+/// Emits the (already redacted) XML payload of a toast.
+#[allow(unused_variables)]
+pub(crate) fn payload(app_id: &str, xml: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(target: "win_toast", app_id, payload = xml, "toast payload");
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::debug!(target: "win_toast", "toast payload for {}: {}", app_id, xml);
+}
+
+/// Emits what was changed in a toast so the running build can show it.
+#[allow(unused_variables)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn downgraded(app_id: &str, build: OsBuild, changes: &[Diagnostic]) {
+    #[cfg(feature = "tracing")]
+    for change in changes {
+        tracing::debug!(target: "win_toast", app_id, build = build.0, %change, "toast downgraded");
+    }
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    for change in changes {
+        log::debug!(target: "win_toast", "toast for {} downgraded for build {}: {}", app_id, build, change);
+    }
+}
+
+/// Emits the characters XML does not allow that were sanitized out of a payload.
+#[allow(unused_variables)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn sanitized(app_id: &str, report: &[Diagnostic]) {
+    #[cfg(feature = "tracing")]
+    for change in report {
+        tracing::debug!(target: "win_toast", app_id, %change, "toast payload sanitized");
+    }
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    for change in report {
+        log::debug!(target: "win_toast", "toast payload for {} sanitized: {}", app_id, change);
+    }
+}
+
+/// Emits a warning that something that happened to a toast could not be written to the history.
+#[allow(unused_variables)]
+pub(crate) fn history_failed(id: &str, error: &io::Error) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(target: "win_toast", id, %error, "toast history not written");
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::warn!(target: "win_toast", "toast history for {} not written: {}", id, error);
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Stage {
+    Build,
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Send,
+}
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Build => "build",
+            Stage::Send => "send",
+        }
+    }
+}
+
+/// Times a stage and reports the elapsed time when dropped.
+pub(crate) struct Timed {
+    stage: Stage,
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+}
+
+#[allow(unused_variables)]
+pub(crate) fn timed(stage: Stage, app_id: &str) -> Timed {
+    Timed {
+        stage,
+        start: Instant::now(),
+        #[cfg(feature = "tracing")]
+        span: match stage {
+            Stage::Build => tracing::debug_span!(target: "win_toast", "build", app_id, elapsed_us = tracing::field::Empty),
+            Stage::Send => tracing::debug_span!(target: "win_toast", "send", app_id, elapsed_us = tracing::field::Empty),
+        }.entered(),
+    }
+}
+
+impl Drop for Timed {
+    fn drop(&mut self) {
+        let elapsed_us = self.start.elapsed().as_micros() as u64;
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("elapsed_us", elapsed_us);
+            tracing::debug!(target: "win_toast", stage = self.stage.name(), elapsed_us, "stage finished");
+        }
+        #[cfg(all(feature = "log", not(feature = "tracing")))]
+        log::debug!(target: "win_toast", "{} finished in {}us", self.stage.name(), elapsed_us);
+        #[cfg(not(any(feature = "log", feature = "tracing")))]
+        let _ = (self.stage.name(), elapsed_us);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_global_state;
+
+    #[test]
+    fn test_redactor() {
+        let _global_state = lock_global_state();
+        assert_eq!(redact("secret code"), "secret code");
+        set_redactor(mask);
+        assert_eq!(redact("secret code"), "****** ****");
+        clear_redactor();
+        assert!(!has_redactor());
+    }
+}
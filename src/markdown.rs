@@ -0,0 +1,261 @@
+//! Markdown turned into the content of a toast binding.
+//!
+//! Toasts have no inline formatting, so each block of Markdown becomes an element of its own:
+//!
+//! - a heading becomes text in the style of the same level, `#` as [`TextStyle::Header`] down
+//!   to `####` as [`TextStyle::Subtitle`], and deeper ones as [`TextStyle::Base`];
+//! - a paragraph that is all emphasis or strong emphasis becomes [`TextStyle::Base`] text, and
+//!   any other paragraph plain text;
+//! - a pipe table becomes a group per row with a subgroup per column, the header row in
+//!   [`TextStyle::Base`];
+//! - an image becomes an inline image.
+//!
+//! Everything else keeps its text and loses its formatting, with a warning: emphasis within a
+//! line, links, strikethrough, lists, block quotes and code blocks. HTML and rules are left out.
+
+use pulldown_cmark::{Alignment, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+use crate::notification::*;
+use crate::validate::Diagnostic;
+
+/// The elements Markdown converts to, and the warnings for what it could not keep.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Converted {
+    pub elems: Vec<BindingInnerElement>,
+    /// Each at the path `line N` of the Markdown.
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl Converted {
+    /// A binding with the title and the converted elements.
+    pub fn into_binding(self, title: impl Into<String>) -> BindingElem {
+        BindingElem { title: (title.into(), None), elems: self.elems, ..BindingElem::default() }
+    }
+}
+
+pub fn convert(markdown: &str) -> Converted {
+    let mut converter = Converter::default();
+    // The events of a block end back at its start, so lines are looked up rather than counted on.
+    let newlines: Vec<usize> = markdown.match_indices('\n').map(|(at, _)| at).collect();
+    for (event, range) in Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH).into_offset_iter() {
+        converter.line = newlines.partition_point(|&at| at < range.start) + 1;
+        converter.event(event);
+    }
+    converter.flush(None);
+    Converted { elems: converter.elems, warnings: converter.warnings }
+}
+
+struct Table {
+    alignments: Vec<Alignment>,
+    rows: Vec<(bool, Vec<String>)>,
+    cells: Vec<String>,
+    in_head: bool,
+}
+
+#[derive(Default)]
+struct Converter {
+    line: usize,
+    elems: Vec<BindingInnerElement>,
+    warnings: Vec<Diagnostic>,
+    /// The text of the block being read.
+    text: String,
+    emphasis: usize,
+    /// Whether the block has text with and without emphasis.
+    emphasized: bool,
+    plain: bool,
+    /// The numbers of the lists the block is in, or `None` for bulleted ones.
+    lists: Vec<Option<u64>>,
+    table: Option<Table>,
+    /// The image whose alternative text is being read.
+    image: Option<Image>,
+}
+
+impl Converter {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(Tag::Heading { .. } | Tag::Paragraph) if self.lists.is_empty() => self.flush(None),
+            Event::End(TagEnd::Heading(level)) => self.flush(Some(heading_style(level))),
+            Event::End(TagEnd::Paragraph) if self.lists.is_empty() => self.flush(None),
+            Event::End(TagEnd::Paragraph) => self.text.push('\n'),
+            Event::Start(Tag::List(start)) => {
+                self.warn("lists are shown as lines of text");
+                self.flush(None);
+                self.lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                self.flush(None);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.text = format!("{}{}", "    ".repeat(self.lists.len() - 1), marker);
+            }
+            Event::End(TagEnd::Item) => self.flush(None),
+            Event::Start(Tag::BlockQuote(_)) => self.warn("block quotes are shown as plain text"),
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.warn("code blocks are shown as plain text");
+                self.flush(None);
+            }
+            Event::End(TagEnd::CodeBlock) => self.flush(None),
+            Event::Start(Tag::HtmlBlock) | Event::Html(_) | Event::InlineHtml(_) => self.warn("HTML is left out"),
+            Event::Rule => self.warn("rules are left out"),
+            Event::Start(Tag::Emphasis | Tag::Strong) => self.emphasis += 1,
+            Event::End(TagEnd::Emphasis | TagEnd::Strong) => self.emphasis -= 1,
+            Event::Start(Tag::Strikethrough) => self.warn("strikethrough is shown as plain text"),
+            Event::Start(Tag::Link { .. }) => self.warn("links cannot be clicked in a toast, only their text is shown"),
+            Event::Start(Tag::Image { dest_url, .. }) => self.image = Some(Image { src: dest_url.into_string(), ..Image::default() }),
+            Event::End(TagEnd::Image) => {
+                if let Some(image) = self.image.take() {
+                    self.flush(None);
+                    self.elems.push(BindingInnerElement::Image(image));
+                }
+            }
+            Event::Start(Tag::Table(alignments)) => {
+                self.flush(None);
+                self.table = Some(Table { alignments, rows: Vec::new(), cells: Vec::new(), in_head: false });
+            }
+            Event::Start(Tag::TableHead) => self.table_mut().in_head = true,
+            Event::Start(Tag::TableCell) => self.text.clear(),
+            Event::End(TagEnd::TableCell) => {
+                let cell = std::mem::take(&mut self.text).trim().to_string();
+                (self.emphasized, self.plain) = (false, false);
+                self.table_mut().cells.push(cell);
+            }
+            Event::End(TagEnd::TableHead | TagEnd::TableRow) => {
+                let table = self.table_mut();
+                let cells = std::mem::take(&mut table.cells);
+                table.rows.push((table.in_head, cells));
+                table.in_head = false;
+            }
+            Event::End(TagEnd::Table) => {
+                if let Some(table) = self.table.take() {
+                    self.elems.extend(table.rows.into_iter().map(|(head, cells)| {
+                        BindingInnerElement::Group(cells.into_iter().enumerate().map(|(i, text)| {
+                            vec![SubgroupElement::Text_(InnerText {
+                                text,
+                                hint_style: if head { TextStyle::Base } else { TextStyle::Default },
+                                hint_align: column_align(table.alignments.get(i)),
+                                ..InnerText::default()
+                            })]
+                        }).collect())
+                    }));
+                }
+            }
+            Event::Text(text) | Event::Code(text) => self.push(&text),
+            Event::SoftBreak => self.push(" "),
+            Event::HardBreak => self.push("\n"),
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        if let Some(image) = &mut self.image {
+            image.alt.push_str(text);
+            return;
+        }
+        if !text.trim().is_empty() {
+            if self.emphasis > 0 {
+                self.emphasized = true;
+            } else {
+                self.plain = true;
+            }
+        }
+        self.text.push_str(text);
+    }
+
+    /// Ends the block being read, in the style unless it is all emphasis.
+    fn flush(&mut self, style: Option<TextStyle>) {
+        let text = std::mem::take(&mut self.text);
+        let (emphasized, plain) = (std::mem::take(&mut self.emphasized), std::mem::take(&mut self.plain));
+        if text.trim().is_empty() {
+            return;
+        }
+        let style = match style {
+            Some(style) => Some(style),
+            None if emphasized && !plain => Some(TextStyle::Base),
+            None => {
+                if emphasized {
+                    self.warn("emphasis within a line is shown as plain text");
+                }
+                None
+            }
+        };
+        let text = text.trim_start_matches('\n').trim_end().to_string();
+        self.elems.push(match style {
+            Some(hint_style) => BindingInnerElement::Group(vec![vec![SubgroupElement::Text_(InnerText { text, hint_style, ..InnerText::default() })]]),
+            None => BindingInnerElement::Text(text),
+        });
+    }
+
+    fn warn(&mut self, message: &str) {
+        let path = format!("line {}", self.line);
+        if !self.warnings.iter().any(|warning| warning.path == path && warning.message == message) {
+            self.warnings.push(Diagnostic::warning(path, message));
+        }
+    }
+
+    fn table_mut(&mut self) -> &mut Table {
+        self.table.as_mut().expect("table events are inside a table")
+    }
+}
+
+fn heading_style(level: HeadingLevel) -> TextStyle {
+    match level {
+        HeadingLevel::H1 => TextStyle::Header,
+        HeadingLevel::H2 => TextStyle::Subheader,
+        HeadingLevel::H3 => TextStyle::Title,
+        HeadingLevel::H4 => TextStyle::Subtitle,
+        HeadingLevel::H5 | HeadingLevel::H6 => TextStyle::Base,
+    }
+}
+
+fn column_align(alignment: Option<&Alignment>) -> TextAlign {
+    match alignment {
+        Some(Alignment::Left) => TextAlign::Left,
+        Some(Alignment::Center) => TextAlign::Center,
+        Some(Alignment::Right) => TextAlign::Right,
+        Some(Alignment::None) | None => TextAlign::Default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown() {
+        let converted = convert("## Deploy *finished*\n\n**All green**\n\nSee [the log](https://ci.example.com) for *details*.\n\n\
+            | Job | Time |\n|:----|-----:|\n| build | 2m |\n| test | 5m |\n\n![chart](https://ci.example.com/chart.png)\n");
+        let styled = |text: &str, hint_style: TextStyle, hint_align: TextAlign| {
+            SubgroupElement::Text_(InnerText { text: text.to_string(), hint_style, hint_align, ..InnerText::default() })
+        };
+        assert_eq!(converted.elems, vec![
+            BindingInnerElement::Group(vec![vec![styled("Deploy finished", TextStyle::Subheader, TextAlign::Default)]]),
+            BindingInnerElement::Group(vec![vec![styled("All green", TextStyle::Base, TextAlign::Default)]]),
+            BindingInnerElement::Text("See the log for details.".to_string()),
+            BindingInnerElement::Group(vec![vec![styled("Job", TextStyle::Base, TextAlign::Left)], vec![styled("Time", TextStyle::Base, TextAlign::Right)]]),
+            BindingInnerElement::Group(vec![vec![styled("build", TextStyle::Default, TextAlign::Left)], vec![styled("2m", TextStyle::Default, TextAlign::Right)]]),
+            BindingInnerElement::Group(vec![vec![styled("test", TextStyle::Default, TextAlign::Left)], vec![styled("5m", TextStyle::Default, TextAlign::Right)]]),
+            BindingInnerElement::Image(Image { src: "https://ci.example.com/chart.png".to_string(), alt: "chart".to_string(), ..Image::default() }),
+        ]);
+        let warnings: Vec<String> = converted.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, vec![
+            "warning: line 5: links cannot be clicked in a toast, only their text is shown",
+            "warning: line 5: emphasis within a line is shown as plain text",
+        ]);
+
+        let converted = convert("1. one\n2. two\n   - nested\n");
+        assert_eq!(converted.elems, vec![
+            BindingInnerElement::Text("1. one".to_string()),
+            BindingInnerElement::Text("2. two".to_string()),
+            BindingInnerElement::Text("    • nested".to_string()),
+        ]);
+        assert_eq!(converted.warnings.len(), 2);
+    }
+}
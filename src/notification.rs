@@ -1,10 +1,21 @@
+use serde::{Deserialize, Deserializer, Serialize};
+#[cfg(windows)]
 use windows::core::*;
-use windows::Data::Xml::Dom::{IXmlNode, XmlDocument, XmlElement};
+#[cfg(windows)]
+use windows::Data::Xml::Dom::{IXmlNode, XmlDocument};
+#[cfg(windows)]
 use windows::UI::Notifications::{ToastNotification, ToastNotificationManager, ToastNotifier};
-use crate::notification::HintCrop::Circle;
+#[cfg(windows)]
+use crate::logging::{self, Stage};
+#[cfg(windows)]
+use crate::sanitize::{self, sanitize_xml};
+#[cfg(windows)]
+use crate::validate::Diagnostic;
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum Duration {
+    #[default]
     Default,
     /// 7 seconds
     Short,
@@ -13,8 +24,10 @@ pub enum Duration {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum Scenario {
     /// The normal toast behavior.
+    #[default]
     Default,
     /// This will be displayed pre-expanded and stay on the user's screen till dismissed. Audio will loop by default and will use alarm audio.
     Alarm,
@@ -27,16 +40,20 @@ pub enum Scenario {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum HintCrop {
     /// The image is not cropped and displayed as a square.
+    #[default]
     Default,
     /// The image is cropped into a circle.
     Circle,
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum TextStyle {
     /// Default value. Style is determined by the renderer.
+    #[default]
     Default,
     /// Smaller than paragraph font size.
     Caption,
@@ -75,8 +92,10 @@ pub enum TextStyle {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum TextAlign {
     /// Default value. Alignment is automatically determined by the renderer.
+    #[default]
     Default,
     /// Alignment determined by the current language and culture.
     Auto,
@@ -89,8 +108,10 @@ pub enum TextAlign {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum ImageAlign {
     /// Default value. Alignment behavior determined by renderer.
+    #[default]
     Default,
     /// Image stretches to fill available width (and potentially available height too, depending on where the image is placed).
     Stretch,
@@ -103,14 +124,19 @@ pub enum ImageAlign {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum SubgroupElement {
+    #[serde(rename = "Text")]
     Text_(InnerText),
+    #[serde(rename = "Image")]
     Image_(Image),
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum SoundSrc {
     /// Not support looping
+    #[default]
     Default,
     /// Not support looping
     IM,
@@ -142,15 +168,21 @@ pub enum SoundSrc {
     Call10,
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct InnerText {
     pub text: String,
     pub hint_style: TextStyle,
+    /// Written as `hint-wrap`, which older versions misspelled `hint-warp`; the field keeps the
+    /// old name.
     pub hint_warp: Option<bool>,
     pub hint_max_lines: Option<u32>,
     pub hint_min_lines: Option<u32>,
     pub hint_align: TextAlign,
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Image {
     /// The URI of the image source, using one of these protocol handlers:
     ///
@@ -182,6 +214,7 @@ pub struct Image {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BindingInnerElement {
     Text(String),
     Group(Vec<Vec<SubgroupElement>>),
@@ -189,15 +222,18 @@ pub enum BindingInnerElement {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum InputType {
     /// The element is default text(optional).
     Text(Option<String>),
-    /// The elements are (defaultSelectionBoxItemId and selections).
+    /// The elements are (the id of the selection chosen at first and selections).
     ///
-    /// The elements in the selection item are (id and title).
+    /// The elements in the selection item are (id and content).
     Selection(Option<String>, Vec<(String, String)>),
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Input {
     /// The ID associated with the input.
     pub id: String,
@@ -210,8 +246,10 @@ pub struct Input {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum ActivationType {
     /// Your foreground app is launched.
+    #[default]
     Foreground,
     /// Your corresponding background task is triggered, and you can execute code in the background without interrupting the user.
     Background,
@@ -220,11 +258,15 @@ pub enum ActivationType {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum ActionPlacement {
+    #[default]
     Default,
     ContextMenu,
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Action {
     /// The content displayed on the button.
     pub content: String,
@@ -260,493 +302,209 @@ pub struct Action {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ActionsElem {
+    #[serde(rename = "Action")]
     Action_(Action),
+    #[serde(rename = "Input")]
     Input_(Input),
 }
 
+/// The layout of a binding. The legacy templates of Windows 8 hold a fixed number of texts, the
+/// first of them bold, and the `ToastImageAndText` ones an image; Windows 10 and later show them
+/// like `ToastGeneric`.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BindingTemplate {
+    #[default]
+    ToastGeneric,
+    /// A text wrapped over up to three lines.
+    ToastText01,
+    /// A bold line and a text wrapped over up to two lines.
+    ToastText02,
+    /// A bold text wrapped over up to two lines and a line of text.
+    ToastText03,
+    /// A bold line and two lines of text.
+    ToastText04,
+    ToastImageAndText01,
+    ToastImageAndText02,
+    ToastImageAndText03,
+    ToastImageAndText04,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BindingElem {
     pub icon: Option<Image>,
     pub hero: Option<Image>,
     /// The elements are (text, hint-maxLines).
+    ///
+    /// In the serde form this may also be written as just the text.
+    #[serde(deserialize_with = "deserialize_title")]
     pub title: (String, Option<u32>),
     pub elems: Vec<BindingInnerElement>,
+    /// A line at the bottom of the toast naming the source of its content.
+    pub attribution: Option<String>,
+    /// The language of the text, as a BCP-47 tag such as "en-US" or "fr".
+    pub lang: Option<String>,
+    /// With a legacy template the title is the first text and the icon the image, and the binding
+    /// holds nothing but texts.
+    pub template: BindingTemplate,
+}
+
+/// Groups toasts under a heading in the action center. Toasts with the same header id are shown
+/// together, newest first.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Header {
+    pub id: String,
+    pub title: String,
+    /// Passed to the app when the user clicks the header.
+    pub arguments: String,
+    /// Only Foreground and Protocol are supported.
+    pub activation_type: ActivationType,
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Toast {
     pub app_id: String,
     pub binding_elems: Vec<BindingElem>,
     pub duration: Duration,
     pub scenario: Scenario,
-    /// The elements are (loop, silent and src).
+    /// The elements are (loop, silent and src). Only the alarm and call sounds loop; for the
+    /// others `loop="false"` is written whatever loop is.
     pub audio: Option<(bool, bool, SoundSrc)>,
     pub actions: Vec<ActionsElem>,
     pub use_btn_style: bool,
     pub display_timestamp: Option<String>,
+    /// Identifies the toast within its group, so a later toast with the same tag replaces it.
+    pub tag: Option<String>,
+    /// Toasts in a group can be replaced and removed together.
+    pub group: Option<String>,
+    pub header: Option<Header>,
+    /// The channel the toast is sent on by a [`ChannelNotifier`](crate::channel::ChannelNotifier),
+    /// the default channel if none is set.
+    pub channel: Option<String>,
+    /// Puts the toast straight into the action center without showing it on screen.
+    pub suppress_popup: bool,
+}
+
+impl Default for InputType {
+    fn default() -> Self {
+        InputType::Text(None)
+    }
+}
+
+fn deserialize_title<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<(String, Option<u32>), D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Title {
+        Text(String),
+        Single([String; 1]),
+        Pair(String, Option<u32>),
+    }
+    Ok(match Title::deserialize(deserializer)? {
+        Title::Text(text) | Title::Single([text]) => (text, None),
+        Title::Pair(text, max_lines) => (text, max_lines),
+    })
 }
 
 impl Toast {
     pub const POWERSHELL_APP_ID: &'static str = "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\
                                                  \\WindowsPowerShell\\v1.0\\powershell.exe";
+}
 
+#[cfg(windows)]
+impl Toast {
     pub fn create_notification(&self) -> Result<ToastNotification> {
+        if self.images().any(|image| image.src.is_empty()) {
+            return Err(Error::new(HRESULT(-1), HSTRING::from("src is empty")));
+        }
+        if logging::payload_enabled() {
+            logging::sanitized(&self.app_id, &self.sanitize_report());
+        }
         let xml: XmlDocument = XmlDocument::new()?;
-        let toast_elem: XmlElement = xml.CreateElement(w!("toast"))?;
-        if self.use_btn_style {
-            toast_elem.SetAttribute(w!("useButtonStyle"), &HSTRING::from(self.use_btn_style.to_string()))?;
+        xml.LoadXml(&HSTRING::from(self.to_xml()))?;
+        let notification = ToastNotification::CreateToastNotification(&xml)?;
+        if let Some(tag) = &self.tag {
+            notification.SetTag(&HSTRING::from(tag))?;
         }
-        if let Some(timestamp) = &self.display_timestamp {
-            toast_elem.SetAttribute(w!("displayTimestamp"), &HSTRING::from(timestamp))?;
+        if let Some(group) = &self.group {
+            notification.SetGroup(&HSTRING::from(group))?;
         }
-        {
-            match self.duration {
-                Duration::Default => {}
-                Duration::Short => {
-                    toast_elem.SetAttribute(w!("duration"), w!("short"))?;
-                }
-                Duration::Long => {
-                    toast_elem.SetAttribute(w!("duration"), w!("long"))?;
-                }
-            };
-            match self.scenario {
-                Scenario::Default => {}
-                Scenario::Alarm => {
-                    toast_elem.SetAttribute(w!("scenario"), w!("alarm"))?;
-                }
-                Scenario::Reminder => {
-                    toast_elem.SetAttribute(w!("scenario"), w!("reminder"))?;
-                }
-                Scenario::IncomingCall => {
-                    toast_elem.SetAttribute(w!("scenario"), w!("incomingCall"))?;
-                }
-                Scenario::Urgent => {
-                    toast_elem.SetAttribute(w!("scenario"), w!("urgent"))?;
-                }
-            };
-            let visual_elem: XmlElement = xml.CreateElement(w!("visual"))?;
-            {
-                for elem in &self.binding_elems {
-                    let binding_elem: XmlElement = xml.CreateElement(w!("binding"))?;
-                    {
-                        binding_elem.SetAttribute(w!("template"), w!("ToastGeneric"))?;
-                        if let Some(image) = &elem.icon {
-                            add_image_elem_with_placement(image, &xml, &binding_elem, "appLogoOverride", false)?;
-                        }
-                        if let Some(image) = &elem.hero {
-                            add_image_elem_with_placement(image, &xml, &binding_elem, "hero", false)?;
-                        }
-                        let title_elem: XmlElement = xml.CreateElement(w!("text"))?;
-                        {
-                            if let Some(value) = &elem.title.1 {
-                                title_elem.SetAttribute(w!("hint-maxLines"), &HSTRING::from(value.to_string()))?;
-                            }
-                            title_elem.SetInnerText(&HSTRING::from(&elem.title.0))?;
-                            binding_elem.AppendChild(InParam::owned(IXmlNode::try_from(title_elem)?))?;
-                        }
-                        for elem in &elem.elems {
-                            match elem {
-                                BindingInnerElement::Text(text) => {
-                                    let text_elem: XmlElement = xml.CreateElement(w!("text"))?;
-                                    {
-                                        text_elem.SetInnerText(&HSTRING::from(text))?;
-                                    }
-                                    binding_elem.AppendChild(InParam::owned(IXmlNode::try_from(text_elem)?))?;
-                                }
-                                BindingInnerElement::Group(ref group_elems) => {
-                                    add_group(group_elems, &xml, &binding_elem)?;
-                                }
-                                BindingInnerElement::Image(ref image) => {
-                                    add_image_elem(image, &xml, &binding_elem)?;
-                                }
-                            }
-                        }
-                        visual_elem.AppendChild(InParam::owned(IXmlNode::try_from(binding_elem)?))?;
-                    }
-                }
-            }
-            toast_elem.AppendChild(InParam::owned(IXmlNode::try_from(visual_elem)?))?;
-            add_audio(&self.audio, &xml, &toast_elem)?;
-            add_actions(&self.actions, &xml, &toast_elem)?;
+        if self.suppress_popup {
+            notification.SetSuppressPopup(true)?;
         }
-
-        xml.AppendChild(InParam::owned(IXmlNode::try_from(toast_elem)?))?;
-        println!("{}", xml.GetXml()?);
-        ToastNotification::CreateToastNotification(&xml)
+        Ok(notification)
     }
 
     pub fn show(&self) -> Result<()> {
+        let notification = self.create_notification()?;
+        let _timed = logging::timed(Stage::Send, &self.app_id);
         let notifier: ToastNotifier = ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(&self.app_id))?;
-        notifier.Show(&self.create_notification()?)?;
+        notifier.Show(&notification)?;
         Ok(())
     }
 
-    pub fn show_with_xml(app_id: &str, xml_content: &str) -> Result<()> {
+    /// Shows a payload written by hand, with the characters XML does not allow sanitized like
+    /// those of [`Toast::to_xml`], and returns a warning for each of them. When it still does not
+    /// load, the error says where it fails.
+    pub fn show_with_xml(app_id: &str, xml_content: &str) -> Result<Vec<Diagnostic>> {
+        let _timed = logging::timed(Stage::Send, app_id);
+        let (xml_content, report) = sanitize_xml(xml_content, sanitize::policy());
+        logging::sanitized(app_id, &report);
         let xml: XmlDocument = XmlDocument::new()?;
-        xml.LoadXml(&HSTRING::from(xml_content))?;
+        xml.LoadXml(&HSTRING::from(xml_content.as_str())).map_err(|e| match roxmltree::Document::parse(&xml_content) {
+            Err(parse_error) => Error::new(e.code(), HSTRING::from(format!("{}: {}", e.message(), parse_error))),
+            Ok(_) => e,
+        })?;
+        log_payload(app_id, &xml)?;
         let notification: ToastNotification = ToastNotification::CreateToastNotification(&xml)?;
         let notifier: ToastNotifier = ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(app_id))?;
         notifier.Show(&notification)?;
-        Ok(())
+        Ok(report)
     }
 }
 
-fn add_actions(actions_elems: &Vec<ActionsElem>, xml: &XmlDocument, toast_elem: &XmlElement) -> Result<()> {
-    if actions_elems.is_empty() {
-        return Ok(());
-    }
-    let actions_elem: XmlElement = xml.CreateElement(w!("actions"))?;
-    let (mut action_count, mut input_count) = (0, 0);
-    for elem in actions_elems {
-        if action_count >= 5 && input_count >= 5 {
-            break;
-        }
-        match elem {
-            ActionsElem::Action_(action) => {
-                if action_count < 5 {
-                    let action_elem: XmlElement = xml.CreateElement(w!("action"))?;
-                    action_elem.SetAttribute(w!("content"), &HSTRING::from(&action.content))?;
-                    action_elem.SetAttribute(w!("arguments"), &HSTRING::from(&action.arguments))?;
-                    match action.activation_type {
-                        ActivationType::Foreground => {
-                            action_elem.SetAttribute(w!("activationType"), &HSTRING::from("foreground"))?;
-                        }
-                        ActivationType::Background => {
-                            action_elem.SetAttribute(w!("activationType"), &HSTRING::from("background"))?;
-                        }
-                        ActivationType::Protocol => {
-                            action_elem.SetAttribute(w!("activationType"), &HSTRING::from("protocol"))?;
-                        }
-                    }
-                    if matches!(action.placement, ActionPlacement::ContextMenu) {
-                        action_elem.SetAttribute(w!("activationType"), w!("contextMenu"))?;
-                    }
-                    if let Some(value) = &action.image_uri {
-                        action_elem.SetAttribute(w!("imageUri"), &HSTRING::from(value))?;
-                    }
-                    if let Some(value) = &action.hint_input_id {
-                        action_elem.SetAttribute(w!("hint-inputId"), &HSTRING::from(value))?;
-                    }
-                    if let Some(value) = &action.hint_button_style {
-                        match value.as_str() {
-                            "Success" | "Critical" => {
-                                action_elem.SetAttribute(w!("hint-buttonStyle"), &HSTRING::from(value))?;
-                            }
-                            _ => {}
-                        }
-                    }
-                    if let Some(value) = &action.hint_tool_tip {
-                        action_elem.SetAttribute(w!("hint-toolTip"), &HSTRING::from(value))?;
-                    }
-                    actions_elem.AppendChild(InParam::owned(IXmlNode::try_from(action_elem)?))?;
-                    action_count += 1;
-                }
-            }
-            ActionsElem::Input_(input) => {
-                if input_count < 5 {
-                    let input_elem: XmlElement = xml.CreateElement(w!("input"))?;
-                    input_elem.SetAttribute(w!("id"), &HSTRING::from(&input.id))?;
-                    let type_ = match &input.type_ {
-                        InputType::Text(default) => {
-                            if let Some(content) = &input.place_holder_content {
-                                input_elem.SetAttribute(w!("placeHolderContent"), &HSTRING::from(content))?;
-                            }
-                            if let Some(value) = default {
-                                input_elem.SetAttribute(w!("defaultInput"), &HSTRING::from(value))?;
-                            }
-                            Ok::<&str, Error>("text")
-                        }
-                        InputType::Selection(default_id, selections) => {
-                            if let Some(value) = default_id {
-                                input_elem.SetAttribute(w!("defaultSelectionBoxItemId"), &HSTRING::from(value))?;
-                            }
-                            let mut selection_count = 0;
-                            for (id, title) in selections {
-                                if selection_count >= 5 {
-                                    break;
-                                }
-                                let selection_elem: XmlElement = xml.CreateElement(w!("selection"))?;
-                                selection_elem.SetAttribute(w!("id"), &HSTRING::from(id))?;
-                                selection_elem.SetAttribute(w!("title"), &HSTRING::from(title))?;
-                                input_elem.AppendChild(InParam::owned(IXmlNode::try_from(selection_elem)?))?;
-                                selection_count += 1;
-                            }
-                            Ok::<&str, Error>("selection")
-                        }
-                    }?;
-                    input_elem.SetAttribute(w!("type"), &HSTRING::from(type_))?;
-                    if let Some(title) = &input.title {
-                        input_elem.SetAttribute(w!("title"), &HSTRING::from(title))?;
+impl Toast {
+    /// Every image of the toast, in document order.
+    pub fn images(&self) -> impl Iterator<Item = &Image> {
+        self.binding_elems.iter().flat_map(|binding| {
+            binding.icon.iter()
+                .chain(binding.hero.iter())
+                .chain(binding.elems.iter().flat_map(|elem| -> Box<dyn Iterator<Item = &Image>> {
+                    match elem {
+                        BindingInnerElement::Text(_) => Box::new(std::iter::empty()),
+                        BindingInnerElement::Image(image) => Box::new(std::iter::once(image)),
+                        BindingInnerElement::Group(subgroups) => Box::new(subgroups.iter().flatten().filter_map(|elem| match elem {
+                            SubgroupElement::Image_(image) => Some(image),
+                            SubgroupElement::Text_(_) => None,
+                        })),
                     }
-                    actions_elem.AppendChild(InParam::owned(IXmlNode::try_from(input_elem)?))?;
-                    input_count += 1;
-                }
-            }
-        }
-    }
-    toast_elem.AppendChild(InParam::owned(IXmlNode::try_from(actions_elem)?))?;
-    Ok(())
-}
-
-fn add_image_elem(image: &Image, xml: &XmlDocument, binding_elem: &XmlElement) -> Result<()> {
-    add_image_elem_with_placement(image, xml, binding_elem, "", true)
-}
-
-fn add_image_elem_with_placement(image: &Image, xml: &XmlDocument, binding_elem: &XmlElement, placement: &str, in_group: bool) -> Result<()> {
-    if image.src.is_empty() {
-        return Err(Error::new(HRESULT(-1), HSTRING::from("src is empty")));
+                }))
+        })
     }
-    let image_elem: XmlElement = xml.CreateElement(w!("image"))?;
-    {
-        image_elem.SetAttribute(w!("src"), &HSTRING::from(&image.src))?;
-        if !placement.is_empty() {
-            image_elem.SetAttribute(w!("placement"), &HSTRING::from(placement))?;
-        }
-        if !image.alt.is_empty() {
-            image_elem.SetAttribute(w!("alt"), &HSTRING::from(&image.alt))?;
-        }
-        if matches!(image.hint_crop, Circle) {
-            image_elem.SetAttribute(w!("hint-crop"), w!("circle"))?;
-        }
-        if in_group {
-            match image.hint_align {
-                ImageAlign::Default => {}
-                ImageAlign::Stretch => {
-                    image_elem.SetAttribute(w!("hint-align"), w!("stretch"))?;
-                }
-                ImageAlign::Left => {
-                    image_elem.SetAttribute(w!("hint-align"), w!("left"))?;
-                }
-                ImageAlign::Center => {
-                    image_elem.SetAttribute(w!("hint-align"), w!("center"))?;
-                }
-                ImageAlign::Right => {
-                    image_elem.SetAttribute(w!("hint-align"), w!("right"))?;
-                }
-            }
-        }
-        binding_elem.AppendChild(InParam::owned(IXmlNode::try_from(image_elem)?))?;
-    }
-    Ok(())
 }
 
-fn add_in_group_text_elem(text: &InnerText, xml: &XmlDocument, subgroup_elem: &XmlElement) -> Result<()> {
-    let text_elem: XmlElement = xml.CreateElement(w!("text"))?;
-    {
-        if let Some(value) = text.hint_max_lines {
-            text_elem.SetAttribute(w!("hint-maxLines"), &HSTRING::from(value.to_string()))?;
-        }
-        if let Some(value) = text.hint_min_lines {
-            text_elem.SetAttribute(w!("hint-minLines"), &HSTRING::from(value.to_string()))?;
-        }
-        if let Some(value) = text.hint_warp {
-            if value {
-                text_elem.SetAttribute(w!("hint-warp"), w!("true"))?;
-            }
-        }
-        match text.hint_style {
-            TextStyle::Default => {}
-            TextStyle::Caption => {
-                text_elem.SetAttribute(w!("hint-style"), w!("caption"))?;
-            }
-            TextStyle::CaptionSubtle => {
-                text_elem.SetAttribute(w!("hint-style"), w!("captionSubtle"))?;
-            }
-            TextStyle::Body => {
-                text_elem.SetAttribute(w!("hint-style"), w!("body"))?;
-            }
-            TextStyle::BodySubtle => {
-                text_elem.SetAttribute(w!("hint-style"), w!("bodySubtle"))?;
-            }
-            TextStyle::Base => {
-                text_elem.SetAttribute(w!("hint-style"), w!("base"))?;
-            }
-            TextStyle::BaseSubtle => {
-                text_elem.SetAttribute(w!("hint-style"), w!("baseSubtle"))?;
-            }
-            TextStyle::Subtitle => {
-                text_elem.SetAttribute(w!("hint-style"), w!("subtitle"))?;
-            }
-            TextStyle::SubtitleSubtle => {
-                text_elem.SetAttribute(w!("hint-style"), w!("subtitleSubtle"))?;
-            }
-            TextStyle::Title => {
-                text_elem.SetAttribute(w!("hint-style"), w!("title"))?;
-            }
-            TextStyle::TitleSubtle => {
-                text_elem.SetAttribute(w!("hint-style"), w!("titleSubtle"))?;
-            }
-            TextStyle::TitleNumeral => {
-                text_elem.SetAttribute(w!("hint-style"), w!("titleNumeral"))?;
-            }
-            TextStyle::Subheader => {
-                text_elem.SetAttribute(w!("hint-style"), w!("subheader"))?;
-            }
-            TextStyle::SubheaderSubtle => {
-                text_elem.SetAttribute(w!("hint-style"), w!("subheaderSubtle"))?;
-            }
-            TextStyle::SubheaderNumeral => {
-                text_elem.SetAttribute(w!("hint-style"), w!("subheaderNumeral"))?;
-            }
-            TextStyle::Header => {
-                text_elem.SetAttribute(w!("hint-style"), w!("header"))?;
-            }
-            TextStyle::HeaderSubtle => {
-                text_elem.SetAttribute(w!("hint-style"), w!("headerSubtle"))?;
-            }
-            TextStyle::HeaderNumeral => {
-                text_elem.SetAttribute(w!("hint-style"), w!("headerNumeral"))?;
-            }
-        }
-        match text.hint_align {
-            TextAlign::Default => {}
-            TextAlign::Auto => {
-                text_elem.SetAttribute(w!("hint-align"), w!("auto"))?;
-            }
-            TextAlign::Left => {
-                text_elem.SetAttribute(w!("hint-align"), w!("left"))?;
-            }
-            TextAlign::Center => {
-                text_elem.SetAttribute(w!("hint-align"), w!("center"))?;
-            }
-            TextAlign::Right => {
-                text_elem.SetAttribute(w!("hint-align"), w!("right"))?;
-            }
-        }
-        text_elem.SetInnerText(&HSTRING::from(&text.text))?;
-    }
-    subgroup_elem.AppendChild(InParam::owned(IXmlNode::try_from(text_elem)?))?;
-    Ok(())
-}
-
-fn add_group(group_elems: &Vec<Vec<SubgroupElement>>, xml: &XmlDocument, binding_elem: &XmlElement) -> Result<()> {
-    if group_elems.is_empty() {
+#[cfg(windows)]
+/// Dumps the payload at debug level, passing the content of every `<text>` element through
+/// the redactor installed with [`logging::set_redactor`].
+fn log_payload(app_id: &str, xml: &XmlDocument) -> Result<()> {
+    if !logging::payload_enabled() {
         return Ok(());
     }
-    let group_elem: XmlElement = xml.CreateElement(w!("group"))?;
-    for subgroup_elems in group_elems.iter()
-        .filter(|elem| !elem.is_empty()) {
-        let subgroup_elem: XmlElement = xml.CreateElement(w!("subgroup"))?;
-        {
-            for elem in subgroup_elems {
-                match elem {
-                    SubgroupElement::Text_(text) => {
-                        add_in_group_text_elem(text, &xml, &subgroup_elem)?;
-                    }
-                    SubgroupElement::Image_(image) => {
-                        add_image_elem(image, &xml, &subgroup_elem)?;
-                    }
-                }
-            }
-            group_elem.AppendChild(InParam::owned(IXmlNode::try_from(subgroup_elem)?))?;
-        }
+    if !logging::has_redactor() {
+        logging::payload(app_id, &xml.GetXml()?.to_string());
+        return Ok(());
     }
-    binding_elem.AppendChild(InParam::owned(IXmlNode::try_from(group_elem)?))?;
-    Ok(())
-}
-
-fn add_audio(audio: &Option<(bool, bool, SoundSrc)>, xml: &XmlDocument, toast_elem: &XmlElement) -> Result<()> {
-    if let Some((loop_, silent, src)) = audio {
-        let audio_elem: XmlElement = xml.CreateElement(w!("audio"))?;
-        audio_elem.SetAttribute(w!("loop"), w!("false"))?;
-        audio_elem.SetAttribute(w!("silent"), &HSTRING::from(silent.to_string()))?;
-        match src {
-            SoundSrc::Default => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Default"))?;
-            }
-            SoundSrc::IM => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.IM"))?;
-            }
-            SoundSrc::Mail => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Mail"))?;
-            }
-            SoundSrc::Reminder => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Reminder"))?;
-            }
-            SoundSrc::SMS => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.SMS"))?;
-            }
-            SoundSrc::Alarm => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Alarm2 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm2"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Alarm3 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm3"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Alarm4 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm4"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Alarm5 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm5"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Alarm6 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm6"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Alarm7 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm7"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Alarm8 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm8"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Alarm9 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm9"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Alarm10 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Alarm10"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call2 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call2"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call3 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call3"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call4 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call4"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call5 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call5"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call6 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call6"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call7 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call7"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call8 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call8"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call9 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call9"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-            SoundSrc::Call10 => {
-                audio_elem.SetAttribute(w!("src"), w!("ms-winsoundevent:Notification.Looping.Call10"))?;
-                audio_elem.SetAttribute(w!("loop"), &HSTRING::from(loop_.to_string()))?;
-            }
-        }
-        toast_elem.AppendChild(InParam::owned(IXmlNode::try_from(audio_elem)?))?;
+    let copy: XmlDocument = XmlDocument::new()?;
+    copy.LoadXml(&xml.GetXml()?)?;
+    let texts = copy.GetElementsByTagName(w!("text"))?;
+    for i in 0..texts.Length()? {
+        let text: IXmlNode = texts.Item(i)?;
+        text.SetInnerText(&HSTRING::from(logging::redact(&text.InnerText()?.to_string())))?;
     }
+    logging::payload(app_id, &copy.GetXml()?.to_string());
     Ok(())
 }
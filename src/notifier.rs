@@ -0,0 +1,514 @@
+//! Backends that deliver toasts.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::notification::Toast;
+
+#[derive(Debug)]
+pub enum NotifyError {
+    /// There is no notification platform to deliver to on this OS.
+    Unsupported,
+    /// The notification platform rejected the toast.
+    Platform(String),
+    /// No toast shown by this notifier has the id.
+    NotFound(String),
+    /// A [`SendQueue`](crate::queue::SendQueue) is full and rejects toasts until it drains.
+    QueueFull,
+    /// The toast names a channel that is not registered.
+    UnknownChannel(String),
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyError::Unsupported => f.write_str("toasts can only be shown on Windows"),
+            NotifyError::Platform(message) => write!(f, "the toast could not be shown: {}", message),
+            NotifyError::NotFound(id) => write!(f, "no toast has the id {}", id),
+            NotifyError::QueueFull => f.write_str("too many toasts are waiting to be shown"),
+            NotifyError::UnknownChannel(channel) => write!(f, "there is no channel named {:?}", channel),
+        }
+    }
+}
+
+impl std::error::Error for NotifyError {}
+
+#[cfg(windows)]
+impl From<windows::core::Error> for NotifyError {
+    fn from(e: windows::core::Error) -> Self {
+        NotifyError::Platform(e.message().to_string())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DismissReason {
+    /// The user closed the toast.
+    UserCanceled,
+    /// The app removed the toast.
+    ApplicationHidden,
+    /// The toast timed out and moved to the action center.
+    TimedOut,
+}
+
+/// Something that happened to a toast after it was shown.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ToastEvent {
+    /// The user clicked the toast or one of its buttons.
+    Activated {
+        id: String,
+        /// The arguments of the clicked button, or the launch arguments of the toast.
+        arguments: String,
+        /// The values of the inputs by input id.
+        user_input: BTreeMap<String, String>,
+    },
+    Dismissed {
+        id: String,
+        reason: DismissReason,
+    },
+    Failed {
+        id: String,
+        message: String,
+    },
+}
+
+impl ToastEvent {
+    /// The id of the toast the event is about.
+    pub fn id(&self) -> &str {
+        match self {
+            ToastEvent::Activated { id, .. } | ToastEvent::Dismissed { id, .. } | ToastEvent::Failed { id, .. } => id,
+        }
+    }
+
+    pub(crate) fn with_id(mut self, new_id: String) -> ToastEvent {
+        match &mut self {
+            ToastEvent::Activated { id, .. } | ToastEvent::Dismissed { id, .. } | ToastEvent::Failed { id, .. } => *id = new_id,
+        }
+        self
+    }
+}
+
+/// Something that can show a toast to the user.
+pub trait Notifier: Send + Sync {
+    /// Shows the toast and returns the id it can be updated or removed by.
+    fn show(&self, toast: &Toast) -> Result<String, NotifyError>;
+
+    /// Replaces a toast shown before with new content.
+    fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError>;
+
+    /// Removes a toast from the screen and the action center.
+    fn remove(&self, id: &str) -> Result<(), NotifyError>;
+
+    /// Receives what happens to the toasts from now on, until the receiver is dropped.
+    fn subscribe(&self) -> mpsc::Receiver<ToastEvent>;
+}
+
+/// Gives a toast without an app id the one of Windows PowerShell, and refuses a toast Windows
+/// would reject.
+pub(crate) fn prepare(mut toast: Toast) -> Result<Toast, String> {
+    if toast.app_id.is_empty() {
+        toast.app_id = Toast::POWERSHELL_APP_ID.to_string();
+    }
+    let errors: Vec<String> = toast.validate().into_iter()
+        .filter(|diagnostic| diagnostic.is_error())
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors.join("; "));
+    }
+    Ok(toast)
+}
+
+/// A new id, unique within the process and unlikely to repeat across processes.
+pub(crate) fn next_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    format!("{:x}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// The senders of every live subscription.
+#[derive(Clone, Default)]
+pub(crate) struct Subscribers(Arc<Mutex<Vec<mpsc::Sender<ToastEvent>>>>);
+
+impl Subscribers {
+    pub(crate) fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(sender);
+        receiver
+    }
+
+    pub(crate) fn send(&self, event: ToastEvent) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
+
+/// How many ids a notifier that hands out ids of its own keeps track of, the oldest forgotten
+/// first.
+pub(crate) const MAX_TRACKED: usize = 1000;
+
+struct Entry<D> {
+    delivery: D,
+    /// The ids the inner notifier showed the toast under, the last one current.
+    inner_ids: Vec<String>,
+}
+
+/// What became of the ids a notifier handed out, and which of them the ids of its inner notifier
+/// belong to. Past [`MAX_TRACKED`] ids the oldest are forgotten, unless they are still pending.
+pub(crate) struct Tracked<D> {
+    entries: HashMap<String, Entry<D>>,
+    /// The ids of `entries`, oldest first.
+    order: VecDeque<String>,
+    by_inner: HashMap<String, String>,
+}
+
+impl<D> Default for Tracked<D> {
+    fn default() -> Tracked<D> {
+        Tracked { entries: HashMap::new(), order: VecDeque::new(), by_inner: HashMap::new() }
+    }
+}
+
+impl<D> Tracked<D> {
+    pub(crate) fn get(&self, id: &str) -> Option<&D> {
+        self.entries.get(id).map(|entry| &entry.delivery)
+    }
+
+    /// The id the toast shown under the id of the inner notifier is known by.
+    pub(crate) fn by_inner(&self, inner_id: &str) -> Option<&String> {
+        self.by_inner.get(inner_id)
+    }
+
+    /// Records what became of the id. An id seen before keeps its place, a new one makes the
+    /// oldest ids past the limit be forgotten, skipping those `pending` holds on to.
+    pub(crate) fn insert(&mut self, id: String, delivery: D, pending: impl Fn(&str, &D) -> bool) {
+        if let Some(entry) = self.entries.get_mut(&id) {
+            entry.delivery = delivery;
+            return;
+        }
+        self.entries.insert(id.clone(), Entry { delivery, inner_ids: Vec::new() });
+        self.order.push_back(id);
+        for _ in 0..self.order.len().saturating_sub(MAX_TRACKED) {
+            let Some(oldest) = self.order.pop_front() else { break };
+            match self.entries.get(&oldest) {
+                Some(entry) if pending(&oldest, &entry.delivery) => self.order.push_back(oldest),
+                _ => {
+                    self.forget(&oldest);
+                }
+            }
+        }
+    }
+
+    /// Notes that the inner notifier shows the toast with the id under `inner_id`.
+    pub(crate) fn link(&mut self, id: &str, inner_id: String) {
+        if let Some(entry) = self.entries.get_mut(id) {
+            self.by_inner.insert(inner_id.clone(), id.to_string());
+            entry.inner_ids.push(inner_id);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, id: &str) -> Option<D> {
+        self.order.retain(|tracked| tracked != id);
+        self.forget(id)
+    }
+
+    fn forget(&mut self, id: &str) -> Option<D> {
+        let entry = self.entries.remove(id)?;
+        for inner_id in &entry.inner_ids {
+            self.by_inner.remove(inner_id);
+        }
+        Some(entry.delivery)
+    }
+}
+
+#[cfg(windows)]
+pub use self::winrt::WinRtNotifier;
+
+#[cfg(windows)]
+mod winrt {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex, Weak};
+
+    use windows::core::{Interface, HSTRING, IInspectable};
+    use windows::Foundation::{IPropertyValue, TypedEventHandler};
+    use windows::Foundation::Collections::ValueSet;
+    use windows::UI::Notifications::*;
+
+    use super::{next_id, DismissReason, Notifier, NotifyError, Subscribers, ToastEvent, Tracked};
+    use crate::capability::OsBuild;
+    use crate::logging::{self, Stage};
+    use crate::notification::Toast;
+
+    /// The group of toasts that do not name one, so they can be removed by tag.
+    const DEFAULT_GROUP: &str = "win_toast";
+
+    struct Shown {
+        toast: Toast,
+        // Windows only raises the events of a notification while it is alive.
+        notification: ToastNotification,
+    }
+
+    /// Forgets a toast Windows is done with, unless an update has taken its place since.
+    fn finished(shown: &Weak<Mutex<Tracked<Shown>>>, id: &str, notification: &Option<ToastNotification>) {
+        let (Some(shown), Some(notification)) = (shown.upgrade(), notification) else { return };
+        let mut shown = shown.lock().unwrap_or_else(|e| e.into_inner());
+        if shown.get(id).is_some_and(|current| &current.notification == notification) {
+            shown.remove(id);
+        }
+    }
+
+    /// Shows toasts through the Windows `ToastNotificationManager`.
+    ///
+    /// A toast is forgotten once it is clicked or closed, after which it can no longer be updated
+    /// or removed. Of the toasts that timed out into the action center, the newest 1000 are kept.
+    #[derive(Default)]
+    pub struct WinRtNotifier {
+        /// The toasts that can still be updated or removed.
+        shown: Arc<Mutex<Tracked<Shown>>>,
+        subscribers: Subscribers,
+    }
+
+    impl WinRtNotifier {
+        fn deliver(&self, id: &str, toast: Toast) -> Result<(), NotifyError> {
+            let notification = match OsBuild::current() {
+                Some(build) => {
+                    let (downgraded, changes) = toast.downgrade_for(build);
+                    logging::downgraded(&toast.app_id, build, &changes);
+                    downgraded.create_notification()?
+                }
+                None => toast.create_notification()?,
+            };
+            let (subscribers, event_id, shown) = (self.subscribers.clone(), id.to_string(), Arc::downgrade(&self.shown));
+            notification.Activated(&TypedEventHandler::new(move |sender: &Option<ToastNotification>, args: &Option<IInspectable>| {
+                let args = args.as_ref().and_then(|args| args.cast::<ToastActivatedEventArgs>().ok());
+                let (arguments, user_input) = match args {
+                    Some(args) => (args.Arguments()?.to_string(), read_user_input(&args.UserInput()?)?),
+                    None => (String::new(), BTreeMap::new()),
+                };
+                subscribers.send(ToastEvent::Activated { id: event_id.clone(), arguments, user_input });
+                finished(&shown, &event_id, sender);
+                Ok(())
+            }))?;
+            let (subscribers, event_id, shown) = (self.subscribers.clone(), id.to_string(), Arc::downgrade(&self.shown));
+            notification.Dismissed(&TypedEventHandler::new(move |sender: &Option<ToastNotification>, args: &Option<ToastDismissedEventArgs>| {
+                let reason = match args.as_ref().map(|args| args.Reason()).transpose()? {
+                    Some(ToastDismissalReason::ApplicationHidden) => DismissReason::ApplicationHidden,
+                    Some(ToastDismissalReason::TimedOut) => DismissReason::TimedOut,
+                    _ => DismissReason::UserCanceled,
+                };
+                subscribers.send(ToastEvent::Dismissed { id: event_id.clone(), reason });
+                // A toast that timed out can still be clicked in the action center.
+                if reason != DismissReason::TimedOut {
+                    finished(&shown, &event_id, sender);
+                }
+                Ok(())
+            }))?;
+            let (subscribers, event_id) = (self.subscribers.clone(), id.to_string());
+            notification.Failed(&TypedEventHandler::new(move |_, args: &Option<ToastFailedEventArgs>| {
+                let message = match args {
+                    Some(args) => windows::core::Error::from(args.ErrorCode()?).message().to_string(),
+                    None => String::new(),
+                };
+                subscribers.send(ToastEvent::Failed { id: event_id.clone(), message });
+                Ok(())
+            }))?;
+            let _timed = logging::timed(Stage::Send, &toast.app_id);
+            ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(&toast.app_id))?.Show(&notification)?;
+            self.shown.lock().unwrap_or_else(|e| e.into_inner())
+                .insert(id.to_string(), Shown { toast, notification }, |_, _| false);
+            Ok(())
+        }
+    }
+
+    impl Notifier for WinRtNotifier {
+        fn show(&self, toast: &Toast) -> Result<String, NotifyError> {
+            let id = next_id();
+            let mut toast = toast.clone();
+            toast.tag.get_or_insert_with(|| id.clone());
+            toast.group.get_or_insert_with(|| DEFAULT_GROUP.to_string());
+            self.deliver(&id, toast)?;
+            Ok(id)
+        }
+
+        fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError> {
+            let (tag, group) = match self.shown.lock().unwrap_or_else(|e| e.into_inner()).get(id) {
+                Some(shown) => (shown.toast.tag.clone(), shown.toast.group.clone()),
+                None => return Err(NotifyError::NotFound(id.to_string())),
+            };
+            // A toast with the same tag and group takes the place of the old one.
+            self.deliver(id, Toast { tag, group, ..toast.clone() })
+        }
+
+        fn remove(&self, id: &str) -> Result<(), NotifyError> {
+            let shown = self.shown.lock().unwrap_or_else(|e| e.into_inner()).remove(id)
+                .ok_or_else(|| NotifyError::NotFound(id.to_string()))?;
+            let toast = shown.toast;
+            ToastNotificationManager::History()?.RemoveGroupedTagWithId(
+                &HSTRING::from(toast.tag.unwrap_or_default()),
+                &HSTRING::from(toast.group.unwrap_or_default()),
+                &HSTRING::from(toast.app_id),
+            )?;
+            Ok(())
+        }
+
+        fn subscribe(&self) -> std::sync::mpsc::Receiver<ToastEvent> {
+            self.subscribers.subscribe()
+        }
+    }
+
+    fn read_user_input(values: &ValueSet) -> windows::core::Result<BTreeMap<String, String>> {
+        let mut user_input = BTreeMap::new();
+        let pairs = values.First()?;
+        while pairs.HasCurrent()? {
+            let pair = pairs.Current()?;
+            let value = pair.Value()?.cast::<IPropertyValue>()?.GetString()?;
+            user_input.insert(pair.Key()?.to_string(), value.to_string());
+            pairs.MoveNext()?;
+        }
+        Ok(user_input)
+    }
+}
+
+/// The notifier of the platform, which only exists on Windows.
+pub fn platform_notifier() -> Result<Box<dyn Notifier>, NotifyError> {
+    #[cfg(windows)]
+    {
+        Ok(Box::new(WinRtNotifier::default()))
+    }
+    #[cfg(not(windows))]
+    {
+        Err(NotifyError::Unsupported)
+    }
+}
+
+/// Records toasts instead of showing them, for tests and previews.
+///
+/// Like Windows, a toast with the tag and group of a toast on screen takes its place. Events are
+/// raised with [`activate`](MockNotifier::activate) and [`dismiss`](MockNotifier::dismiss).
+#[derive(Default)]
+pub struct MockNotifier {
+    shown: Mutex<Vec<Toast>>,
+    current: Mutex<Vec<(String, Toast)>>,
+    subscribers: Subscribers,
+}
+
+impl MockNotifier {
+    pub fn new() -> MockNotifier {
+        MockNotifier::default()
+    }
+
+    /// The toasts shown so far, including updates, oldest first.
+    pub fn shown(&self) -> Vec<Toast> {
+        self.shown.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// The toasts on screen with their ids, oldest first.
+    pub fn current(&self) -> Vec<(String, Toast)> {
+        self.current.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Acts as if the user clicked the toast or one of its buttons.
+    pub fn activate(&self, id: &str, arguments: &str, user_input: BTreeMap<String, String>) {
+        self.subscribers.send(ToastEvent::Activated { id: id.to_string(), arguments: arguments.to_string(), user_input });
+    }
+
+    /// Acts as if the toast went away, taking it off the screen.
+    pub fn dismiss(&self, id: &str, reason: DismissReason) {
+        self.current.lock().unwrap_or_else(|e| e.into_inner()).retain(|(current_id, _)| current_id != id);
+        self.subscribers.send(ToastEvent::Dismissed { id: id.to_string(), reason });
+    }
+}
+
+impl Notifier for MockNotifier {
+    fn show(&self, toast: &Toast) -> Result<String, NotifyError> {
+        let id = next_id();
+        self.shown.lock().unwrap_or_else(|e| e.into_inner()).push(toast.clone());
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        if toast.tag.is_some() {
+            current.retain(|(_, other)| (&other.tag, &other.group) != (&toast.tag, &toast.group));
+        }
+        current.push((id.clone(), toast.clone()));
+        Ok(id)
+    }
+
+    fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = current.iter_mut().find(|(current_id, _)| current_id == id)
+            .ok_or_else(|| NotifyError::NotFound(id.to_string()))?;
+        entry.1 = toast.clone();
+        self.shown.lock().unwrap_or_else(|e| e.into_inner()).push(toast.clone());
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<(), NotifyError> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let before = current.len();
+        current.retain(|(current_id, _)| current_id != id);
+        if current.len() == before {
+            return Err(NotifyError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+        self.subscribers.subscribe()
+    }
+}
+
+/// Clicked by the user before `show` returns, as Windows may report it. `show` returns only once
+/// every subscriber has received the click.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct ClickedOnShow {
+    notifier: MockNotifier,
+    subscribers: Mutex<Vec<mpsc::SyncSender<ToastEvent>>>,
+}
+
+#[cfg(test)]
+impl Notifier for ClickedOnShow {
+    fn show(&self, toast: &Toast) -> Result<String, NotifyError> {
+        let id = self.notifier.show(toast)?;
+        let event = ToastEvent::Activated { id: id.clone(), arguments: "open".to_string(), user_input: BTreeMap::new() };
+        // Each send waits for the subscriber to take the event, as the channels have no buffer.
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).retain(|sender| sender.send(event.clone()).is_ok());
+        Ok(id)
+    }
+
+    fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError> {
+        self.notifier.update(id, toast)
+    }
+
+    fn remove(&self, id: &str) -> Result<(), NotifyError> {
+        self.notifier.remove(id)
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+        let (sender, receiver) = mpsc::sync_channel(0);
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).push(sender);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracked() {
+        let mut tracked = Tracked::default();
+        tracked.insert("pending".to_string(), true, |_, pending| *pending);
+        tracked.link("pending", "inner".to_string());
+        for i in 0..MAX_TRACKED + 10 {
+            tracked.insert(i.to_string(), false, |_, pending| *pending);
+            tracked.link(&i.to_string(), format!("inner {}", i));
+        }
+        assert_eq!((tracked.entries.len(), tracked.by_inner.len()), (MAX_TRACKED, MAX_TRACKED));
+        assert_eq!(tracked.get("pending"), Some(&true));
+        assert!(tracked.get("10").is_none() && tracked.by_inner("inner 10").is_none());
+        assert_eq!(tracked.by_inner("inner 11").map(String::as_str), Some("11"));
+        assert_eq!(tracked.remove("pending"), Some(true));
+        assert!(tracked.by_inner("inner").is_none() && tracked.remove("pending").is_none());
+    }
+}
@@ -0,0 +1,238 @@
+//! The size of the XML payload of a toast, and toasts trimmed to fit the limit Windows sets on it.
+//!
+//! Windows rejects a toast whose payload is larger than [`MAX_PAYLOAD_BYTES`]. Toasts that show
+//! content of users can grow past it, so [`Toast::trim_to`] makes them fit step by step:
+//!
+//! 1. the longest lines of body text are shortened and end in an ellipsis;
+//! 2. groups are dropped, the last first, as what comes further down matters least;
+//! 3. images are dropped, inline images the last first, then the hero image and the app logo.
+//!
+//! The title, the actions and the audio are never changed.
+
+use std::fmt;
+
+use crate::notification::*;
+use crate::sanitize;
+use crate::validate::Diagnostic;
+use crate::xml::Element;
+
+/// The largest payload Windows shows, in bytes.
+pub const MAX_PAYLOAD_BYTES: usize = 5 * 1024;
+
+/// Lines of body text are not shortened to fewer characters than this.
+const MIN_TEXT_CHARS: usize = 20;
+
+const ELLIPSIS: char = '…';
+
+/// The bytes of a payload, in total and by section. What is left of the total is the `<toast>`
+/// element itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PayloadSize {
+    pub total: usize,
+    pub visual: usize,
+    pub actions: usize,
+    pub audio: usize,
+    pub header: usize,
+}
+
+impl PayloadSize {
+    pub fn fits(&self, limit: usize) -> bool {
+        self.total <= limit
+    }
+}
+
+impl fmt::Display for PayloadSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes (visual {}, actions {}, audio {}, header {})", self.total, self.visual, self.actions, self.audio, self.header)
+    }
+}
+
+impl Toast {
+    /// The size of the payload [`Toast::to_xml`] writes.
+    pub fn payload_size(&self) -> PayloadSize {
+        let toast_elem = self.to_element();
+        let mut size = PayloadSize { total: written_len(&toast_elem), ..PayloadSize::default() };
+        for child in &toast_elem.children {
+            let section = match child.name {
+                "visual" => &mut size.visual,
+                "actions" => &mut size.actions,
+                "audio" => &mut size.audio,
+                "header" => &mut size.header,
+                _ => continue,
+            };
+            *section += written_len(child);
+        }
+        size
+    }
+
+    /// The toast trimmed until its payload is at most `limit` bytes, with a warning for each
+    /// step, or an error when it still does not fit after all of them.
+    pub fn trim_to(&self, limit: usize) -> (Toast, Vec<Diagnostic>) {
+        let mut toast = self.clone();
+        let mut steps = Vec::new();
+
+        // Shortening a line again keeps the length it had at first in its report.
+        let mut shortened: Vec<(String, usize, usize)> = Vec::new();
+        while let Some(over) = toast.payload_size().total.checked_sub(limit).filter(|over| *over > 0) {
+            let mut texts = body_texts_mut(&mut toast);
+            let Some((path, text)) = texts.iter_mut().filter(|(_, text)| text.chars().count() > MIN_TEXT_CHARS + 1)
+                .max_by_key(|(_, text)| text.chars().count()) else { break };
+            let chars = text.chars().count();
+            // The text keeps the characters whose escaped bytes fit, room for the ellipsis aside.
+            let mut budget = text.chars().map(escaped_len).sum::<usize>().saturating_sub(over + ELLIPSIS.len_utf8());
+            let keep = text.chars().take_while(|c| budget.checked_sub(escaped_len(*c)).map(|left| budget = left).is_some()).count();
+            let kept: String = text.chars().take(keep.clamp(MIN_TEXT_CHARS, chars - 2)).collect();
+            **text = format!("{}{}", kept.trim_end(), ELLIPSIS);
+            let now = text.chars().count();
+            match shortened.iter_mut().find(|(shortened_path, _, _)| shortened_path == path) {
+                Some((_, _, length)) => *length = now,
+                None => shortened.push((path.clone(), chars, now)),
+            }
+        }
+        steps.extend(shortened.into_iter()
+            .map(|(path, from, to)| Diagnostic::warning(path, format!("the text is shortened from {} to {} characters", from, to))));
+
+        // Dropped elements are emptied first and removed at the end, which keeps the paths of
+        // the ones after them; an empty group is left out of the payload like a missing one.
+        let mut dropped: Vec<(usize, usize)> = Vec::new();
+        let mut groups = Vec::new();
+        let mut images = Vec::new();
+        for (i, binding) in toast.binding_elems.iter().enumerate() {
+            for (j, elem) in binding.elems.iter().enumerate() {
+                match elem {
+                    BindingInnerElement::Text(_) => {}
+                    BindingInnerElement::Image(_) => images.push((i, j, None)),
+                    BindingInnerElement::Group(subgroups) => {
+                        groups.push((i, j));
+                        for (k, subgroup) in subgroups.iter().enumerate() {
+                            for (l, elem) in subgroup.iter().enumerate() {
+                                if let SubgroupElement::Image_(_) = elem {
+                                    images.push((i, j, Some((k, l))));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for (i, j) in groups.into_iter().rev() {
+            if toast.payload_size().fits(limit) {
+                break;
+            }
+            toast.binding_elems[i].elems[j] = BindingInnerElement::Group(Vec::new());
+            dropped.push((i, j));
+            steps.push(Diagnostic::warning(format!("binding_elems[{}].elems[{}]", i, j), "the group is dropped"));
+        }
+        for (i, j, in_group) in images.into_iter().rev() {
+            if toast.payload_size().fits(limit) {
+                break;
+            }
+            let elem = &mut toast.binding_elems[i].elems[j];
+            match (elem, in_group) {
+                (BindingInnerElement::Group(subgroups), Some((k, l))) if !subgroups.is_empty() => {
+                    subgroups[k].remove(l);
+                    steps.push(Diagnostic::warning(format!("binding_elems[{}].elems[{}][{}][{}]", i, j, k, l), "the image is dropped"));
+                }
+                (elem @ BindingInnerElement::Image(_), None) => {
+                    *elem = BindingInnerElement::Group(Vec::new());
+                    dropped.push((i, j));
+                    steps.push(Diagnostic::warning(format!("binding_elems[{}].elems[{}]", i, j), "the image is dropped"));
+                }
+                _ => {}
+            }
+        }
+        for i in 0..toast.binding_elems.len() {
+            for field in ["hero", "icon"] {
+                if toast.payload_size().fits(limit) {
+                    break;
+                }
+                let binding = &mut toast.binding_elems[i];
+                let image = if field == "hero" { &mut binding.hero } else { &mut binding.icon };
+                if image.take().is_some() {
+                    steps.push(Diagnostic::warning(format!("binding_elems[{}].{}", i, field), "the image is dropped"));
+                }
+            }
+        }
+        dropped.sort_unstable();
+        for (i, j) in dropped.into_iter().rev() {
+            toast.binding_elems[i].elems.remove(j);
+        }
+
+        let size = toast.payload_size();
+        if !size.fits(limit) {
+            steps.push(Diagnostic::error("/", format!("the payload is {} bytes after trimming, over the limit of {}", size.total, limit)));
+        }
+        (toast, steps)
+    }
+}
+
+/// The bytes a character of text takes in the payload.
+fn escaped_len(c: char) -> usize {
+    match c {
+        '&' => "&amp;".len(),
+        '<' | '>' => "&lt;".len(),
+        c => c.len_utf8(),
+    }
+}
+
+fn written_len(elem: &Element) -> usize {
+    let mut out = String::new();
+    elem.write(&mut out, sanitize::policy());
+    out.len()
+}
+
+/// The lines of body text of the toast, which are all texts of its bindings but the titles,
+/// with their paths.
+fn body_texts_mut(toast: &mut Toast) -> Vec<(String, &mut String)> {
+    let mut texts = Vec::new();
+    for (i, binding) in toast.binding_elems.iter_mut().enumerate() {
+        for (j, elem) in binding.elems.iter_mut().enumerate() {
+            let path = format!("binding_elems[{}].elems[{}]", i, j);
+            match elem {
+                BindingInnerElement::Text(text) => texts.push((path, text)),
+                BindingInnerElement::Image(_) => {}
+                BindingInnerElement::Group(subgroups) => {
+                    for (k, subgroup) in subgroups.iter_mut().enumerate() {
+                        for (l, elem) in subgroup.iter_mut().enumerate() {
+                            if let SubgroupElement::Text_(text) = elem {
+                                texts.push((format!("{}[{}][{}]", path, k, l), &mut text.text));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    texts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_trim_to() {
+        let mut toast = sample_toast();
+        toast.binding_elems[0].elems.push(BindingInnerElement::Text("many & more ".repeat(500)));
+        toast.binding_elems[0].elems.push(BindingInnerElement::Image(Image { src: "file:///C:/chart.png".to_string(), ..Image::default() }));
+        let size = toast.payload_size();
+        assert_eq!(size.total, toast.to_xml().len());
+        assert!(size.visual > 6000 && size.visual + size.actions + size.header + size.audio < size.total);
+        assert!(toast.validate().iter().any(|diagnostic| diagnostic.is_error() && diagnostic.path == "/"));
+
+        let (trimmed, steps) = toast.trim_to(MAX_PAYLOAD_BYTES);
+        assert!(trimmed.payload_size().fits(MAX_PAYLOAD_BYTES) && trimmed.payload_size().total > MAX_PAYLOAD_BYTES - 10);
+        let BindingInnerElement::Text(text) = &trimmed.binding_elems[0].elems[1] else { panic!("expected a text") };
+        assert!(text.ends_with('…') && text.len() > 3000);
+        assert_eq!(steps.len(), 1);
+        assert!(steps[0].message.starts_with("the text is shortened from 6000 to "));
+
+        let (trimmed, steps) = toast.trim_to(400);
+        let paths: Vec<&str> = steps.iter().map(|step| step.path.as_str()).collect();
+        assert_eq!(paths, ["binding_elems[0].elems[1]", "binding_elems[0].elems[0]", "binding_elems[0].elems[2]", "binding_elems[0].icon", "/"]);
+        assert_eq!(steps[1].message, "the group is dropped");
+        assert_eq!(trimmed.binding_elems[0].elems, vec![BindingInnerElement::Text("many & more many & m…".to_string())]);
+        assert_eq!(trimmed.binding_elems[0].title, toast.binding_elems[0].title);
+    }
+}
@@ -0,0 +1,39 @@
+//! Approximate renderings of a [`Toast`](crate::notification::Toast) for machines
+//! without the Windows notification shell.
+
+use crate::notification::*;
+
+pub mod html;
+pub mod terminal;
+
+pub use html::Theme;
+
+/// Splits the actions element into the actions and inputs Windows will actually display,
+/// which are at most five of each.
+pub(crate) fn visible_actions(elems: &[ActionsElem]) -> (Vec<&Action>, Vec<&Input>) {
+    let mut actions = Vec::new();
+    let mut inputs = Vec::new();
+    for elem in elems {
+        match elem {
+            ActionsElem::Action_(action) if actions.len() < 5 => actions.push(action),
+            ActionsElem::Input_(input) if inputs.len() < 5 => inputs.push(input),
+            _ => {}
+        }
+    }
+    (actions, inputs)
+}
+
+/// The display name of the app, which is the last segment of its id.
+pub(crate) fn app_name(app_id: &str) -> &str {
+    app_id.rsplit(['\\', '/', '!']).next().unwrap_or(app_id)
+}
+
+pub(crate) fn scenario_badge(scenario: &Scenario) -> Option<&'static str> {
+    match scenario {
+        Scenario::Default => None,
+        Scenario::Alarm => Some("Alarm"),
+        Scenario::Reminder => Some("Reminder"),
+        Scenario::IncomingCall => Some("Incoming call"),
+        Scenario::Urgent => Some("Urgent"),
+    }
+}
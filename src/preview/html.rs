@@ -0,0 +1,351 @@
+use std::fmt::Write;
+
+use crate::notification::*;
+use crate::preview::{app_name, scenario_badge, visible_actions};
+
+/// The colour scheme used by [`Toast::render_html`].
+#[allow(dead_code)]
+pub enum Theme {
+    /// The Windows light theme.
+    Light,
+    /// The Windows dark theme.
+    Dark,
+    /// Follows the `prefers-color-scheme` setting of the browser viewing the page.
+    System,
+}
+
+const STYLE: &str = r#"
+:root, .theme-light {
+    --page-bg: #e6e6e6; --bg: #f9f9f9; --fg: #1b1b1b; --border: #d6d6d6;
+    --control-bg: #fdfdfd; --control-border: #c8c8c8; --success: #0f7b0f; --critical: #c42b1c;
+}
+.theme-dark {
+    --page-bg: #202020; --bg: #2c2c2c; --fg: #ffffff; --border: #3d3d3d;
+    --control-bg: #373737; --control-border: #4a4a4a; --success: #6ccb5f; --critical: #ff99a4;
+}
+@media (prefers-color-scheme: dark) {
+    .theme-system {
+        --page-bg: #202020; --bg: #2c2c2c; --fg: #ffffff; --border: #3d3d3d;
+        --control-bg: #373737; --control-border: #4a4a4a; --success: #6ccb5f; --critical: #ff99a4;
+    }
+}
+body { background: var(--page-bg); color: var(--fg); margin: 24px; font: 14px/20px "Segoe UI Variable Text", "Segoe UI", sans-serif; }
+.toast { width: 364px; background: var(--bg); border: 1px solid var(--border); border-radius: 8px; box-shadow: 0 8px 16px rgba(0, 0, 0, .14); overflow: hidden; margin-bottom: 16px; }
+.toast-header { display: flex; gap: 8px; align-items: center; padding: 12px 16px 0; font-size: 12px; opacity: .8; }
+.toast-header .app-name { flex: 1; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }
+.badge { border: 1px solid var(--control-border); border-radius: 4px; padding: 0 4px; }
+.hero { display: block; width: 100%; aspect-ratio: 2 / 1; object-fit: cover; margin-top: 12px; }
+.binding { display: flex; gap: 12px; padding: 12px 16px 16px; }
+.app-logo { width: 48px; height: 48px; object-fit: cover; flex: none; }
+.content { flex: 1; min-width: 0; }
+.title { font-weight: 600; }
+.clamp { display: -webkit-box; -webkit-box-orient: vertical; overflow: hidden; }
+.inline-image { display: block; max-width: 100%; margin-top: 8px; }
+.attribution { font-size: 12px; opacity: .6; margin-top: 4px; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+.group { display: flex; gap: 8px; margin-top: 8px; }
+.subgroup { flex: 1; min-width: 0; }
+.subgroup .nowrap { white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+.subgroup img { display: block; max-width: 100%; }
+.circle { border-radius: 50%; }
+.align-stretch { width: 100%; }
+.align-left { margin-right: auto; }
+.align-center { margin: 0 auto; }
+.align-right { margin-left: auto; }
+.style-caption { font-size: 12px; line-height: 16px; }
+.style-body { font-size: 14px; }
+.style-base { font-size: 14px; font-weight: 600; }
+.style-subtitle { font-size: 20px; line-height: 28px; }
+.style-title { font-size: 28px; line-height: 36px; font-weight: 600; }
+.style-subheader { font-size: 34px; line-height: 40px; font-weight: 300; }
+.style-header { font-size: 46px; line-height: 56px; font-weight: 300; }
+.subtle { opacity: .6; }
+.numeral { line-height: 1; padding: 0; }
+.actions { padding: 0 16px 16px; }
+.input { margin-bottom: 8px; }
+.input label { display: block; font-size: 12px; margin-bottom: 4px; }
+.input-row { display: flex; gap: 8px; }
+.input-row input, .input-row select { flex: 1; min-width: 0; }
+input, select, button { font: inherit; color: var(--fg); background: var(--control-bg); border: 1px solid var(--control-border); border-radius: 4px; padding: 4px 8px; }
+.buttons { display: flex; gap: 8px; }
+.buttons button { flex: 1; min-width: 0; }
+button img { width: 16px; height: 16px; vertical-align: middle; }
+button.success { background: var(--success); border-color: var(--success); color: #ffffff; }
+button.critical { background: var(--critical); border-color: var(--critical); color: #ffffff; }
+.context-menu { width: 240px; background: var(--bg); border: 1px solid var(--border); border-radius: 8px; padding: 4px; }
+.context-menu-caption { font-size: 12px; opacity: .6; padding: 4px 8px; }
+.context-menu div.item { padding: 6px 8px; border-radius: 4px; }
+"#;
+
+impl Toast {
+    /// Renders a standalone HTML page approximating how Windows 10/11 lays out this toast.
+    ///
+    /// The page embeds its own stylesheet and references images by their `src`; local paths are
+    /// turned into `file:///` URIs, `ms-appx:///` and `ms-appdata:///` images are not resolved.
+    pub fn render_html(&self, theme: Theme) -> String {
+        let theme_class = match theme {
+            Theme::Light => "theme-light",
+            Theme::Dark => "theme-dark",
+            Theme::System => "theme-system",
+        };
+        let mut page = String::new();
+        page.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Toast preview</title>\n<style>");
+        page.push_str(STYLE);
+        page.push_str("</style>\n</head>\n");
+        let _ = writeln!(page, "<body class=\"{}\">", theme_class);
+        self.write_html_toast(&mut page);
+        self.write_html_context_menu(&mut page);
+        page.push_str("</body>\n</html>\n");
+        page
+    }
+
+    fn write_html_toast(&self, page: &mut String) {
+        let _ = writeln!(page, "<div class=\"toast\">");
+        page.push_str("<div class=\"toast-header\">");
+        let _ = write!(page, "<span class=\"app-name\">{}</span>", escape(app_name(&self.app_id)));
+        if let Some(badge) = scenario_badge(&self.scenario) {
+            let _ = write!(page, "<span class=\"badge\">{}</span>", badge);
+        }
+        if let Some(timestamp) = &self.display_timestamp {
+            let _ = write!(page, "<span class=\"timestamp\">{}</span>", escape(timestamp));
+        }
+        page.push_str("</div>\n");
+        for binding in &self.binding_elems {
+            if let Some(hero) = &binding.hero {
+                let _ = writeln!(page, "<img class=\"hero\" src=\"{}\" alt=\"{}\">", escape(&image_uri(&hero.src)), escape(&hero.alt));
+            }
+            page.push_str("<div class=\"binding\">\n");
+            if let Some(icon) = &binding.icon {
+                let _ = writeln!(page, "<img class=\"app-logo{}\" src=\"{}\" alt=\"{}\">",
+                                 if matches!(icon.hint_crop, HintCrop::Circle) { " circle" } else { "" },
+                                 escape(&image_uri(&icon.src)), escape(&icon.alt));
+            }
+            page.push_str("<div class=\"content\">\n");
+            let _ = writeln!(page, "<div class=\"title clamp\" style=\"-webkit-line-clamp: {}\">{}</div>",
+                             binding.title.1.unwrap_or(2), escape(&binding.title.0));
+            for elem in &binding.elems {
+                match elem {
+                    BindingInnerElement::Text(text) => {
+                        let _ = writeln!(page, "<div class=\"text clamp\" style=\"-webkit-line-clamp: 4\">{}</div>", escape(text));
+                    }
+                    BindingInnerElement::Group(subgroups) => write_html_group(page, subgroups),
+                    BindingInnerElement::Image(image) => {
+                        let _ = writeln!(page, "<img class=\"inline-image\" src=\"{}\" alt=\"{}\">", escape(&image_uri(&image.src)), escape(&image.alt));
+                    }
+                }
+            }
+            if let Some(attribution) = &binding.attribution {
+                let _ = writeln!(page, "<div class=\"attribution\">{}</div>", escape(attribution));
+            }
+            page.push_str("</div>\n</div>\n");
+        }
+        self.write_html_actions(page);
+        page.push_str("</div>\n");
+    }
+
+    fn write_html_actions(&self, page: &mut String) {
+        let (actions, inputs) = visible_actions(&self.actions);
+        let buttons: Vec<&Action> = actions.iter()
+            .filter(|action| matches!(action.placement, ActionPlacement::Default))
+            .copied()
+            .collect();
+        if buttons.is_empty() && inputs.is_empty() {
+            return;
+        }
+        page.push_str("<div class=\"actions\">\n");
+        for input in &inputs {
+            page.push_str("<div class=\"input\">");
+            if let Some(title) = &input.title {
+                let _ = write!(page, "<label>{}</label>", escape(title));
+            }
+            page.push_str("<div class=\"input-row\">");
+            match &input.type_ {
+                InputType::Text(default) => {
+                    let _ = write!(page, "<input type=\"text\" placeholder=\"{}\" value=\"{}\">",
+                                   escape(input.place_holder_content.as_deref().unwrap_or("")),
+                                   escape(default.as_deref().unwrap_or("")));
+                }
+                InputType::Selection(default_id, selections) => {
+                    page.push_str("<select>");
+                    for (id, title) in selections.iter().take(5) {
+                        let selected = default_id.as_deref() == Some(id.as_str());
+                        let _ = write!(page, "<option{}>{}</option>", if selected { " selected" } else { "" }, escape(title));
+                    }
+                    page.push_str("</select>");
+                }
+            }
+            for button in buttons.iter().filter(|action| action.hint_input_id.as_deref() == Some(input.id.as_str())) {
+                write_html_button(page, button, self.use_btn_style);
+            }
+            page.push_str("</div></div>\n");
+        }
+        let row: Vec<&&Action> = buttons.iter()
+            .filter(|action| !inputs.iter().any(|input| action.hint_input_id.as_deref() == Some(input.id.as_str())))
+            .collect();
+        if !row.is_empty() {
+            page.push_str("<div class=\"buttons\">");
+            for button in row {
+                write_html_button(page, button, self.use_btn_style);
+            }
+            page.push_str("</div>\n");
+        }
+        page.push_str("</div>\n");
+    }
+
+    fn write_html_context_menu(&self, page: &mut String) {
+        let (actions, _) = visible_actions(&self.actions);
+        let items: Vec<&Action> = actions.into_iter()
+            .filter(|action| matches!(action.placement, ActionPlacement::ContextMenu))
+            .collect();
+        if items.is_empty() {
+            return;
+        }
+        page.push_str("<div class=\"context-menu\">\n<div class=\"context-menu-caption\">Context menu</div>\n");
+        for item in items {
+            let _ = writeln!(page, "<div class=\"item\">{}</div>", escape(&item.content));
+        }
+        page.push_str("</div>\n");
+    }
+}
+
+fn write_html_group(page: &mut String, subgroups: &[Vec<SubgroupElement>]) {
+    page.push_str("<div class=\"group\">\n");
+    for subgroup in subgroups.iter().filter(|subgroup| !subgroup.is_empty()) {
+        page.push_str("<div class=\"subgroup\">\n");
+        for elem in subgroup {
+            match elem {
+                SubgroupElement::Text_(text) => {
+                    let mut classes = vec![text_style_class(&text.hint_style)];
+                    if text.hint_warp != Some(true) {
+                        classes.push("nowrap");
+                    } else if text.hint_max_lines.is_some() {
+                        classes.push("clamp");
+                    }
+                    let mut style = String::new();
+                    if let Some(align) = text_align(&text.hint_align) {
+                        let _ = write!(style, "text-align: {}; ", align);
+                    }
+                    if let (Some(true), Some(max)) = (text.hint_warp, text.hint_max_lines) {
+                        let _ = write!(style, "-webkit-line-clamp: {}; ", max);
+                    }
+                    if let Some(min) = text.hint_min_lines {
+                        let _ = write!(style, "min-height: {}em; ", min as f32 * 1.43);
+                    }
+                    let _ = writeln!(page, "<div class=\"{}\" style=\"{}\">{}</div>", classes.join(" ").trim(), style.trim_end(), escape(&text.text));
+                }
+                SubgroupElement::Image_(image) => {
+                    let mut classes = Vec::new();
+                    if matches!(image.hint_crop, HintCrop::Circle) {
+                        classes.push("circle");
+                    }
+                    match image.hint_align {
+                        ImageAlign::Default => {}
+                        ImageAlign::Stretch => classes.push("align-stretch"),
+                        ImageAlign::Left => classes.push("align-left"),
+                        ImageAlign::Center => classes.push("align-center"),
+                        ImageAlign::Right => classes.push("align-right"),
+                    }
+                    let _ = writeln!(page, "<img class=\"{}\" src=\"{}\" alt=\"{}\">", classes.join(" "), escape(&image_uri(&image.src)), escape(&image.alt));
+                }
+            }
+        }
+        page.push_str("</div>\n");
+    }
+    page.push_str("</div>\n");
+}
+
+fn write_html_button(page: &mut String, action: &Action, use_btn_style: bool) {
+    let class = match action.hint_button_style.as_deref() {
+        Some("Success") if use_btn_style => " class=\"success\"",
+        Some("Critical") if use_btn_style => " class=\"critical\"",
+        _ => "",
+    };
+    let tool_tip = action.hint_tool_tip.as_deref()
+        .map(|tip| format!(" title=\"{}\"", escape(tip)))
+        .unwrap_or_default();
+    let _ = write!(page, "<button{}{}>", class, tool_tip);
+    if let Some(uri) = &action.image_uri {
+        let _ = write!(page, "<img src=\"{}\" alt=\"\"> ", escape(&image_uri(uri)));
+    }
+    let _ = write!(page, "{}</button>", escape(&action.content));
+}
+
+fn text_style_class(style: &TextStyle) -> &'static str {
+    match style {
+        TextStyle::Default | TextStyle::Body => "style-body",
+        TextStyle::Caption => "style-caption",
+        TextStyle::CaptionSubtle => "style-caption subtle",
+        TextStyle::BodySubtle => "style-body subtle",
+        TextStyle::Base => "style-base",
+        TextStyle::BaseSubtle => "style-base subtle",
+        TextStyle::Subtitle => "style-subtitle",
+        TextStyle::SubtitleSubtle => "style-subtitle subtle",
+        TextStyle::Title => "style-title",
+        TextStyle::TitleSubtle => "style-title subtle",
+        TextStyle::TitleNumeral => "style-title numeral",
+        TextStyle::Subheader => "style-subheader",
+        TextStyle::SubheaderSubtle => "style-subheader subtle",
+        TextStyle::SubheaderNumeral => "style-subheader numeral",
+        TextStyle::Header => "style-header",
+        TextStyle::HeaderSubtle => "style-header subtle",
+        TextStyle::HeaderNumeral => "style-header numeral",
+    }
+}
+
+fn text_align(align: &TextAlign) -> Option<&'static str> {
+    match align {
+        TextAlign::Default => None,
+        TextAlign::Auto => Some("start"),
+        TextAlign::Left => Some("left"),
+        TextAlign::Center => Some("center"),
+        TextAlign::Right => Some("right"),
+    }
+}
+
+/// Local paths are turned into `file:///` URIs so the browser can load them.
+fn image_uri(src: &str) -> String {
+    if src.contains("://") {
+        return src.to_string();
+    }
+    let path = src.replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else if path.as_bytes().get(1) == Some(&b':') {
+        format!("file:///{}", path)
+    } else {
+        path
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_render_html() {
+        let html = sample_toast().render_html(Theme::Dark);
+        assert!(html.contains("<body class=\"theme-dark\">"));
+        assert!(html.contains("Build &lt;finished&gt;"));
+        assert!(html.contains("class=\"app-logo circle\" src=\"file:///"));
+        assert!(html.contains("<div class=\"style-base subtle nowrap\" style=\"text-align: right;\">42 passed</div>"));
+        assert!(html.contains("<div class=\"attribution\">via ci.example.com</div>"));
+        assert!(html.contains("<button class=\"success\">Open</button>"));
+        assert!(html.contains("<span class=\"badge\">Reminder</span>"));
+        assert!(html.contains("<div class=\"item\">Settings</div>"));
+    }
+}
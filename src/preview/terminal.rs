@@ -0,0 +1,383 @@
+use crate::notification::*;
+use crate::preview::{app_name, scenario_badge, visible_actions};
+
+/// The number of columns inside the box, matching the 364px width of a toast at 14px text.
+const INNER_WIDTH: usize = 56;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const GREEN: &str = "\x1b[1;37;42m";
+const RED: &str = "\x1b[1;37;41m";
+const REVERSE: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+struct Span {
+    text: String,
+    style: &'static str,
+}
+
+impl Span {
+    fn plain(text: impl Into<String>) -> Span {
+        Span { text: text.into(), style: "" }
+    }
+
+    fn styled(text: impl Into<String>, style: &'static str) -> Span {
+        Span { text: text.into(), style }
+    }
+}
+
+type Line = Vec<Span>;
+
+impl Toast {
+    /// Renders a box-drawn preview of this toast for terminals and CI logs.
+    ///
+    /// Styles are written as ANSI escape sequences unless the `NO_COLOR` environment variable is set.
+    pub fn render_terminal(&self) -> String {
+        let color = std::env::var_os("NO_COLOR").is_none();
+        let mut lines: Vec<Line> = Vec::new();
+
+        let mut header: Line = vec![Span::styled(app_name(&self.app_id), DIM)];
+        let mut badges = Vec::new();
+        if let Some(badge) = scenario_badge(&self.scenario) {
+            badges.push(badge);
+        }
+        match self.duration {
+            Duration::Default => {}
+            Duration::Short => badges.push("short"),
+            Duration::Long => badges.push("long"),
+        }
+        if let Some(timestamp) = &self.display_timestamp {
+            header.push(Span::styled(format!("  {}", timestamp), DIM));
+        }
+        let badges: String = badges.iter().map(|badge| format!(" [{}]", badge)).collect();
+        let used = width(&header) + badges.chars().count();
+        if used < INNER_WIDTH {
+            header.push(Span::plain(" ".repeat(INNER_WIDTH - used)));
+        }
+        header.push(Span::styled(badges, BOLD));
+        lines.push(header);
+
+        for binding in &self.binding_elems {
+            if let Some(hero) = &binding.hero {
+                lines.push(vec![Span::styled(format!("▣ {}", image_label(hero)), DIM)]);
+            }
+            let prefix = match &binding.icon {
+                Some(icon) if matches!(icon.hint_crop, HintCrop::Circle) => format!("({}) ", initial(icon)),
+                Some(icon) => format!("[{}] ", initial(icon)),
+                None => String::new(),
+            };
+            let indent = prefix.chars().count();
+            for (i, row) in wrap(&binding.title.0, INNER_WIDTH - indent, binding.title.1.or(Some(2))).into_iter().enumerate() {
+                let lead = if i == 0 { prefix.clone() } else { " ".repeat(indent) };
+                lines.push(vec![Span::plain(lead), Span::styled(row, BOLD)]);
+            }
+            for elem in &binding.elems {
+                match elem {
+                    BindingInnerElement::Text(text) => {
+                        for row in wrap(text, INNER_WIDTH - indent, Some(4)) {
+                            lines.push(vec![Span::plain(" ".repeat(indent)), Span::plain(row)]);
+                        }
+                    }
+                    BindingInnerElement::Group(subgroups) => {
+                        lines.extend(group_lines(subgroups, indent));
+                    }
+                    BindingInnerElement::Image(image) => {
+                        lines.push(vec![Span::plain(" ".repeat(indent)), Span::styled(format!("▣ {}", image_label(image)), DIM)]);
+                    }
+                }
+            }
+            if let Some(attribution) = &binding.attribution {
+                for row in wrap(attribution, INNER_WIDTH - indent, Some(1)) {
+                    lines.push(vec![Span::plain(" ".repeat(indent)), Span::styled(row, DIM)]);
+                }
+            }
+        }
+
+        let (actions, inputs) = visible_actions(&self.actions);
+        let buttons: Vec<&Action> = actions.iter()
+            .filter(|action| matches!(action.placement, ActionPlacement::Default))
+            .copied()
+            .collect();
+        let mut action_lines: Vec<Line> = Vec::new();
+        for input in &inputs {
+            if let Some(title) = &input.title {
+                action_lines.push(vec![Span::styled(title.clone(), DIM)]);
+            }
+            let field = match &input.type_ {
+                InputType::Text(default) => match default {
+                    Some(value) => Span::styled(format!(" {} ", value), REVERSE),
+                    None => Span::styled(format!(" {} ", input.place_holder_content.as_deref().unwrap_or("")), DIM),
+                },
+                InputType::Selection(default_id, selections) => {
+                    let selected = selections.iter()
+                        .find(|(id, _)| Some(id) == default_id.as_ref())
+                        .or_else(|| selections.first())
+                        .map(|(_, title)| title.as_str())
+                        .unwrap_or("");
+                    Span::plain(format!("< {} ▾ >", selected))
+                }
+            };
+            let mut line = vec![field];
+            for button in buttons.iter().filter(|action| action.hint_input_id.as_deref() == Some(input.id.as_str())) {
+                line.push(Span::plain(" "));
+                line.push(button_span(button, self.use_btn_style));
+            }
+            action_lines.push(line);
+        }
+        let mut row: Line = Vec::new();
+        for button in buttons.iter()
+            .filter(|action| !inputs.iter().any(|input| action.hint_input_id.as_deref() == Some(input.id.as_str()))) {
+            let span = button_span(button, self.use_btn_style);
+            if !row.is_empty() && width(&row) + 1 + span.text.chars().count() > INNER_WIDTH {
+                action_lines.push(std::mem::take(&mut row));
+            }
+            if !row.is_empty() {
+                row.push(Span::plain(" "));
+            }
+            row.push(span);
+        }
+        if !row.is_empty() {
+            action_lines.push(row);
+        }
+
+        let mut out = String::new();
+        out.push_str(&format!("╭{}╮\n", "─".repeat(INNER_WIDTH + 2)));
+        for line in &lines {
+            push_line(&mut out, line, color);
+        }
+        if !action_lines.is_empty() {
+            out.push_str(&format!("├{}┤\n", "─".repeat(INNER_WIDTH + 2)));
+            for line in &action_lines {
+                push_line(&mut out, line, color);
+            }
+        }
+        out.push_str(&format!("╰{}╯\n", "─".repeat(INNER_WIDTH + 2)));
+        for action in actions.iter().filter(|action| matches!(action.placement, ActionPlacement::ContextMenu)) {
+            out.push_str(&format!("  ≡ {}\n", printable(&action.content)));
+        }
+        out
+    }
+}
+
+fn group_lines(subgroups: &[Vec<SubgroupElement>], indent: usize) -> Vec<Line> {
+    let subgroups: Vec<&Vec<SubgroupElement>> = subgroups.iter().filter(|subgroup| !subgroup.is_empty()).collect();
+    if subgroups.is_empty() {
+        return Vec::new();
+    }
+    let separators = (subgroups.len() - 1) * 3;
+    // Past 14 or so subgroups the columns would have no width left, so each keeps one.
+    let column_width = ((INNER_WIDTH - indent).saturating_sub(separators) / subgroups.len()).max(1);
+    let columns: Vec<Vec<(String, &'static str)>> = subgroups.iter()
+        .map(|subgroup| {
+            let mut rows = Vec::new();
+            for elem in subgroup.iter() {
+                match elem {
+                    SubgroupElement::Text_(text) => {
+                        let max_lines = if text.hint_warp == Some(true) { text.hint_max_lines } else { Some(1) };
+                        let mut wrapped = wrap(&text.text, column_width, max_lines);
+                        while wrapped.len() < text.hint_min_lines.unwrap_or(0) as usize {
+                            wrapped.push(String::new());
+                        }
+                        for row in wrapped {
+                            rows.push((align(&row, column_width, &text.hint_align), text_style(&text.hint_style)));
+                        }
+                    }
+                    SubgroupElement::Image_(image) => {
+                        let label = truncate(&format!("▣ {}", image_label(image)), column_width);
+                        rows.push((pad(&label, column_width), DIM));
+                    }
+                }
+            }
+            rows
+        })
+        .collect();
+    let height = columns.iter().map(Vec::len).max().unwrap_or(0);
+    (0..height)
+        .map(|i| {
+            let mut line: Line = vec![Span::plain(" ".repeat(indent))];
+            for (n, column) in columns.iter().enumerate() {
+                if n > 0 {
+                    line.push(Span::styled(" ┆ ", DIM));
+                }
+                match column.get(i) {
+                    Some((text, style)) => line.push(Span::styled(text.clone(), style)),
+                    None => line.push(Span::plain(" ".repeat(column_width))),
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+fn button_span(action: &Action, use_btn_style: bool) -> Span {
+    let label = if action.content.is_empty() {
+        action.hint_tool_tip.as_deref().unwrap_or("…")
+    } else {
+        action.content.as_str()
+    };
+    let style = match action.hint_button_style.as_deref() {
+        Some("Success") if use_btn_style => GREEN,
+        Some("Critical") if use_btn_style => RED,
+        _ => "",
+    };
+    Span::styled(format!("[ {} ]", label), style)
+}
+
+fn text_style(style: &TextStyle) -> &'static str {
+    match style {
+        TextStyle::Default | TextStyle::Body | TextStyle::Caption => "",
+        TextStyle::CaptionSubtle | TextStyle::BodySubtle => DIM,
+        TextStyle::BaseSubtle | TextStyle::SubtitleSubtle | TextStyle::TitleSubtle
+        | TextStyle::SubheaderSubtle | TextStyle::HeaderSubtle => "\x1b[1;2m",
+        _ => BOLD,
+    }
+}
+
+fn image_label(image: &Image) -> String {
+    let file = image.src.rsplit(['/', '\\']).next().unwrap_or(&image.src);
+    if image.alt.is_empty() {
+        file.to_string()
+    } else {
+        format!("{} ({})", image.alt, file)
+    }
+}
+
+fn initial(image: &Image) -> char {
+    image.alt.chars().next().map(|c| c.to_ascii_uppercase()).unwrap_or('•')
+}
+
+/// Word-wraps `text` to `width` columns, ending the last permitted line with an ellipsis when
+/// the text has to be cut to honor `max_lines`.
+fn wrap(text: &str, width: usize, max_lines: Option<u32>) -> Vec<String> {
+    if width == 0 {
+        return Vec::new();
+    }
+    let mut rows: Vec<String> = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut row = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.to_string();
+            while word.chars().count() > width {
+                if !row.is_empty() {
+                    rows.push(std::mem::take(&mut row));
+                }
+                let split: String = word.chars().take(width).collect();
+                word = word.chars().skip(width).collect();
+                rows.push(split);
+            }
+            if !row.is_empty() && row.chars().count() + 1 + word.chars().count() > width {
+                rows.push(std::mem::take(&mut row));
+            }
+            if !row.is_empty() {
+                row.push(' ');
+            }
+            row.push_str(&word);
+        }
+        rows.push(row);
+    }
+    if let Some(max) = max_lines {
+        let max = max.max(1) as usize;
+        if rows.len() > max {
+            rows.truncate(max);
+            let last = rows.last_mut().unwrap();
+            *last = truncate(&format!("{} …", last), width);
+        }
+    }
+    rows
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.chars().count())))
+}
+
+fn align(text: &str, width: usize, align: &TextAlign) -> String {
+    let space = width.saturating_sub(text.chars().count());
+    match align {
+        TextAlign::Center => format!("{}{}{}", " ".repeat(space / 2), text, " ".repeat(space - space / 2)),
+        TextAlign::Right => format!("{}{}", " ".repeat(space), text),
+        _ => pad(text, width),
+    }
+}
+
+fn width(line: &Line) -> usize {
+    line.iter().map(|span| span.text.chars().count()).sum()
+}
+
+fn push_line(out: &mut String, line: &Line, color: bool) {
+    out.push_str("│ ");
+    let mut used = 0;
+    for span in line {
+        let text = truncate(&printable(&span.text), INNER_WIDTH - used);
+        used += text.chars().count();
+        if color && !span.style.is_empty() {
+            out.push_str(span.style);
+            out.push_str(&text);
+            out.push_str(RESET);
+        } else {
+            out.push_str(&text);
+        }
+        if used >= INNER_WIDTH {
+            break;
+        }
+    }
+    out.push_str(&" ".repeat(INNER_WIDTH - used));
+    out.push_str(" │\n");
+}
+
+/// The text with control characters shown as their symbols, so text of users cannot write
+/// escape sequences to the terminal.
+fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\u{0}'..='\u{1f}' => char::from_u32(0x2400 + c as u32).unwrap_or(char::REPLACEMENT_CHARACTER),
+            '\u{7f}' => '␡',
+            c if c.is_control() => char::REPLACEMENT_CHARACTER,
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::notification::*;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_many_columns_and_control_characters() {
+        let subgroups = (0..16).map(|i| vec![SubgroupElement::Text_(InnerText { text: format!("word{}", i), ..InnerText::default() })]).collect();
+        let toast = Toast {
+            binding_elems: vec![BindingElem {
+                icon: Some(Image { src: "logo.png".to_string(), ..Image::default() }),
+                title: ("\u{1b}]0;owned\u{7}Build".to_string(), None),
+                elems: vec![BindingInnerElement::Group(subgroups)],
+                ..BindingElem::default()
+            }],
+            ..Toast::default()
+        };
+        let preview = toast.render_terminal();
+        assert!(preview.contains("␛]0;owned␇Build"));
+        assert!(!preview.contains("\u{1b}]") && !preview.contains('\u{7}'));
+    }
+
+    #[test]
+    fn test_render_terminal() {
+        let preview = sample_toast().render_terminal();
+        let lines: Vec<&str> = preview.lines().collect();
+        assert!(lines[0].starts_with("╭─"));
+        assert!(lines[1].contains("powershell.exe") && lines[1].contains("[Reminder]"));
+        assert!(lines[2].contains("(L) ") && lines[2].contains("Build <finished>"));
+        assert!(lines[3].contains("42 passed"));
+        assert!(lines[4].contains("via ci.example.com"));
+        assert!(lines[6].contains("[ Open ]"));
+        assert_eq!(lines[8], "  ≡ Settings");
+    }
+}
@@ -0,0 +1,556 @@
+//! A send queue in front of a notifier that keeps bursts of toasts from flooding the screen
+//! and from getting the app throttled by Windows.
+//!
+//! Toasts are shown right away while the rate limits allow. Over the limits they wait in the
+//! queue and are shown by [`SendQueue::tick`], which a pump thread started with
+//! [`SendQueue::spawn_pump`] calls periodically. A toast with the same content as one submitted
+//! within the deduplication window is not shown again, and when too many toasts of an app are
+//! waiting they are shown as one summary counting them instead.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{self, Clock, SystemClock};
+use crate::notification::*;
+use crate::notifier::{next_id, Notifier, NotifyError, Subscribers, ToastEvent, Tracked};
+
+/// How often a blocked submission ticks the queue.
+pub const BLOCK_TICK: Duration = Duration::from_secs(1);
+
+/// At most `count` toasts per `per`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rate {
+    pub count: usize,
+    pub per: Duration,
+}
+
+/// What happens to a toast submitted to a full queue.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// The new toast is dropped.
+    #[default]
+    DropNewest,
+    /// The toast that waited longest is dropped to make room.
+    DropOldest,
+    /// The submission fails with [`NotifyError::QueueFull`], so the caller can back off.
+    Reject,
+    /// The submission waits until a tick makes room. Without a pump it ticks the queue itself
+    /// every [`BLOCK_TICK`], so it never waits for a tick that does not come.
+    Block,
+}
+
+#[derive(Clone, Debug)]
+pub struct QueueConfig {
+    /// The limit for the toasts of each app. Windows starts throttling an app at around 20 toasts
+    /// a minute.
+    pub per_app: Option<Rate>,
+    /// The limit for the toasts of each tag.
+    pub per_tag: Option<Rate>,
+    /// A toast with the same content as one submitted this long before is not shown again.
+    pub dedup_window: Option<Duration>,
+    /// When more toasts of an app than this are waiting, they are shown as one summary.
+    pub coalesce_after: Option<usize>,
+    /// How many toasts may wait.
+    pub capacity: usize,
+    pub overflow: Overflow,
+}
+
+impl Default for QueueConfig {
+    fn default() -> QueueConfig {
+        QueueConfig {
+            per_app: Some(Rate { count: 10, per: Duration::from_secs(60) }),
+            per_tag: None,
+            dedup_window: Some(Duration::from_secs(10)),
+            coalesce_after: Some(3),
+            capacity: 100,
+            overflow: Overflow::DropNewest,
+        }
+    }
+}
+
+/// What became of a submitted toast. Each carries the id the queue knows the toast by.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Submitted {
+    Shown(String),
+    /// The toast waits for the rate limits to allow it.
+    Queued(String),
+    /// The toast has the content of the toast with this id and was not shown again.
+    Duplicate(String),
+    /// The queue was full and the toast was dropped.
+    Dropped(String),
+}
+
+impl Submitted {
+    pub fn id(&self) -> &str {
+        match self {
+            Submitted::Shown(id) | Submitted::Queued(id) | Submitted::Duplicate(id) | Submitted::Dropped(id) => id,
+        }
+    }
+}
+
+/// Where a toast the queue handed out an id for is.
+#[derive(Clone, Debug)]
+enum Delivery {
+    Waiting,
+    /// Shown by the inner notifier under this id.
+    Shown(String),
+    /// Shown as part of the summary with this queue id.
+    Coalesced(String),
+    Dropped,
+}
+
+struct Waiting {
+    id: String,
+    toast: Toast,
+}
+
+#[derive(Default)]
+struct State {
+    waiting: VecDeque<Waiting>,
+    /// Waiting toasts are never forgotten.
+    deliveries: Tracked<Delivery>,
+    sent_by_app: HashMap<String, VecDeque<SystemTime>>,
+    sent_by_tag: HashMap<String, VecDeque<SystemTime>>,
+    /// The content hash of recent toasts, with the id and time they were submitted.
+    recent: HashMap<u64, (String, SystemTime)>,
+}
+
+impl State {
+    fn shown(&mut self, id: String, inner_id: String) {
+        self.deliver(id.clone(), Delivery::Shown(inner_id.clone()));
+        self.deliveries.link(&id, inner_id);
+    }
+
+    fn deliver(&mut self, id: String, delivery: Delivery) {
+        self.deliveries.insert(id, delivery, |_, delivery| matches!(delivery, Delivery::Waiting));
+    }
+}
+
+pub struct SendQueue {
+    inner: Arc<dyn Notifier>,
+    config: QueueConfig,
+    clock: Arc<dyn Clock>,
+    state: Arc<Mutex<State>>,
+    room: Condvar,
+    subscribers: Subscribers,
+}
+
+impl SendQueue {
+    pub fn new(inner: Arc<dyn Notifier>, config: QueueConfig) -> SendQueue {
+        SendQueue::with_clock(inner, config, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(inner: Arc<dyn Notifier>, config: QueueConfig, clock: Arc<dyn Clock>) -> SendQueue {
+        let events = inner.subscribe();
+        let state: Arc<Mutex<State>> = Arc::default();
+        let subscribers = Subscribers::default();
+        let (known, forward) = (state.clone(), subscribers.clone());
+        // The events of the inner notifier are passed on with its ids translated to the ids of
+        // the queue.
+        thread::spawn(move || {
+            for event in events {
+                let id = known.lock().unwrap_or_else(|e| e.into_inner()).deliveries.by_inner(event.id()).cloned();
+                forward.send(match id {
+                    Some(id) => event.with_id(id),
+                    None => event,
+                });
+            }
+        });
+        SendQueue { inner, config, clock, state, room: Condvar::new(), subscribers }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Shows the toast now if the limits allow, and queues it otherwise.
+    pub fn submit(&self, toast: &Toast) -> Result<Submitted, NotifyError> {
+        let now = self.clock.now();
+        let id = next_id();
+        let mut state = self.lock();
+        let hash = self.config.dedup_window.map(|window| {
+            state.recent.retain(|_, (_, at)| now.duration_since(*at).unwrap_or_default() < window);
+            content_hash(toast)
+        });
+        if let Some((original, _)) = hash.and_then(|hash| state.recent.get(&hash)) {
+            return Ok(Submitted::Duplicate(original.clone()));
+        }
+        let app_waits = state.waiting.iter().any(|waiting| waiting.toast.app_id == toast.app_id);
+        if !app_waits && self.allows(&mut state, toast, now) {
+            self.record(&mut state, toast, now);
+            drop(state);
+            let inner_id = self.inner.show(toast)?;
+            let mut state = self.lock();
+            if let Some(hash) = hash {
+                state.recent.insert(hash, (id.clone(), now));
+            }
+            state.shown(id.clone(), inner_id);
+            return Ok(Submitted::Shown(id));
+        }
+        while state.waiting.len() >= self.config.capacity {
+            match self.config.overflow {
+                Overflow::DropNewest => {
+                    state.deliver(id.clone(), Delivery::Dropped);
+                    return Ok(Submitted::Dropped(id));
+                }
+                Overflow::DropOldest => {
+                    let Some(oldest) = state.waiting.pop_front() else { break };
+                    state.deliver(oldest.id, Delivery::Dropped);
+                }
+                Overflow::Reject => return Err(NotifyError::QueueFull),
+                Overflow::Block => {
+                    let timed_out;
+                    (state, timed_out) = self.room.wait_timeout(state, BLOCK_TICK).unwrap_or_else(|e| e.into_inner());
+                    if timed_out.timed_out() {
+                        drop(state);
+                        self.tick();
+                        state = self.lock();
+                    }
+                }
+            }
+        }
+        if let Some(hash) = hash {
+            state.recent.insert(hash, (id.clone(), now));
+        }
+        state.deliver(id.clone(), Delivery::Waiting);
+        state.waiting.push_back(Waiting { id: id.clone(), toast: toast.clone() });
+        Ok(Submitted::Queued(id))
+    }
+
+    /// Shows the waiting toasts the limits allow by now, coalescing the apps with too many
+    /// waiting into a summary. Returns how many toasts were shown; the toasts the inner notifier
+    /// failed to show are dropped and reported to subscribers as [`ToastEvent::Failed`].
+    pub fn tick(&self) -> usize {
+        let now = self.clock.now();
+        let mut due: Vec<(Vec<String>, Toast)> = Vec::new();
+        {
+            let mut state = self.lock();
+            // The times of apps and tags that sent nothing within their window are forgotten.
+            if let Some(rate) = self.config.per_app {
+                state.sent_by_app.retain(|_, sent| sent.back().is_some_and(|at| now.duration_since(*at).unwrap_or_default() < rate.per));
+            }
+            if let Some(rate) = self.config.per_tag {
+                state.sent_by_tag.retain(|_, sent| sent.back().is_some_and(|at| now.duration_since(*at).unwrap_or_default() < rate.per));
+            }
+            if let Some(threshold) = self.config.coalesce_after {
+                let mut by_app: BTreeMap<String, usize> = BTreeMap::new();
+                for waiting in &state.waiting {
+                    *by_app.entry(waiting.toast.app_id.clone()).or_default() += 1;
+                }
+                for (app_id, count) in by_app {
+                    if count <= threshold || !self.app_allows(&mut state, &app_id, now) {
+                        continue;
+                    }
+                    let (merged, rest): (VecDeque<Waiting>, VecDeque<Waiting>) = std::mem::take(&mut state.waiting).into_iter()
+                        .partition(|waiting| waiting.toast.app_id == app_id);
+                    state.waiting = rest;
+                    let summary = summary_toast(&app_id, merged.iter().map(|waiting| &waiting.toast));
+                    self.record(&mut state, &summary, now);
+                    due.push((merged.into_iter().map(|waiting| waiting.id).collect(), summary));
+                }
+            }
+            let mut kept = VecDeque::new();
+            while let Some(waiting) = state.waiting.pop_front() {
+                // Toasts of an app keep their order, so one held back holds back the rest.
+                let app_held = kept.iter().any(|held: &Waiting| held.toast.app_id == waiting.toast.app_id);
+                if !app_held && self.allows(&mut state, &waiting.toast, now) {
+                    self.record(&mut state, &waiting.toast, now);
+                    due.push((vec![waiting.id], waiting.toast));
+                } else {
+                    kept.push_back(waiting);
+                }
+            }
+            state.waiting = kept;
+        }
+        self.room.notify_all();
+        let mut shown = 0;
+        for (ids, toast) in due {
+            let result = self.inner.show(&toast);
+            let mut state = self.lock();
+            match (result, ids.as_slice()) {
+                (Ok(inner_id), [id]) => {
+                    state.shown(id.clone(), inner_id);
+                    shown += 1;
+                }
+                (Ok(inner_id), _) => {
+                    let summary_id = next_id();
+                    for id in ids {
+                        state.deliver(id, Delivery::Coalesced(summary_id.clone()));
+                    }
+                    state.shown(summary_id, inner_id);
+                    shown += 1;
+                }
+                (Err(e), _) => {
+                    for id in &ids {
+                        state.deliver(id.clone(), Delivery::Dropped);
+                    }
+                    drop(state);
+                    for id in ids {
+                        self.subscribers.send(ToastEvent::Failed { id, message: e.to_string() });
+                    }
+                }
+            }
+        }
+        shown
+    }
+
+    /// How many toasts are waiting.
+    pub fn len(&self) -> usize {
+        self.lock().waiting.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Calls [`tick`](SendQueue::tick) every `interval` until the queue is dropped.
+    pub fn spawn_pump(self: &Arc<Self>, interval: Duration) -> thread::JoinHandle<()> {
+        clock::spawn_pump(self, interval, |queue| {
+            queue.tick();
+        })
+    }
+
+    fn allows(&self, state: &mut State, toast: &Toast, now: SystemTime) -> bool {
+        if !self.app_allows(state, &toast.app_id, now) {
+            return false;
+        }
+        match (self.config.per_tag, &toast.tag) {
+            (Some(rate), Some(tag)) => under(state.sent_by_tag.entry(tag.clone()).or_default(), rate, now),
+            _ => true,
+        }
+    }
+
+    fn app_allows(&self, state: &mut State, app_id: &str, now: SystemTime) -> bool {
+        match self.config.per_app {
+            Some(rate) => under(state.sent_by_app.entry(app_id.to_string()).or_default(), rate, now),
+            None => true,
+        }
+    }
+
+    fn record(&self, state: &mut State, toast: &Toast, now: SystemTime) {
+        if self.config.per_app.is_some() {
+            state.sent_by_app.entry(toast.app_id.clone()).or_default().push_back(now);
+        }
+        if let (Some(_), Some(tag)) = (self.config.per_tag, &toast.tag) {
+            state.sent_by_tag.entry(tag.clone()).or_default().push_back(now);
+        }
+    }
+
+    /// The id of the inner notifier for a queue id, following coalesced toasts to their summary.
+    fn inner_id(&self, id: &str) -> Result<Option<String>, NotifyError> {
+        let state = self.lock();
+        let mut delivery = state.deliveries.get(id);
+        while let Some(Delivery::Coalesced(summary)) = delivery {
+            delivery = state.deliveries.get(summary);
+        }
+        match delivery {
+            Some(Delivery::Shown(inner_id)) => Ok(Some(inner_id.clone())),
+            Some(Delivery::Waiting) => Ok(None),
+            _ => Err(NotifyError::NotFound(id.to_string())),
+        }
+    }
+}
+
+/// Forgets the times that fell out of the window and tells whether another toast fits.
+fn under(sent: &mut VecDeque<SystemTime>, rate: Rate, now: SystemTime) -> bool {
+    while sent.front().is_some_and(|at| now.duration_since(*at).unwrap_or_default() >= rate.per) {
+        sent.pop_front();
+    }
+    sent.len() < rate.count
+}
+
+fn content_hash(toast: &Toast) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(toast).unwrap_or_default().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// One toast standing in for many, with a table of their titles and how often each came.
+pub(crate) fn summary_toast<'a>(app_id: &str, toasts: impl Iterator<Item = &'a Toast>) -> Toast {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    let mut total = 0;
+    for toast in toasts {
+        total += 1;
+        let title = toast.binding_elems.first().map(|binding| binding.title.0.clone()).unwrap_or_default();
+        match counts.iter_mut().find(|(seen, _)| *seen == title) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((title, 1)),
+        }
+    }
+    let shown = counts.len().min(5);
+    let mut titles: Vec<SubgroupElement> = Vec::new();
+    let mut numbers: Vec<SubgroupElement> = Vec::new();
+    for (title, count) in &counts[..shown] {
+        titles.push(SubgroupElement::Text_(InnerText { text: title.clone(), hint_style: TextStyle::Caption, ..InnerText::default() }));
+        numbers.push(SubgroupElement::Text_(InnerText {
+            text: format!("×{}", count),
+            hint_style: TextStyle::CaptionSubtle,
+            hint_align: TextAlign::Right,
+            ..InnerText::default()
+        }));
+    }
+    let mut elems = vec![BindingInnerElement::Group(vec![titles, numbers])];
+    if counts.len() > shown {
+        elems.push(BindingInnerElement::Text(format!("and {} more kinds", counts.len() - shown)));
+    }
+    Toast {
+        app_id: app_id.to_string(),
+        binding_elems: vec![BindingElem {
+            title: (format!("{} notifications", total), None),
+            elems,
+            ..BindingElem::default()
+        }],
+        ..Toast::default()
+    }
+}
+
+impl Notifier for SendQueue {
+    /// Submits the toast and returns the id the queue knows it by, whatever became of it.
+    fn show(&self, toast: &Toast) -> Result<String, NotifyError> {
+        self.submit(toast).map(|submitted| submitted.id().to_string())
+    }
+
+    fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError> {
+        match self.inner_id(id)? {
+            Some(inner_id) => self.inner.update(&inner_id, toast),
+            None => {
+                let mut state = self.lock();
+                match state.waiting.iter_mut().find(|waiting| waiting.id == id) {
+                    Some(waiting) => waiting.toast = toast.clone(),
+                    None => return Err(NotifyError::NotFound(id.to_string())),
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn remove(&self, id: &str) -> Result<(), NotifyError> {
+        match self.inner_id(id)? {
+            Some(inner_id) => self.inner.remove(&inner_id),
+            None => {
+                let mut state = self.lock();
+                state.waiting.retain(|waiting| waiting.id != id);
+                state.deliver(id.to_string(), Delivery::Dropped);
+                self.room.notify_all();
+                Ok(())
+            }
+        }
+    }
+
+    /// The events of the inner notifier, with its ids translated to the ids of the queue, and
+    /// the failures of waiting toasts.
+    fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+        self.subscribers.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::notifier::MockNotifier;
+
+    fn toast(title: &str) -> Toast {
+        Toast { binding_elems: vec![BindingElem { title: (title.to_string(), None), ..BindingElem::default() }], ..Toast::default() }
+    }
+
+    struct Failing;
+
+    impl Notifier for Failing {
+        fn show(&self, _: &Toast) -> Result<String, NotifyError> {
+            Err(NotifyError::Platform("broken".to_string()))
+        }
+
+        fn update(&self, id: &str, _: &Toast) -> Result<(), NotifyError> {
+            Err(NotifyError::NotFound(id.to_string()))
+        }
+
+        fn remove(&self, id: &str) -> Result<(), NotifyError> {
+            Err(NotifyError::NotFound(id.to_string()))
+        }
+
+        fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+            mpsc::channel().1
+        }
+    }
+
+    #[test]
+    fn test_failed_toasts_are_reported() {
+        let clock = Arc::new(FakeClock::default());
+        let queue = SendQueue::with_clock(Arc::new(Failing), QueueConfig {
+            per_app: Some(Rate { count: 1, per: Duration::from_secs(60) }),
+            ..QueueConfig::default()
+        }, clock.clone());
+        let events = queue.subscribe();
+        assert!(queue.submit(&toast("a")).is_err());
+        let Ok(Submitted::Queued(id)) = queue.submit(&toast("b")) else { panic!("expected the toast to be queued") };
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(queue.tick(), 0);
+        let Ok(ToastEvent::Failed { id: failed, .. }) = events.try_recv() else { panic!("expected a failure") };
+        assert_eq!(failed, id);
+        assert!(queue.update(&id, &toast("c")).is_err());
+    }
+
+    #[test]
+    fn test_block_without_pump() {
+        let queue = SendQueue::new(Arc::new(MockNotifier::new()), QueueConfig {
+            per_app: Some(Rate { count: 1, per: Duration::from_millis(100) }),
+            dedup_window: None,
+            coalesce_after: None,
+            capacity: 1,
+            overflow: Overflow::Block,
+            ..QueueConfig::default()
+        });
+        assert!(matches!(queue.submit(&toast("first")), Ok(Submitted::Shown(_))));
+        assert!(matches!(queue.submit(&toast("second")), Ok(Submitted::Queued(_))));
+        // Nothing ticks the queue but the blocked submission, which shows the second toast.
+        assert!(matches!(queue.submit(&toast("third")), Ok(Submitted::Queued(_))));
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_send_queue() {
+        let notifier = Arc::new(MockNotifier::new());
+        let clock = Arc::new(FakeClock::default());
+        let queue = SendQueue::with_clock(notifier.clone(), QueueConfig {
+            per_app: Some(Rate { count: 2, per: Duration::from_secs(60) }),
+            capacity: 5,
+            overflow: Overflow::Reject,
+            ..QueueConfig::default()
+        }, clock.clone());
+        let events = queue.subscribe();
+        let toast = |title: &str| Toast {
+            app_id: "app".to_string(),
+            binding_elems: vec![BindingElem { title: (title.to_string(), None), ..BindingElem::default() }],
+            ..Toast::default()
+        };
+
+        let Ok(Submitted::Shown(first)) = queue.submit(&toast("a")) else { panic!("expected the toast to be shown") };
+        assert_eq!(queue.submit(&toast("a")).unwrap(), Submitted::Duplicate(first.clone()));
+        assert!(matches!(queue.submit(&toast("b")), Ok(Submitted::Shown(_))));
+        assert!(matches!(queue.submit(&toast("c")), Ok(Submitted::Queued(_))));
+        assert_eq!(queue.tick(), 0);
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(queue.tick(), 1);
+        assert_eq!(notifier.shown().len(), 3);
+
+        let inner_id = notifier.current()[0].0.clone();
+        notifier.activate(&inner_id, "", BTreeMap::new());
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)).unwrap().id(), first);
+
+        assert!(matches!(queue.submit(&toast("d")), Ok(Submitted::Shown(_))));
+        let queued: Vec<String> = ["e", "f", "f2", "g", "h"].iter().map(|title| queue.submit(&toast(title)).unwrap().id().to_string()).collect();
+        assert!(matches!(queue.submit(&toast("i")), Err(crate::notifier::NotifyError::QueueFull)));
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(queue.tick(), 1);
+        assert!(queue.is_empty());
+        let summary = notifier.shown().pop().unwrap();
+        assert_eq!(summary.binding_elems[0].title.0, "5 notifications");
+        queue.remove(&queued[0]).unwrap();
+        assert!(notifier.current().iter().all(|(_, toast)| toast.binding_elems[0].title.0 != "5 notifications"));
+    }
+}
@@ -0,0 +1,410 @@
+//! Quiet hours, the times of the week toasts should not interrupt the user.
+//!
+//! A [`QuietNotifier`] applies a [`QuietPolicy`] to the toasts shown through it. During quiet
+//! hours a toast is held, downgraded or dropped, as the rule for the hours says, unless its
+//! scenario breaks through. Held toasts are shown as one digest by [`QuietNotifier::tick`] once
+//! no hours hold them any more; a pump thread started with [`QuietNotifier::spawn_pump`] calls
+//! it periodically.
+
+use std::collections::BTreeMap;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use jiff::civil::{self, Time};
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{self, Clock, SystemClock};
+use crate::notification::{Scenario, SoundSrc, Toast};
+use crate::notifier::{next_id, Notifier, NotifyError, Subscribers, ToastEvent, Tracked};
+use crate::queue::summary_toast;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<civil::Weekday> for Weekday {
+    fn from(day: civil::Weekday) -> Weekday {
+        match day {
+            civil::Weekday::Monday => Weekday::Monday,
+            civil::Weekday::Tuesday => Weekday::Tuesday,
+            civil::Weekday::Wednesday => Weekday::Wednesday,
+            civil::Weekday::Thursday => Weekday::Thursday,
+            civil::Weekday::Friday => Weekday::Friday,
+            civil::Weekday::Saturday => Weekday::Saturday,
+            civil::Weekday::Sunday => Weekday::Sunday,
+        }
+    }
+}
+
+/// A quiet period each day, from `start` to `end` in the time zone. An `end` not after `start`
+/// is on the next day, so 22:00 to 07:00 is overnight.
+///
+/// In the serde form the times are written like `"22:00"`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: Time,
+    pub end: Time,
+    /// The days the period starts on. Every day when empty.
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+    /// An IANA time zone such as `"Europe/Berlin"`. The system time zone when not set.
+    #[serde(default)]
+    pub time_zone: Option<String>,
+}
+
+impl QuietHours {
+    /// Quiet every day from `start` to `end` in the system time zone.
+    pub fn daily(start: Time, end: Time) -> QuietHours {
+        QuietHours { start, end, weekdays: Vec::new(), time_zone: None }
+    }
+
+    /// The time zone the hours are in, or an error if it is not known.
+    pub fn zone(&self) -> Result<TimeZone, jiff::Error> {
+        match &self.time_zone {
+            Some(name) => TimeZone::get(name),
+            None => Ok(TimeZone::system()),
+        }
+    }
+
+    /// Whether `at` falls in a quiet period. An unknown time zone is taken to be UTC, so check
+    /// [`zone`](QuietHours::zone) when the hours are configured.
+    pub fn contains(&self, at: SystemTime) -> bool {
+        let Ok(at) = Timestamp::try_from(at) else { return false };
+        let local = at.to_zoned(self.zone().unwrap_or(TimeZone::UTC));
+        let (time, day) = (local.time(), local.weekday());
+        if self.start < self.end {
+            self.on(day) && self.start <= time && time < self.end
+        } else {
+            // The hours after midnight belong to the period that started the day before.
+            (self.on(day) && time >= self.start) || (self.on(day.previous()) && time < self.end)
+        }
+    }
+
+    fn on(&self, day: civil::Weekday) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&day.into())
+    }
+}
+
+/// What happens to a toast during quiet hours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietAction {
+    /// The toast is shown in a digest when the quiet hours end.
+    #[default]
+    Hold,
+    /// The toast goes silently and straight into the action center.
+    Downgrade,
+    Drop,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuietRule {
+    pub hours: QuietHours,
+    #[serde(default)]
+    pub action: QuietAction,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuietPolicy {
+    /// The first rule whose hours contain the time applies.
+    pub rules: Vec<QuietRule>,
+    /// Toasts with these scenarios are shown even during quiet hours.
+    pub break_through: Vec<Scenario>,
+}
+
+impl Default for QuietPolicy {
+    fn default() -> QuietPolicy {
+        QuietPolicy { rules: Vec::new(), break_through: vec![Scenario::Urgent, Scenario::Alarm] }
+    }
+}
+
+impl QuietPolicy {
+    /// What happens at `now` to a toast with the scenario, or `None` when it is shown as it is.
+    pub fn action(&self, scenario: &Scenario, now: SystemTime) -> Option<QuietAction> {
+        if self.break_through.contains(scenario) {
+            return None;
+        }
+        self.rules.iter().find(|rule| rule.hours.contains(now)).map(|rule| rule.action)
+    }
+}
+
+/// What became of a toast shown through a [`QuietNotifier`]. Each carries the id the notifier
+/// knows the toast by.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Quieted {
+    Shown(String),
+    /// The toast waits for the quiet hours to end.
+    Held(String),
+    Downgraded(String),
+    Dropped(String),
+}
+
+impl Quieted {
+    pub fn id(&self) -> &str {
+        match self {
+            Quieted::Shown(id) | Quieted::Held(id) | Quieted::Downgraded(id) | Quieted::Dropped(id) => id,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Delivery {
+    Held,
+    /// Shown by the inner notifier under this id.
+    Shown(String),
+    /// Shown as part of the digest with this id.
+    InDigest(String),
+    Dropped,
+}
+
+#[derive(Default)]
+struct State {
+    held: Vec<(String, Toast)>,
+    /// Held toasts are never forgotten.
+    deliveries: Tracked<Delivery>,
+}
+
+impl State {
+    fn shown(&mut self, id: String, inner_id: String) {
+        self.deliver(id.clone(), Delivery::Shown(inner_id.clone()));
+        self.deliveries.link(&id, inner_id);
+    }
+
+    fn deliver(&mut self, id: String, delivery: Delivery) {
+        self.deliveries.insert(id, delivery, |_, delivery| matches!(delivery, Delivery::Held));
+    }
+}
+
+pub struct QuietNotifier {
+    inner: Arc<dyn Notifier>,
+    policy: QuietPolicy,
+    clock: Arc<dyn Clock>,
+    state: Arc<Mutex<State>>,
+    subscribers: Subscribers,
+}
+
+impl QuietNotifier {
+    pub fn new(inner: Arc<dyn Notifier>, policy: QuietPolicy) -> QuietNotifier {
+        QuietNotifier::with_clock(inner, policy, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(inner: Arc<dyn Notifier>, policy: QuietPolicy, clock: Arc<dyn Clock>) -> QuietNotifier {
+        let events = inner.subscribe();
+        let state: Arc<Mutex<State>> = Arc::default();
+        let subscribers = Subscribers::default();
+        let (known, forward) = (state.clone(), subscribers.clone());
+        thread::spawn(move || {
+            for event in events {
+                let id = known.lock().unwrap_or_else(|e| e.into_inner()).deliveries.by_inner(event.id()).cloned();
+                forward.send(match id {
+                    Some(id) => event.with_id(id),
+                    None => event,
+                });
+            }
+        });
+        QuietNotifier { inner, policy, clock, state, subscribers }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn submit(&self, toast: &Toast) -> Result<Quieted, NotifyError> {
+        let id = next_id();
+        let mut toast = toast.clone();
+        let quieted = match self.policy.action(&toast.scenario, self.clock.now()) {
+            None => Quieted::Shown(id.clone()),
+            Some(QuietAction::Hold) => {
+                let mut state = self.lock();
+                state.deliver(id.clone(), Delivery::Held);
+                state.held.push((id.clone(), toast));
+                return Ok(Quieted::Held(id));
+            }
+            Some(QuietAction::Downgrade) => {
+                downgrade(&mut toast);
+                Quieted::Downgraded(id.clone())
+            }
+            Some(QuietAction::Drop) => {
+                self.lock().deliver(id.clone(), Delivery::Dropped);
+                return Ok(Quieted::Dropped(id));
+            }
+        };
+        let inner_id = self.inner.show(&toast)?;
+        self.lock().shown(id, inner_id);
+        Ok(quieted)
+    }
+
+    /// Shows the held toasts as a digest for each app, if no quiet hours hold them any more.
+    /// An app with a single held toast gets the toast itself. Returns how many held toasts were
+    /// delivered, alone or in a digest; those the inner notifier failed to show are dropped.
+    pub fn tick(&self) -> usize {
+        let now = self.clock.now();
+        if self.policy.rules.iter().any(|rule| rule.action == QuietAction::Hold && rule.hours.contains(now)) {
+            return 0;
+        }
+        let held = std::mem::take(&mut self.lock().held);
+        let mut by_app: BTreeMap<String, Vec<(String, Toast)>> = BTreeMap::new();
+        for (id, toast) in held {
+            by_app.entry(toast.app_id.clone()).or_default().push((id, toast));
+        }
+        let mut delivered = 0;
+        for (app_id, mut toasts) in by_app {
+            if toasts.len() == 1 {
+                let (id, toast) = toasts.remove(0);
+                let result = self.inner.show(&toast);
+                let mut state = self.lock();
+                match result {
+                    Ok(inner_id) => {
+                        state.shown(id, inner_id);
+                        delivered += 1;
+                    }
+                    Err(_) => {
+                        state.deliver(id, Delivery::Dropped);
+                    }
+                }
+                continue;
+            }
+            let mut digest = summary_toast(&app_id, toasts.iter().map(|(_, toast)| toast));
+            digest.binding_elems[0].title.0 = format!("{} notifications during quiet hours", toasts.len());
+            let result = self.inner.show(&digest);
+            let mut state = self.lock();
+            let digest_id = next_id();
+            let count = toasts.len();
+            for (id, _) in toasts {
+                let delivery = if result.is_ok() { Delivery::InDigest(digest_id.clone()) } else { Delivery::Dropped };
+                state.deliver(id, delivery);
+            }
+            if let Ok(inner_id) = result {
+                state.shown(digest_id, inner_id);
+                delivered += count;
+            }
+        }
+        delivered
+    }
+
+    /// How many toasts are held.
+    pub fn held(&self) -> usize {
+        self.lock().held.len()
+    }
+
+    /// Calls [`tick`](QuietNotifier::tick) every `interval` until the notifier is dropped.
+    pub fn spawn_pump(self: &Arc<Self>, interval: Duration) -> thread::JoinHandle<()> {
+        clock::spawn_pump(self, interval, |notifier| {
+            notifier.tick();
+        })
+    }
+
+    /// The id of the inner notifier for an id, following held toasts to their digest. `None`
+    /// for a toast that is still held.
+    fn inner_id(&self, id: &str) -> Result<Option<String>, NotifyError> {
+        let state = self.lock();
+        let mut delivery = state.deliveries.get(id);
+        if let Some(Delivery::InDigest(digest)) = delivery {
+            delivery = state.deliveries.get(digest);
+        }
+        match delivery {
+            Some(Delivery::Shown(inner_id)) => Ok(Some(inner_id.clone())),
+            Some(Delivery::Held) => Ok(None),
+            _ => Err(NotifyError::NotFound(id.to_string())),
+        }
+    }
+}
+
+/// Keeps the toast from making a sound or showing on screen.
+fn downgrade(toast: &mut Toast) {
+    toast.audio = Some((false, true, SoundSrc::Default));
+    toast.suppress_popup = true;
+    toast.scenario = Scenario::Default;
+}
+
+impl Notifier for QuietNotifier {
+    /// Submits the toast and returns the id the notifier knows it by, whatever became of it.
+    fn show(&self, toast: &Toast) -> Result<String, NotifyError> {
+        self.submit(toast).map(|quieted| quieted.id().to_string())
+    }
+
+    fn update(&self, id: &str, toast: &Toast) -> Result<(), NotifyError> {
+        match self.inner_id(id)? {
+            Some(inner_id) => self.inner.update(&inner_id, toast),
+            None => {
+                let mut state = self.lock();
+                let held = state.held.iter_mut().find(|(held_id, _)| held_id == id)
+                    .ok_or_else(|| NotifyError::NotFound(id.to_string()))?;
+                held.1 = toast.clone();
+                Ok(())
+            }
+        }
+    }
+
+    fn remove(&self, id: &str) -> Result<(), NotifyError> {
+        match self.inner_id(id)? {
+            Some(inner_id) => self.inner.remove(&inner_id),
+            None => {
+                let mut state = self.lock();
+                state.held.retain(|(held_id, _)| held_id != id);
+                state.deliver(id.to_string(), Delivery::Dropped);
+                Ok(())
+            }
+        }
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<ToastEvent> {
+        self.subscribers.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::notifier::MockNotifier;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_quiet_hours() {
+        let utc = |start: &str, end: &str, weekdays: Vec<Weekday>| QuietHours {
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+            weekdays,
+            time_zone: Some("UTC".to_string()),
+        };
+        let notifier = Arc::new(MockNotifier::new());
+        // The Unix epoch was a Thursday, at midnight in UTC.
+        let clock = Arc::new(FakeClock::default());
+        let quiet = QuietNotifier::with_clock(notifier.clone(), QuietPolicy {
+            rules: vec![
+                QuietRule { hours: utc("22:00", "07:00", vec![]), action: QuietAction::Hold },
+                QuietRule { hours: utc("07:00", "09:00", vec![Weekday::Thursday]), action: QuietAction::Downgrade },
+            ],
+            ..QuietPolicy::default()
+        }, clock.clone());
+
+        let Ok(Quieted::Held(held)) = quiet.submit(&sample_toast()) else { panic!("expected the toast to be held") };
+        assert!(matches!(quiet.submit(&sample_toast()), Ok(Quieted::Held(_))));
+        assert!(matches!(quiet.submit(&Toast { scenario: Scenario::Urgent, ..sample_toast() }), Ok(Quieted::Shown(_))));
+        assert_eq!(quiet.tick(), 0);
+
+        clock.advance(Duration::from_secs(7 * 3600));
+        assert_eq!(quiet.tick(), 2);
+        assert_eq!(quiet.held(), 0);
+        assert_eq!(notifier.shown().pop().unwrap().binding_elems[0].title.0, "2 notifications during quiet hours");
+
+        assert!(matches!(quiet.submit(&sample_toast()), Ok(Quieted::Downgraded(_))));
+        let downgraded = notifier.shown().pop().unwrap();
+        assert!(downgraded.suppress_popup && downgraded.audio == Some((false, true, SoundSrc::Default)));
+        quiet.remove(&held).unwrap();
+        assert!(notifier.current().iter().all(|(_, toast)| !toast.binding_elems[0].title.0.contains("quiet hours")));
+    }
+}
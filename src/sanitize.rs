@@ -0,0 +1,219 @@
+//! Characters that XML 1.0 does not allow, which make Windows reject the whole payload.
+//!
+//! Text taken from logs and terminals brings control characters along, such as the escape that
+//! starts a color code, and payloads written by hand can hold character references to them or
+//! to lone surrogates like `&#xD800;`. The serializer of [`Toast::to_xml_with`] and
+//! [`sanitize_xml`] apply the [`SanitizePolicy`] they are given to them, and report each one the
+//! same way, with [`Toast::sanitize_report_with`] and [`sanitize_xml`]. [`Toast::to_xml`] and
+//! `Toast::show_with_xml` use the default set with [`set_policy`].
+//!
+//! Markup in text needs no policy: it is always escaped, so it shows as written.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use crate::notification::*;
+use crate::validate::Diagnostic;
+use crate::xml::Element;
+
+/// What is done with a character that XML 1.0 does not allow.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SanitizePolicy {
+    /// The character is left out.
+    #[default]
+    Strip,
+    /// The character is replaced by U+FFFD, the replacement character.
+    Replace,
+    /// The character is written as text, like `\u{1b}`, so it stays visible.
+    Escape,
+}
+
+impl SanitizePolicy {
+    fn name(self) -> &'static str {
+        match self {
+            SanitizePolicy::Strip => "strip",
+            SanitizePolicy::Replace => "replace",
+            SanitizePolicy::Escape => "escape",
+        }
+    }
+
+    pub(crate) fn apply(self, code_point: u32, out: &mut String) {
+        match self {
+            SanitizePolicy::Strip => {}
+            SanitizePolicy::Replace => out.push(char::REPLACEMENT_CHARACTER),
+            SanitizePolicy::Escape => out.push_str(&format!("\\u{{{:x}}}", code_point)),
+        }
+    }
+
+    fn diagnostic(self, path: String, code_point: u32, place: &str) -> Diagnostic {
+        let done = match self {
+            SanitizePolicy::Strip => "stripped",
+            SanitizePolicy::Replace => "replaced",
+            SanitizePolicy::Escape => "escaped",
+        };
+        Diagnostic::warning(path, format!("U+{:04X}{} is not allowed in XML and is {}", code_point, place, done))
+    }
+}
+
+impl fmt::Display for SanitizePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SanitizePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SanitizePolicy, String> {
+        [SanitizePolicy::Strip, SanitizePolicy::Replace, SanitizePolicy::Escape].into_iter()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| format!("{:?} is not strip, replace or escape", s))
+    }
+}
+
+static POLICY: RwLock<SanitizePolicy> = RwLock::new(SanitizePolicy::Strip);
+
+/// Sets the default policy, for every payload built or shown from now on without one.
+pub fn set_policy(policy: SanitizePolicy) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+pub fn policy() -> SanitizePolicy {
+    *POLICY.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether XML 1.0 allows the character, which surrogates it never does.
+pub fn is_xml_char(code_point: u32) -> bool {
+    matches!(code_point, 0x9 | 0xA | 0xD | 0x20..=0xD7FF | 0xE000..=0xFFFD | 0x10000..=0x10FFFF)
+}
+
+/// Where character references are only text, with how each of them starts and ends.
+const VERBATIM: &[(&str, &str)] = &[("<![CDATA[", "]]>"), ("<!--", "-->")];
+
+/// The payload with the policy applied to the characters XML 1.0 does not allow, both written
+/// out and as character references, and a warning for each at its line and column. In CDATA
+/// sections and comments, where references are text, only the characters are.
+pub fn sanitize_xml(xml: &str, policy: SanitizePolicy) -> (String, Vec<Diagnostic>) {
+    let mut out = String::with_capacity(xml.len());
+    let mut report = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    let mut rest = xml;
+    // The end of the CDATA section or comment `rest` is in.
+    let mut verbatim: Option<&str> = None;
+    while let Some(c) = rest.chars().next() {
+        match verbatim {
+            None => verbatim = VERBATIM.iter().find(|(start, _)| rest.starts_with(start)).map(|(_, end)| *end),
+            Some(end) if rest.starts_with(end) => verbatim = None,
+            Some(_) => {}
+        }
+        let path = format!("line {}, column {}", line, column);
+        let reference = if verbatim.is_none() { char_reference(rest) } else { None };
+        let (code_point, len, place) = match reference {
+            Some((code_point, len)) => (code_point, len, " as a character reference"),
+            None => (c as u32, c.len_utf8(), ""),
+        };
+        if is_xml_char(code_point) {
+            out.push_str(&rest[..len]);
+        } else {
+            policy.apply(code_point, &mut out);
+            report.push(policy.diagnostic(path, code_point, place));
+        }
+        if c == '\n' {
+            (line, column) = (line + 1, 1);
+        } else {
+            column += rest[..len].chars().count();
+        }
+        rest = &rest[len..];
+    }
+    (out, report)
+}
+
+/// The code point and length of the character reference `rest` starts with.
+fn char_reference(rest: &str) -> Option<(u32, usize)> {
+    let reference = rest.strip_prefix("&#")?;
+    let end = reference.find(';')?;
+    let digits = &reference[..end];
+    let code_point = match digits.strip_prefix('x') {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some((code_point, "&#;".len() + end))
+}
+
+impl Toast {
+    /// A warning for each character of the toast that XML 1.0 does not allow, at the path of its
+    /// element in the payload, saying what the serializer does with it under the default policy.
+    pub fn sanitize_report(&self) -> Vec<Diagnostic> {
+        self.sanitize_report_with(policy())
+    }
+
+    /// Like [`Toast::sanitize_report`], for what [`Toast::to_xml_with`] does under `policy`.
+    pub fn sanitize_report_with(&self, policy: SanitizePolicy) -> Vec<Diagnostic> {
+        let mut report = Vec::new();
+        element_report(&self.to_element(), "", 1, policy, &mut report);
+        report
+    }
+}
+
+fn element_report(elem: &Element, parent_path: &str, index: usize, policy: SanitizePolicy, report: &mut Vec<Diagnostic>) {
+    let path = format!("{}/{}[{}]", parent_path, elem.name, index);
+    for (name, value) in &elem.attrs {
+        let place = format!(" in the {} attribute", name);
+        report.extend(value.chars().filter(|c| !is_xml_char(*c as u32)).map(|c| policy.diagnostic(path.clone(), c as u32, &place)));
+    }
+    if let Some(text) = &elem.text {
+        report.extend(text.chars().filter(|c| !is_xml_char(*c as u32)).map(|c| policy.diagnostic(path.clone(), c as u32, "")));
+    }
+    for (i, child) in elem.children.iter().enumerate() {
+        let index = elem.children[..i].iter().filter(|sibling| sibling.name == child.name).count() + 1;
+        element_report(child, &path, index, policy, report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_toast;
+    use crate::validate::validate_xml;
+
+    #[test]
+    fn test_policy_per_call() {
+        let toast = Toast { binding_elems: vec![BindingElem { title: ("a\u{7}b".to_string(), None), ..BindingElem::default() }], ..Toast::default() };
+        assert!(toast.to_xml_with(SanitizePolicy::Replace).contains("<text>a\u{fffd}b</text>"));
+        assert!(toast.to_xml_with(SanitizePolicy::Escape).contains("<text>a\\u{7}b</text>"));
+        assert!(toast.sanitize_report_with(SanitizePolicy::Replace)[0].message.ends_with("is replaced"));
+    }
+
+    #[test]
+    fn test_references_in_cdata_and_comments() {
+        let payload = "<toast><!-- &#x1; \u{1} --><text><![CDATA[&#0; \u{2}]]>&#3;</text></toast>";
+        let (sanitized, report) = sanitize_xml(payload, SanitizePolicy::Strip);
+        assert_eq!(sanitized, "<toast><!-- &#x1;  --><text><![CDATA[&#0; ]]></text></toast>");
+        let places: Vec<&str> = report.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(places, ["U+0001 is not allowed in XML and is stripped", "U+0002 is not allowed in XML and is stripped",
+            "U+0003 as a character reference is not allowed in XML and is stripped"]);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut toast = sample_toast();
+        toast.binding_elems[0].title.0 = "\u{1b}[31mfailed\u{1b}[0m <b>".to_string();
+        let xml = toast.to_xml();
+        assert!(xml.contains("<text>[31mfailed[0m &lt;b&gt;</text>"));
+        assert!(roxmltree::Document::parse(&xml).is_ok());
+        let report = toast.sanitize_report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].to_string(), "warning: /toast[1]/visual[1]/binding[1]/text[1]: U+001B is not allowed in XML and is stripped");
+        assert_eq!(toast.validate().iter().filter(|diagnostic| diagnostic.path.ends_with("text[1]")).count(), 2);
+
+        let payload = "<toast><visual><binding template=\"ToastGeneric\">\n<text>a\u{7}b &#xD800; &#65;</text></binding></visual></toast>";
+        let (sanitized, report) = sanitize_xml(payload, SanitizePolicy::Escape);
+        assert!(sanitized.contains("<text>a\\u{7}b \\u{d800} &#65;</text>"));
+        let paths: Vec<&str> = report.iter().map(|diagnostic| diagnostic.path.as_str()).collect();
+        assert_eq!(paths, ["line 2, column 8", "line 2, column 11"]);
+        assert!(report[1].message.contains("as a character reference"));
+        assert!(validate_xml(payload).iter().all(|diagnostic| !diagnostic.is_error()));
+        assert_eq!("replace".parse(), Ok(SanitizePolicy::Replace));
+    }
+}
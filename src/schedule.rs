@@ -0,0 +1,156 @@
+//! A file-backed list of toasts to be delivered later.
+//!
+//! On Windows, scheduled toasts are also handed to the `ToastNotifier` schedule so the OS
+//! delivers them even when nothing of ours is running; elsewhere the store is only a record.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::notification::Toast;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledToast {
+    /// At most 16 characters, which is the limit of `ScheduledToastNotification.Id`.
+    pub id: String,
+    pub deliver_at: SystemTime,
+    pub toast: Toast,
+}
+
+pub struct ScheduleStore {
+    path: PathBuf,
+}
+
+impl ScheduleStore {
+    /// Opens the store kept in the JSON file at `path`, which is created on the first change.
+    pub fn open(path: impl Into<PathBuf>) -> ScheduleStore {
+        ScheduleStore { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The scheduled toasts, earliest first.
+    pub fn list(&self) -> io::Result<Vec<ScheduledToast>> {
+        let mut entries: Vec<ScheduledToast> = match fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        entries.sort_by_key(|entry| entry.deliver_at);
+        Ok(entries)
+    }
+
+    pub fn add(&self, toast: Toast, deliver_at: SystemTime) -> io::Result<ScheduledToast> {
+        let mut entries = self.list()?;
+        let mut seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+            ^ ((std::process::id() as u64) << 32);
+        let id = loop {
+            let id = format!("{:08x}", (seed ^ (seed >> 29)) as u32);
+            if !entries.iter().any(|entry| entry.id == id) {
+                break id;
+            }
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        };
+        let entry = ScheduledToast { id, deliver_at, toast };
+        #[cfg(windows)]
+        os::add(&entry).map_err(|e| io::Error::other(e.message().to_string()))?;
+        entries.push(entry.clone());
+        self.save(&entries)?;
+        Ok(entry)
+    }
+
+    /// Removes the toast with this id, returning it if it was scheduled.
+    pub fn cancel(&self, id: &str) -> io::Result<Option<ScheduledToast>> {
+        let mut entries = self.list()?;
+        let Some(index) = entries.iter().position(|entry| entry.id == id) else {
+            return Ok(None);
+        };
+        let entry = entries.remove(index);
+        #[cfg(windows)]
+        os::remove(&entry).map_err(|e| io::Error::other(e.message().to_string()))?;
+        self.save(&entries)?;
+        Ok(Some(entry))
+    }
+
+    /// Removes and returns the toasts due at `now`, for delivery by something other than the OS.
+    pub fn take_due(&self, now: SystemTime) -> io::Result<Vec<ScheduledToast>> {
+        let (due, pending): (Vec<ScheduledToast>, Vec<ScheduledToast>) = self.list()?
+            .into_iter()
+            .partition(|entry| entry.deliver_at <= now);
+        if !due.is_empty() {
+            self.save(&pending)?;
+        }
+        Ok(due)
+    }
+
+    fn save(&self, entries: &[ScheduledToast]) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(entries)?)?;
+        fs::rename(tmp, &self.path)
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use std::time::UNIX_EPOCH;
+
+    use windows::core::*;
+    use windows::Data::Xml::Dom::XmlDocument;
+    use windows::Foundation::DateTime;
+    use windows::UI::Notifications::{ScheduledToastNotification, ToastNotificationManager};
+
+    use super::ScheduledToast;
+
+    /// The number of seconds between 1601-01-01, where `DateTime` starts, and the Unix epoch.
+    const EPOCH_DIFFERENCE: i64 = 11_644_473_600;
+
+    pub(super) fn add(entry: &ScheduledToast) -> Result<()> {
+        let xml: XmlDocument = XmlDocument::new()?;
+        xml.LoadXml(&HSTRING::from(entry.toast.to_xml()))?;
+        let since_epoch = entry.deliver_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let time = DateTime {
+            UniversalTime: (since_epoch.as_secs() as i64 + EPOCH_DIFFERENCE) * 10_000_000 + since_epoch.subsec_nanos() as i64 / 100,
+        };
+        let notification = ScheduledToastNotification::CreateScheduledToastNotification(&xml, time)?;
+        notification.SetId(&HSTRING::from(&entry.id))?;
+        ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(&entry.toast.app_id))?.AddToSchedule(&notification)
+    }
+
+    pub(super) fn remove(entry: &ScheduledToast) -> Result<()> {
+        let notifier = ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(&entry.toast.app_id))?;
+        for notification in notifier.GetScheduledToastNotifications()? {
+            if notification.Id()? == entry.id.as_str() {
+                notifier.RemoveFromSchedule(&notification)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_schedule_store() -> std::io::Result<()> {
+        let path = std::env::temp_dir().join(format!("win_toast-schedule-{}.json", std::process::id()));
+        let store = ScheduleStore::open(&path);
+        let now = std::time::SystemTime::now();
+        let later = store.add(sample_toast(), now + std::time::Duration::from_secs(600))?;
+        let due = store.add(sample_toast(), now)?;
+        assert_eq!(store.list()?.iter().map(|entry| &entry.id).collect::<Vec<_>>(), vec![&due.id, &later.id]);
+        assert_eq!(store.take_due(now)?, vec![due]);
+        assert_eq!(store.cancel(&later.id)?, Some(later));
+        assert!(store.list()?.is_empty());
+        std::fs::remove_file(path)
+    }
+}
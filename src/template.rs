@@ -0,0 +1,173 @@
+//! Toast templates, toast definitions filled in with values each time they are used.
+//!
+//! A template is a toast written as XML or in the serde form as JSON or TOML, with Handlebars
+//! expressions in it: `{{name}}` inserts a value, `{{#if paid}}…{{else}}…{{/if}}` keeps one part
+//! or the other and `{{#each items}}…{{/each}}` repeats a part for every item of a list, such as
+//! a `<subgroup>` per line of an order. Values are escaped for the format of the template, so
+//! in JSON and TOML placeholders belong inside double-quoted strings. In JSON, separate the
+//! repeated parts with `{{#unless @last}},{{/unless}}`.
+//!
+//! Templates are compiled once, when they are registered with a [`TemplateEngine`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use handlebars::Handlebars;
+use serde::Serialize;
+
+use crate::notification::Toast;
+use crate::notifier::prepare;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TemplateFormat {
+    Xml,
+    Json,
+    Toml,
+}
+
+impl TemplateFormat {
+    /// The format of a file by its extension.
+    pub fn from_path(path: &Path) -> Option<TemplateFormat> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "xml" => Some(TemplateFormat::Xml),
+            "json" => Some(TemplateFormat::Json),
+            "toml" => Some(TemplateFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// An error in a template, or in the toast a template rendered to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateError {
+    pub message: String,
+}
+
+impl TemplateError {
+    fn new(message: impl Into<String>) -> TemplateError {
+        TemplateError { message: message.into() }
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Compiled templates by name, rendered into toasts.
+///
+/// A value the context does not have is an error rather than nothing, so a misspelled name is
+/// not sent to users. `{{#if}}` tolerates missing values.
+pub struct TemplateEngine {
+    /// One registry per format, as the escaping applies to a whole registry.
+    registries: HashMap<TemplateFormat, Handlebars<'static>>,
+    formats: HashMap<String, TemplateFormat>,
+}
+
+impl Default for TemplateEngine {
+    fn default() -> TemplateEngine {
+        let registries = [TemplateFormat::Xml, TemplateFormat::Json, TemplateFormat::Toml].into_iter()
+            .map(|format| {
+                let mut registry = Handlebars::new();
+                registry.set_strict_mode(true);
+                match format {
+                    TemplateFormat::Xml => registry.register_escape_fn(handlebars::html_escape),
+                    // JSON escapes are valid in the basic strings of TOML as well.
+                    TemplateFormat::Json | TemplateFormat::Toml => registry.register_escape_fn(|value| {
+                        let quoted = serde_json::to_string(value).unwrap_or_default();
+                        quoted[1..quoted.len() - 1].to_string()
+                    }),
+                }
+                (format, registry)
+            })
+            .collect();
+        TemplateEngine { registries, formats: HashMap::new() }
+    }
+}
+
+impl TemplateEngine {
+    pub fn new() -> TemplateEngine {
+        TemplateEngine::default()
+    }
+
+    /// Compiles the template and keeps it under the name, replacing one of the same name.
+    pub fn register(&mut self, name: &str, format: TemplateFormat, source: &str) -> Result<(), TemplateError> {
+        if let Some(previous) = self.formats.insert(name.to_string(), format) {
+            self.registry_mut(previous).unregister_template(name);
+        }
+        self.registry_mut(format).register_template_string(name, source)
+            .map_err(|e| TemplateError::new(format!("the template {} does not compile: {}", name, e)))
+    }
+
+    /// Registers the template in the file under the file name without its extension, which also
+    /// gives the format. Returns the name.
+    pub fn register_file(&mut self, path: &Path) -> Result<String, TemplateError> {
+        let format = TemplateFormat::from_path(path)
+            .ok_or_else(|| TemplateError::new(format!("{} is not an .xml, .json or .toml file", path.display())))?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| TemplateError::new(format!("{} cannot be read: {}", path.display(), e)))?;
+        self.register(&name, format, &source)?;
+        Ok(name)
+    }
+
+    pub fn has_template(&self, name: &str) -> bool {
+        self.formats.contains_key(name)
+    }
+
+    /// Fills in the template with the values of the context and checks the toast it describes.
+    /// A toast without an app id gets the one of Windows PowerShell.
+    pub fn render(&self, name: &str, context: &impl Serialize) -> Result<Toast, TemplateError> {
+        let format = *self.formats.get(name).ok_or_else(|| TemplateError::new(format!("there is no template named {:?}", name)))?;
+        let text = self.registries[&format].render(name, context)
+            .map_err(|e| TemplateError::new(format!("the template {} cannot be rendered: {}", name, e)))?;
+        let toast = match format {
+            TemplateFormat::Xml => Toast::from_xml("", &text).map_err(|e| e.to_string()),
+            TemplateFormat::Json => serde_json::from_str(&text).map_err(|e| e.to_string()),
+            TemplateFormat::Toml => toml::from_str(&text).map_err(|e| e.to_string()),
+        };
+        let toast = toast.map_err(|message| TemplateError::new(format!("the template {} renders an invalid toast: {}", name, message)))?;
+        prepare(toast).map_err(|message| TemplateError::new(format!("the template {} renders an invalid toast: {}", name, message)))
+    }
+
+    fn registry_mut(&mut self, format: TemplateFormat) -> &mut Handlebars<'static> {
+        self.registries.get_mut(&format).expect("there is a registry for every format")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::notification::*;
+
+    #[test]
+    fn test_templates() {
+        let mut engine = TemplateEngine::new();
+        engine.register("order", TemplateFormat::Xml, r#"<toast><visual><binding template="ToastGeneric">
+            <text>Hi {{name}}</text>{{#if vip}}<text>Thanks for staying with us</text>{{/if}}
+            <group>{{#each items}}<subgroup><text hint-style="caption">{{this}}</text></subgroup>{{/each}}</group>
+            </binding></visual></toast>"#).unwrap();
+        let toast = engine.render("order", &json!({ "name": "Ann & <Bob>", "items": ["tea", "cake"] })).unwrap();
+        assert_eq!(toast.app_id, Toast::POWERSHELL_APP_ID);
+        assert_eq!(toast.binding_elems[0].title.0, "Hi Ann & <Bob>");
+        let BindingInnerElement::Group(subgroups) = &toast.binding_elems[0].elems[0] else { panic!("expected a group") };
+        assert_eq!(subgroups.len(), 2);
+
+        engine.register("json", TemplateFormat::Json, r#"{"binding_elems": [{"title": "For {{name}}",
+            "elems": [{{#each items}}{"Text": "{{this}}"}{{#unless @last}},{{/unless}}{{/each}}]}]}"#).unwrap();
+        let toast = engine.render("json", &json!({ "name": "\"Ann\"", "items": ["a", "b"] })).unwrap();
+        assert_eq!(toast.binding_elems[0].title.0, "For \"Ann\"");
+        assert_eq!(toast.binding_elems[0].elems, vec![BindingInnerElement::Text("a".into()), BindingInnerElement::Text("b".into())]);
+
+        assert!(engine.render("json", &json!({ "items": [] })).unwrap_err().message.contains("cannot be rendered"));
+        engine.register("empty", TemplateFormat::Toml, "app_id = \"{{app}}\"").unwrap();
+        assert!(engine.render("empty", &json!({ "app": "app" })).unwrap_err().message.contains("a toast needs a binding"));
+    }
+}
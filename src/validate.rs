@@ -0,0 +1,304 @@
+//! Checks of a toast against the toast content schema and the limits Windows applies.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::legacy::left_out;
+use crate::notification::*;
+use crate::payload::MAX_PAYLOAD_BYTES;
+use crate::preview::visible_actions;
+use crate::sanitize::{policy, sanitize_xml};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// Windows will reject the toast, or this crate cannot build it.
+    Error,
+    /// The toast will be shown, but not as written.
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Where the problem is, either a field path such as `binding_elems[0].hero` or an element
+    /// path such as `/toast/actions/action[2]`.
+    pub path: String,
+    pub message: String,
+    /// How to fix the problem, when there is an obvious way.
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(path: impl Into<String>, message: impl Into<String>) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, path: path.into(), message: message.into(), suggestion: None }
+    }
+
+    pub fn warning(path: impl Into<String>, message: impl Into<String>) -> Diagnostic {
+        Diagnostic { severity: Severity::Warning, path: path.into(), message: message.into(), suggestion: None }
+    }
+
+    pub fn suggest(mut self, suggestion: impl Into<String>) -> Diagnostic {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", severity, self.path, self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n  help: {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl Toast {
+    /// Checks the toast for content Windows rejects or silently drops.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if self.app_id.is_empty() {
+            diagnostics.push(Diagnostic::error("app_id", "the app id is empty"));
+        }
+        if self.binding_elems.is_empty() {
+            diagnostics.push(Diagnostic::error("binding_elems", "a toast needs a binding"));
+        }
+        for (i, binding) in self.binding_elems.iter().enumerate() {
+            let path = format!("binding_elems[{}]", i);
+            if binding.title.0.is_empty() {
+                diagnostics.push(Diagnostic::warning(format!("{}.title", path), "the title is empty"));
+            }
+            if binding.template.has_image() && binding.icon.is_none() {
+                diagnostics.push(Diagnostic::warning(format!("{}.icon", path), format!("{} needs an image", binding.template.attr())));
+            }
+            for (field, message) in left_out(binding) {
+                diagnostics.push(Diagnostic::warning(format!("{}.{}", path, field), message));
+            }
+            if let Some(image) = &binding.icon {
+                validate_image(image, &format!("{}.icon", path), &mut diagnostics);
+            }
+            if let Some(image) = &binding.hero {
+                validate_image(image, &format!("{}.hero", path), &mut diagnostics);
+            }
+            for (j, elem) in binding.elems.iter().enumerate() {
+                let path = format!("{}.elems[{}]", path, j);
+                match elem {
+                    BindingInnerElement::Text(_) => {}
+                    BindingInnerElement::Image(image) => validate_image(image, &path, &mut diagnostics),
+                    BindingInnerElement::Group(subgroups) => {
+                        if subgroups.iter().all(Vec::is_empty) {
+                            diagnostics.push(Diagnostic::warning(&path, "the group has no content and is left out"));
+                        }
+                        for (k, subgroup) in subgroups.iter().enumerate() {
+                            for (l, elem) in subgroup.iter().enumerate() {
+                                if let SubgroupElement::Image_(image) = elem {
+                                    validate_image(image, &format!("{}[{}][{}]", path, k, l), &mut diagnostics);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(header) = &self.header {
+            if header.id.is_empty() {
+                diagnostics.push(Diagnostic::error("header.id", "the header id is empty"));
+            }
+            if header.title.is_empty() {
+                diagnostics.push(Diagnostic::warning("header.title", "the header title is empty"));
+            }
+            if header.activation_type == ActivationType::Background {
+                diagnostics.push(Diagnostic::error("header.activation_type", "a header can only activate in the foreground or by protocol"));
+            }
+        }
+        if let Some((loop_, _, src)) = &self.audio {
+            if *loop_ && !src.is_looping() {
+                diagnostics.push(Diagnostic::warning("audio", format!("{:?} cannot be looped", src)));
+            }
+        }
+        self.validate_actions(&mut diagnostics);
+        diagnostics.extend(self.sanitize_report());
+        let size = self.payload_size();
+        if !size.fits(MAX_PAYLOAD_BYTES) {
+            diagnostics.push(Diagnostic::error("/", format!("the payload of {} is over the limit of {} bytes", size, MAX_PAYLOAD_BYTES)));
+        }
+        diagnostics
+    }
+
+    fn validate_actions(&self, diagnostics: &mut Vec<Diagnostic>) {
+        let (actions, inputs) = visible_actions(&self.actions);
+        let total_actions = self.actions.iter().filter(|elem| matches!(elem, ActionsElem::Action_(_))).count();
+        let total_inputs = self.actions.len() - total_actions;
+        if total_actions > actions.len() {
+            diagnostics.push(Diagnostic::warning("actions", format!("only 5 of {} actions are shown", total_actions)));
+        }
+        if total_inputs > inputs.len() {
+            diagnostics.push(Diagnostic::warning("actions", format!("only 5 of {} inputs are shown", total_inputs)));
+        }
+        let mut input_ids = HashSet::new();
+        for (i, elem) in self.actions.iter().enumerate() {
+            let path = format!("actions[{}]", i);
+            match elem {
+                ActionsElem::Input_(input) => {
+                    if input.id.is_empty() {
+                        diagnostics.push(Diagnostic::error(&path, "the input id is empty"));
+                    } else if !input_ids.insert(input.id.as_str()) {
+                        diagnostics.push(Diagnostic::error(&path, format!("the input id {:?} is used twice", input.id)));
+                    }
+                    if let InputType::Selection(default_id, selections) = &input.type_ {
+                        if selections.is_empty() {
+                            diagnostics.push(Diagnostic::error(&path, "a selection input needs at least one selection"));
+                        }
+                        if selections.len() > 5 {
+                            diagnostics.push(Diagnostic::warning(&path, format!("only 5 of {} selections are shown", selections.len())));
+                        }
+                        if let Some(id) = default_id {
+                            if !selections.iter().any(|(selection_id, _)| selection_id == id) {
+                                diagnostics.push(Diagnostic::error(&path, format!("the default selection {:?} does not exist", id)));
+                            }
+                        }
+                    }
+                }
+                ActionsElem::Action_(action) => {
+                    if action.content.is_empty() && action.image_uri.is_none() {
+                        diagnostics.push(Diagnostic::error(&path, "the action has neither content nor an image"));
+                    }
+                    match action.hint_button_style.as_deref() {
+                        None => {}
+                        Some("Success" | "Critical") if !self.use_btn_style => {
+                            diagnostics.push(Diagnostic::warning(&path, "hint_button_style is ignored unless use_btn_style is set"));
+                        }
+                        Some("Success" | "Critical") => {}
+                        Some(other) => {
+                            diagnostics.push(Diagnostic::warning(&path, format!("unknown button style {:?}, expected \"Success\" or \"Critical\"", other)));
+                        }
+                    }
+                }
+            }
+        }
+        for (i, elem) in self.actions.iter().enumerate() {
+            if let ActionsElem::Action_(Action { hint_input_id: Some(id), .. }) = elem {
+                if !input_ids.contains(id.as_str()) {
+                    diagnostics.push(Diagnostic::error(format!("actions[{}]", i), format!("hint_input_id refers to the unknown input {:?}", id)));
+                }
+            }
+        }
+        let with_image = actions.iter().filter(|action| action.image_uri.is_some()).count();
+        if with_image > 0 && with_image < actions.len() {
+            diagnostics.push(Diagnostic::warning("actions", "either all buttons or none of them should have an image_uri"));
+        }
+    }
+}
+
+fn validate_image(image: &Image, path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if image.src.is_empty() {
+        diagnostics.push(Diagnostic::error(path, "src is empty"));
+    }
+}
+
+/// The elements of the toast content schema with the parents they may appear in and their attributes.
+const SCHEMA: &[(&str, &[&str], &[&str])] = &[
+    ("toast", &[], &["launch", "duration", "displayTimestamp", "scenario", "useButtonStyle", "activationType",
+        "protocolActivationTargetApplicationPfn", "afterActivationBehavior"]),
+    ("visual", &["toast"], &["version", "lang", "baseUri", "branding", "addImageQuery"]),
+    ("binding", &["visual"], &["template", "fallback", "lang", "baseUri", "branding", "addImageQuery"]),
+    ("text", &["binding", "subgroup"], &["id", "lang", "placement", "hint-style", "hint-wrap", "hint-maxLines",
+        "hint-minLines", "hint-align", "hint-callScenarioCenterAlign"]),
+    ("image", &["binding", "subgroup"], &["id", "src", "alt", "addImageQuery", "placement", "hint-crop",
+        "hint-removeMargin", "hint-align"]),
+    ("group", &["binding"], &[]),
+    ("subgroup", &["group"], &["hint-weight", "hint-textStacking"]),
+    ("progress", &["binding"], &["title", "value", "valueStringOverride", "status"]),
+    ("audio", &["toast"], &["src", "loop", "silent"]),
+    ("actions", &["toast"], &["hint-systemCommands"]),
+    ("input", &["actions"], &["id", "type", "title", "placeHolderContent", "defaultInput"]),
+    ("selection", &["input"], &["id", "content"]),
+    ("action", &["actions"], &["content", "arguments", "activationType", "afterActivationBehavior",
+        "protocolActivationTargetApplicationPfn", "placement", "imageUri", "hint-inputId", "hint-buttonStyle",
+        "hint-toolTip"]),
+    ("header", &["toast"], &["id", "title", "arguments", "activationType"]),
+];
+
+/// Checks an XML payload against the toast content schema, then checks the toast it describes
+/// with [`Toast::validate`]. Characters XML does not allow are reported and sanitized first, as
+/// `Toast::show_with_xml` does.
+pub fn validate_xml(xml: &str) -> Vec<Diagnostic> {
+    let (xml, mut diagnostics) = sanitize_xml(xml, policy());
+    let xml = xml.as_str();
+    let doc = match roxmltree::Document::parse(xml) {
+        Ok(doc) => doc,
+        Err(e) => {
+            diagnostics.push(Diagnostic::error("/", e.to_string()));
+            return diagnostics;
+        }
+    };
+    validate_node(doc.root_element(), "", &mut diagnostics);
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return diagnostics;
+    }
+    match Toast::from_xml(Toast::POWERSHELL_APP_ID, xml) {
+        Ok(toast) => diagnostics.extend(toast.validate()),
+        Err(e) => diagnostics.push(Diagnostic::error("/", e.to_string())),
+    }
+    diagnostics
+}
+
+fn validate_node(node: roxmltree::Node, parent_path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let name = node.tag_name().name();
+    let parent = node.parent_element().map(|parent| parent.tag_name().name()).unwrap_or("");
+    // `prev_siblings` starts with the node itself, which makes the index 1-based.
+    let index = node.prev_siblings().filter(|sibling| sibling.has_tag_name(name)).count();
+    let path = format!("{}/{}[{}]", parent_path, name, index);
+    let Some((_, parents, attrs)) = SCHEMA.iter().find(|(elem, _, _)| *elem == name) else {
+        diagnostics.push(Diagnostic::error(path, format!("<{}> is not part of the toast schema", name)));
+        return;
+    };
+    if parents.is_empty() != parent.is_empty() || (!parent.is_empty() && !parents.contains(&parent)) {
+        let expected = if parents.is_empty() { "the root".to_string() } else { format!("<{}>", parents.join("> or <")) };
+        diagnostics.push(Diagnostic::error(&path, format!("<{}> must be inside {}", name, expected)));
+    }
+    for attr in node.attributes() {
+        if !attrs.contains(&attr.name()) {
+            diagnostics.push(Diagnostic::warning(&path, format!("unknown attribute {:?} is ignored", attr.name())));
+        }
+    }
+    for child in node.children() {
+        if child.is_element() {
+            validate_node(child, &path, diagnostics);
+        } else if child.is_text() && !child.text().unwrap_or_default().trim().is_empty() && name != "text" {
+            diagnostics.push(Diagnostic::warning(&path, format!("text inside <{}> is ignored", name)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_toast;
+
+    #[test]
+    fn test_validate() {
+        let mut toast = sample_toast();
+        toast.use_btn_style = false;
+        let diagnostics = toast.validate();
+        assert_eq!(diagnostics, vec![Diagnostic::warning("actions[0]", "hint_button_style is ignored unless use_btn_style is set")]);
+
+        let diagnostics = validate_xml(r#"<toast><visual><binding template="ToastGeneric"><text>hi</text><button/></binding></visual>
+            <actions><action content="Go" arguments="go" hint-inputId="reply" colour="red"/></actions></toast>"#);
+        assert_eq!(diagnostics, vec![
+            Diagnostic::error("/toast[1]/visual[1]/binding[1]/button[1]", "<button> is not part of the toast schema"),
+            Diagnostic::warning("/toast[1]/actions[1]/action[1]", "unknown attribute \"colour\" is ignored"),
+        ]);
+        let diagnostics = validate_xml(r#"<toast><visual><binding template="ToastGeneric"><text>hi</text></binding></visual>
+            <actions><action content="Go" arguments="go" hint-inputId="reply"/></actions></toast>"#);
+        assert_eq!(diagnostics, vec![Diagnostic::error("actions[0]", "hint_input_id refers to the unknown input \"reply\"")]);
+    }
+}
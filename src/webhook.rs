@@ -0,0 +1,70 @@
+//! Mappers that turn the webhook payloads of other tools into toasts.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::notification::Toast;
+
+pub mod alertmanager;
+pub mod github;
+
+pub use alertmanager::AlertmanagerMapper;
+pub use github::GitHubMapper;
+
+/// An error while mapping a payload, such as a missing field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebhookError {
+    pub message: String,
+}
+
+impl WebhookError {
+    pub fn new(message: impl Into<String>) -> WebhookError {
+        WebhookError { message: message.into() }
+    }
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+/// How the sender of a webhook proves it may post, besides the bearer token every route takes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WebhookAuth {
+    /// Only the `Authorization: Bearer` header.
+    #[default]
+    Bearer,
+    /// An `X-Hub-Signature-256` header with the HMAC-SHA256 of the body, keyed with the token of
+    /// the server, which is set as the secret of the webhook on GitHub.
+    HubSignature256,
+}
+
+/// Turns a webhook payload into the toasts to show.
+///
+/// Closures taking the payload can be used as mappers too.
+pub trait WebhookMapper: Send + Sync {
+    /// The toasts for the payload. An empty list means the payload is of no interest.
+    fn map(&self, payload: &Value) -> Result<Vec<Toast>, WebhookError>;
+
+    fn auth(&self) -> WebhookAuth {
+        WebhookAuth::Bearer
+    }
+}
+
+impl<F> WebhookMapper for F
+    where F: Fn(&Value) -> Result<Vec<Toast>, WebhookError> + Send + Sync {
+    fn map(&self, payload: &Value) -> Result<Vec<Toast>, WebhookError> {
+        self(payload)
+    }
+}
+
+/// The string at a path of object keys, such as `["workflow_run", "name"]`.
+pub(crate) fn str_at<'a>(payload: &'a Value, path: &[&str]) -> Result<&'a str, WebhookError> {
+    path.iter().try_fold(payload, |value, key| value.get(key))
+        .and_then(Value::as_str)
+        .ok_or_else(|| WebhookError::new(format!("the payload has no string at {}", path.join("."))))
+}
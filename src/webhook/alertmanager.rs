@@ -0,0 +1,173 @@
+//! Toasts for Prometheus Alertmanager notifications.
+
+use serde_json::{Map, Value};
+
+use super::{str_at, WebhookError, WebhookMapper};
+use crate::notification::{self, *};
+
+/// The group of alert toasts, which together with the fingerprint of an alert as the tag lets
+/// the resolved toast take the place of the firing one.
+pub const GROUP: &str = "alertmanager";
+
+/// Maps every alert of a webhook notification to a toast.
+///
+/// The `severity` label sets the scenario: `critical` alerts are urgent, and `page` alerts ring
+/// as an alarm until dismissed. The labels are listed in a table, and firing alerts get a
+/// "Silence" button opening the silence form of Alertmanager, plus an "Open runbook" button when
+/// the alert has a `runbook_url` annotation.
+#[derive(Clone, Debug, Default)]
+pub struct AlertmanagerMapper {
+    /// The app id the toasts are shown as. Left empty, they are shown as Windows PowerShell.
+    pub app_id: String,
+}
+
+impl WebhookMapper for AlertmanagerMapper {
+    fn map(&self, payload: &Value) -> Result<Vec<Toast>, WebhookError> {
+        let alerts = payload.get("alerts").and_then(Value::as_array)
+            .ok_or_else(|| WebhookError::new("the payload has no alerts"))?;
+        let external_url = str_at(payload, &["externalURL"]).unwrap_or_default();
+        alerts.iter().map(|alert| self.alert_toast(alert, external_url)).collect()
+    }
+}
+
+impl AlertmanagerMapper {
+    fn alert_toast(&self, alert: &Value, external_url: &str) -> Result<Toast, WebhookError> {
+        let empty = Map::new();
+        let labels = alert.get("labels").and_then(Value::as_object).unwrap_or(&empty);
+        let label = |name: &str| labels.get(name).and_then(Value::as_str);
+        let annotation = |name: &str| str_at(alert, &["annotations", name]).ok();
+        let name = label("alertname").ok_or_else(|| WebhookError::new("an alert has no alertname label"))?;
+        let firing = str_at(alert, &["status"])? == "firing";
+
+        let (scenario, audio) = match (firing, label("severity")) {
+            (true, Some("critical")) => (Scenario::Urgent, None),
+            (true, Some("page")) => (Scenario::Alarm, Some((true, false, SoundSrc::Alarm))),
+            _ => (Scenario::Default, None),
+        };
+        let mut elems: Vec<BindingInnerElement> = annotation("summary").or_else(|| annotation("description"))
+            .map(|text| BindingInnerElement::Text(text.to_string()))
+            .into_iter()
+            .collect();
+        let (names, values): (Vec<_>, Vec<_>) = labels.iter()
+            .filter(|(name, _)| name.as_str() != "alertname")
+            .map(|(name, value)| {
+                let value = value.as_str().unwrap_or_default().to_string();
+                (label_text(name.clone(), TextStyle::CaptionSubtle), label_text(value, TextStyle::Caption))
+            })
+            .unzip();
+        if !names.is_empty() {
+            elems.push(BindingInnerElement::Group(vec![names, values]));
+        }
+
+        let mut actions = Vec::new();
+        if firing {
+            if !external_url.is_empty() {
+                actions.push(protocol_action("Silence", silence_url(external_url, labels), Some("Critical")));
+            }
+            if let Some(runbook) = annotation("runbook_url") {
+                actions.push(protocol_action("Open runbook", runbook.to_string(), None));
+            }
+        }
+        Ok(Toast {
+            app_id: self.app_id.clone(),
+            binding_elems: vec![BindingElem {
+                title: (format!("{}: {}", if firing { "Firing" } else { "Resolved" }, name), Some(2)),
+                elems,
+                ..BindingElem::default()
+            }],
+            duration: if firing { notification::Duration::Long } else { notification::Duration::Default },
+            scenario,
+            audio,
+            actions,
+            use_btn_style: true,
+            tag: str_at(alert, &["fingerprint"]).ok().map(str::to_string),
+            group: Some(GROUP.to_string()),
+            ..Toast::default()
+        })
+    }
+}
+
+fn label_text(text: String, hint_style: TextStyle) -> SubgroupElement {
+    SubgroupElement::Text_(InnerText { text, hint_style, ..InnerText::default() })
+}
+
+fn protocol_action(content: &str, arguments: String, style: Option<&str>) -> ActionsElem {
+    ActionsElem::Action_(Action {
+        content: content.to_string(),
+        arguments,
+        activation_type: ActivationType::Protocol,
+        hint_button_style: style.map(str::to_string),
+        ..Action::default()
+    })
+}
+
+/// The silence form of Alertmanager, filled in with a matcher for every label of the alert.
+fn silence_url(external_url: &str, labels: &Map<String, Value>) -> String {
+    let matchers: Vec<String> = labels.iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, matcher_escape(value.as_str().unwrap_or_default())))
+        .collect();
+    format!("{}/#/silences/new?filter={}", external_url.trim_end_matches('/'), percent_encode(&format!("{{{}}}", matchers.join(","))))
+}
+
+/// Escapes a label value for a double-quoted matcher, as Alertmanager parses them.
+fn matcher_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silence_url_escapes_label_values() {
+        let labels = serde_json::json!({ "job": "say \"hi\"\\now\nthen", "zone": "ü" });
+        let url = silence_url("http://alertmanager/", labels.as_object().unwrap());
+        let filter = url.strip_prefix("http://alertmanager/#/silences/new?filter=").unwrap();
+        assert_eq!(filter, percent_encode(r#"{job="say \"hi\"\\now\nthen",zone="ü"}"#));
+    }
+
+    #[test]
+    fn test_alertmanager_mapper() {
+        let mut payload = serde_json::json!({
+            "status": "firing",
+            "externalURL": "http://alertmanager:9093",
+            "alerts": [{
+                "status": "firing",
+                "fingerprint": "5ef77f1f8a3ecfc0",
+                "labels": {"alertname": "DiskFull", "instance": "db-1", "severity": "page"},
+                "annotations": {"summary": "Disk is 98% full", "runbook_url": "https://runbooks.example.com/disk"},
+            }],
+        });
+        let mapper = AlertmanagerMapper::default();
+        let firing = mapper.map(&payload).unwrap().remove(0);
+        assert_eq!(firing.scenario, Scenario::Alarm);
+        assert_eq!(firing.audio, Some((true, false, SoundSrc::Alarm)));
+        assert_eq!((firing.tag.as_deref(), firing.group.as_deref()), (Some("5ef77f1f8a3ecfc0"), Some("alertmanager")));
+        let xml = firing.to_xml();
+        assert!(xml.contains("<subgroup><text hint-style=\"captionSubtle\">instance</text><text hint-style=\"captionSubtle\">severity</text></subgroup>"));
+        assert!(xml.contains("arguments=\"http://alertmanager:9093/#/silences/new?filter=%7Balertname%3D%22DiskFull%22%2Cinstance%3D%22db-1%22%2Cseverity%3D%22page%22%7D\""));
+        assert!(xml.contains("<action content=\"Open runbook\" arguments=\"https://runbooks.example.com/disk\" activationType=\"protocol\"/>"));
+
+        payload["alerts"][0]["status"] = "resolved".into();
+        let resolved = mapper.map(&payload).unwrap().remove(0);
+        assert_eq!(resolved.binding_elems[0].title.0, "Resolved: DiskFull");
+        assert_eq!((resolved.scenario, resolved.audio, resolved.actions.len()), (Scenario::Default, None, 0));
+        assert_eq!(resolved.tag, firing.tag);
+    }
+}
@@ -0,0 +1,64 @@
+//! Toasts for GitHub Actions workflow runs.
+
+use serde_json::Value;
+
+use super::{str_at, WebhookAuth, WebhookError, WebhookMapper};
+use crate::notification::{self, *};
+
+/// Maps `workflow_run` events to a toast once the run completes. Other events are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct GitHubMapper {
+    /// The app id the toasts are shown as. Left empty, they are shown as Windows PowerShell.
+    pub app_id: String,
+}
+
+/// GitHub cannot send a bearer token, so deliveries are signed with the token as the secret.
+impl WebhookMapper for GitHubMapper {
+    fn auth(&self) -> WebhookAuth {
+        WebhookAuth::HubSignature256
+    }
+
+    fn map(&self, payload: &Value) -> Result<Vec<Toast>, WebhookError> {
+        if payload.get("workflow_run").is_none() || str_at(payload, &["action"])? != "completed" {
+            return Ok(Vec::new());
+        }
+        let name = str_at(payload, &["workflow_run", "name"])?;
+        let url = str_at(payload, &["workflow_run", "html_url"])?;
+        let conclusion = str_at(payload, &["workflow_run", "conclusion"]).unwrap_or("unknown");
+        let run = payload["workflow_run"]["run_number"].as_u64()
+            .map(|number| format!("{} #{}", name, number))
+            .unwrap_or_else(|| name.to_string());
+        let success = conclusion == "success";
+        let title = match conclusion {
+            "success" => format!("✔ {} succeeded", run),
+            "failure" => format!("✘ {} failed", run),
+            other => format!("{} finished: {}", run, other.replace('_', " ")),
+        };
+        let mut elems = Vec::new();
+        if let Ok(repository) = str_at(payload, &["repository", "full_name"]) {
+            elems.push(match str_at(payload, &["workflow_run", "head_branch"]) {
+                Ok(branch) => format!("{} on {}", repository, branch),
+                Err(_) => repository.to_string(),
+            });
+        }
+        if let Ok(message) = str_at(payload, &["workflow_run", "head_commit", "message"]) {
+            elems.push(message.lines().next().unwrap_or_default().to_string());
+        }
+        Ok(vec![Toast {
+            app_id: self.app_id.clone(),
+            binding_elems: vec![BindingElem {
+                title: (title, Some(2)),
+                elems: elems.into_iter().map(BindingInnerElement::Text).collect(),
+                ..BindingElem::default()
+            }],
+            duration: if success { notification::Duration::Default } else { notification::Duration::Long },
+            actions: vec![ActionsElem::Action_(Action {
+                content: "Open run".to_string(),
+                arguments: url.to_string(),
+                activation_type: ActivationType::Protocol,
+                ..Action::default()
+            })],
+            ..Toast::default()
+        }])
+    }
+}
@@ -0,0 +1,843 @@
+//! Conversion between a [`Toast`] and its XML payload, independent of the Windows XML DOM.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::logging::{self, Stage};
+use crate::notification::*;
+use crate::sanitize::{self, SanitizePolicy};
+
+/// An error while reading a toast from XML.
+#[derive(Clone, Debug, PartialEq)]
+pub struct XmlError {
+    pub message: String,
+    /// The (row, column) of the offending node, both starting at 1.
+    pub position: Option<(u32, u32)>,
+}
+
+impl XmlError {
+    fn new(message: impl Into<String>) -> XmlError {
+        XmlError { message: message.into(), position: None }
+    }
+
+    fn at(node: roxmltree::Node, message: impl Into<String>) -> XmlError {
+        let pos = node.document().text_pos_at(node.range().start);
+        XmlError { message: message.into(), position: Some((pos.row, pos.col)) }
+    }
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((row, col)) => write!(f, "{}:{}: {}", row, col, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for XmlError {}
+
+/// A node of the payload as it is written out.
+pub(crate) struct Element {
+    pub(crate) name: &'static str,
+    pub(crate) attrs: Vec<(&'static str, String)>,
+    pub(crate) text: Option<String>,
+    pub(crate) children: Vec<Element>,
+}
+
+impl Element {
+    pub(crate) fn new(name: &'static str) -> Element {
+        Element { name, attrs: Vec::new(), text: None, children: Vec::new() }
+    }
+
+    fn attr(&mut self, name: &'static str, value: impl Into<String>) -> &mut Element {
+        self.attrs.push((name, value.into()));
+        self
+    }
+
+    /// Passes the content of every `<text>` element through the redactor of [`logging`].
+    fn redact_texts(&mut self) {
+        if self.name == "text" {
+            if let Some(text) = &mut self.text {
+                *text = logging::redact(text);
+            }
+        }
+        for child in &mut self.children {
+            child.redact_texts();
+        }
+    }
+
+    pub(crate) fn write(&self, out: &mut String, policy: SanitizePolicy) {
+        out.push('<');
+        out.push_str(self.name);
+        for (name, value) in &self.attrs {
+            out.push(' ');
+            out.push_str(name);
+            out.push_str("=\"");
+            escape_into(value, true, policy, out);
+            out.push('"');
+        }
+        if self.text.is_none() && self.children.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        if let Some(text) = &self.text {
+            escape_into(text, false, policy, out);
+        }
+        for child in &self.children {
+            child.write(out, policy);
+        }
+        out.push_str("</");
+        out.push_str(self.name);
+        out.push('>');
+    }
+}
+
+/// Escapes markup, and applies the policy to characters XML does not allow.
+fn escape_into(text: &str, attribute: bool, policy: SanitizePolicy, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if attribute => out.push_str("&quot;"),
+            '\n' if attribute => out.push_str("&#10;"),
+            '\t' if attribute => out.push_str("&#9;"),
+            // A parser reads a carriage return as a line feed, unless it is a reference.
+            '\r' => out.push_str("&#13;"),
+            c if !sanitize::is_xml_char(c as u32) => policy.apply(c as u32, out),
+            _ => out.push(c),
+        }
+    }
+}
+
+impl Toast {
+    /// Serializes the toast into the XML payload understood by `ToastNotification`.
+    ///
+    /// Like Windows, at most five actions, five inputs and five selections per input are kept.
+    /// Characters XML does not allow are handled by the policy set with [`sanitize::set_policy`].
+    pub fn to_xml(&self) -> String {
+        self.to_xml_with(sanitize::policy())
+    }
+
+    /// Like [`Toast::to_xml`], with the characters XML does not allow handled by `policy`.
+    pub fn to_xml_with(&self, policy: SanitizePolicy) -> String {
+        let _timed = logging::timed(Stage::Build, &self.app_id);
+        let mut toast_elem = self.to_element();
+        let mut out = String::new();
+        toast_elem.write(&mut out, policy);
+        if logging::payload_enabled() {
+            if logging::has_redactor() {
+                toast_elem.redact_texts();
+                let mut redacted = String::new();
+                toast_elem.write(&mut redacted, policy);
+                logging::payload(&self.app_id, &redacted);
+            } else {
+                logging::payload(&self.app_id, &out);
+            }
+        }
+        out
+    }
+
+    pub(crate) fn to_element(&self) -> Element {
+        let mut toast_elem = Element::new("toast");
+        if self.use_btn_style {
+            toast_elem.attr("useButtonStyle", "true");
+        }
+        if let Some(timestamp) = &self.display_timestamp {
+            toast_elem.attr("displayTimestamp", timestamp);
+        }
+        if let Some(duration) = self.duration.attr() {
+            toast_elem.attr("duration", duration);
+        }
+        if let Some(scenario) = self.scenario.attr() {
+            toast_elem.attr("scenario", scenario);
+        }
+        if let Some(header) = &self.header {
+            let mut header_elem = Element::new("header");
+            header_elem.attr("id", &header.id);
+            header_elem.attr("title", &header.title);
+            header_elem.attr("arguments", &header.arguments);
+            header_elem.attr("activationType", header.activation_type.attr());
+            toast_elem.children.push(header_elem);
+        }
+        let mut visual_elem = Element::new("visual");
+        for binding in &self.binding_elems {
+            visual_elem.children.push(binding.to_element());
+        }
+        toast_elem.children.push(visual_elem);
+        if let Some(audio) = &self.audio {
+            toast_elem.children.push(audio_element(audio));
+        }
+        if let Some(actions_elem) = actions_element(&self.actions) {
+            toast_elem.children.push(actions_elem);
+        }
+        toast_elem
+    }
+}
+
+impl BindingElem {
+    fn to_element(&self) -> Element {
+        let mut binding_elem = Element::new("binding");
+        binding_elem.attr("template", self.template.attr());
+        if let Some(lang) = &self.lang {
+            binding_elem.attr("lang", lang);
+        }
+        if let Some(texts) = self.template.text_count() {
+            return self.legacy_element(binding_elem, texts);
+        }
+        if let Some(image) = &self.icon {
+            binding_elem.children.push(image_element(image, Some("appLogoOverride"), false));
+        }
+        if let Some(image) = &self.hero {
+            binding_elem.children.push(image_element(image, Some("hero"), false));
+        }
+        let mut title_elem = Element::new("text");
+        if let Some(value) = self.title.1 {
+            title_elem.attr("hint-maxLines", value.to_string());
+        }
+        title_elem.text = Some(self.title.0.clone());
+        binding_elem.children.push(title_elem);
+        for elem in &self.elems {
+            match elem {
+                BindingInnerElement::Text(text) => {
+                    let mut text_elem = Element::new("text");
+                    text_elem.text = Some(text.clone());
+                    binding_elem.children.push(text_elem);
+                }
+                BindingInnerElement::Group(subgroups) => {
+                    if let Some(group_elem) = group_element(subgroups) {
+                        binding_elem.children.push(group_elem);
+                    }
+                }
+                BindingInnerElement::Image(image) => {
+                    binding_elem.children.push(image_element(image, None, true));
+                }
+            }
+        }
+        if let Some(attribution) = &self.attribution {
+            let mut text_elem = Element::new("text");
+            text_elem.attr("placement", "attribution");
+            text_elem.text = Some(attribution.clone());
+            binding_elem.children.push(text_elem);
+        }
+        binding_elem
+    }
+
+    /// Writes what the legacy template holds, the numbered texts and image.
+    fn legacy_element(&self, mut binding_elem: Element, texts: usize) -> Element {
+        if let Some(image) = self.icon.as_ref().filter(|_| self.template.has_image()) {
+            let mut image_elem = Element::new("image");
+            image_elem.attr("id", "1").attr("src", &image.src);
+            if !image.alt.is_empty() {
+                image_elem.attr("alt", &image.alt);
+            }
+            binding_elem.children.push(image_elem);
+        }
+        let body = self.elems.iter().filter_map(|elem| match elem {
+            BindingInnerElement::Text(text) => Some(text),
+            _ => None,
+        });
+        for (i, text) in std::iter::once(&self.title.0).chain(body).take(texts).enumerate() {
+            let mut text_elem = Element::new("text");
+            text_elem.attr("id", (i + 1).to_string());
+            text_elem.text = Some(text.clone());
+            binding_elem.children.push(text_elem);
+        }
+        binding_elem
+    }
+}
+
+fn image_element(image: &Image, placement: Option<&'static str>, in_group: bool) -> Element {
+    let mut image_elem = Element::new("image");
+    image_elem.attr("src", &image.src);
+    if let Some(placement) = placement {
+        image_elem.attr("placement", placement);
+    }
+    if !image.alt.is_empty() {
+        image_elem.attr("alt", &image.alt);
+    }
+    if matches!(image.hint_crop, HintCrop::Circle) {
+        image_elem.attr("hint-crop", "circle");
+    }
+    if in_group {
+        if let Some(align) = image.hint_align.attr() {
+            image_elem.attr("hint-align", align);
+        }
+    }
+    image_elem
+}
+
+fn group_element(subgroups: &[Vec<SubgroupElement>]) -> Option<Element> {
+    if subgroups.is_empty() {
+        return None;
+    }
+    let mut group_elem = Element::new("group");
+    for subgroup in subgroups.iter().filter(|subgroup| !subgroup.is_empty()) {
+        let mut subgroup_elem = Element::new("subgroup");
+        for elem in subgroup {
+            match elem {
+                SubgroupElement::Text_(text) => subgroup_elem.children.push(text_element(text)),
+                SubgroupElement::Image_(image) => subgroup_elem.children.push(image_element(image, None, true)),
+            }
+        }
+        group_elem.children.push(subgroup_elem);
+    }
+    Some(group_elem)
+}
+
+fn text_element(text: &InnerText) -> Element {
+    let mut text_elem = Element::new("text");
+    if let Some(value) = text.hint_max_lines {
+        text_elem.attr("hint-maxLines", value.to_string());
+    }
+    if let Some(value) = text.hint_min_lines {
+        text_elem.attr("hint-minLines", value.to_string());
+    }
+    if text.hint_warp == Some(true) {
+        text_elem.attr("hint-wrap", "true");
+    }
+    if let Some(style) = text.hint_style.attr() {
+        text_elem.attr("hint-style", style);
+    }
+    if let Some(align) = text.hint_align.attr() {
+        text_elem.attr("hint-align", align);
+    }
+    text_elem.text = Some(text.text.clone());
+    text_elem
+}
+
+fn audio_element((loop_, silent, src): &(bool, bool, SoundSrc)) -> Element {
+    let mut audio_elem = Element::new("audio");
+    audio_elem.attr("loop", (*loop_ && src.is_looping()).to_string());
+    audio_elem.attr("silent", silent.to_string());
+    audio_elem.attr("src", src.attr());
+    audio_elem
+}
+
+fn actions_element(actions_elems: &[ActionsElem]) -> Option<Element> {
+    if actions_elems.is_empty() {
+        return None;
+    }
+    let mut actions_elem = Element::new("actions");
+    let (mut action_count, mut input_count) = (0, 0);
+    for elem in actions_elems {
+        match elem {
+            ActionsElem::Action_(action) if action_count < 5 => {
+                let mut action_elem = Element::new("action");
+                action_elem.attr("content", &action.content);
+                action_elem.attr("arguments", &action.arguments);
+                action_elem.attr("activationType", action.activation_type.attr());
+                if matches!(action.placement, ActionPlacement::ContextMenu) {
+                    action_elem.attr("placement", "contextMenu");
+                }
+                if let Some(value) = &action.image_uri {
+                    action_elem.attr("imageUri", value);
+                }
+                if let Some(value) = &action.hint_input_id {
+                    action_elem.attr("hint-inputId", value);
+                }
+                if let Some(value @ ("Success" | "Critical")) = action.hint_button_style.as_deref() {
+                    action_elem.attr("hint-buttonStyle", value);
+                }
+                if let Some(value) = &action.hint_tool_tip {
+                    action_elem.attr("hint-toolTip", value);
+                }
+                actions_elem.children.push(action_elem);
+                action_count += 1;
+            }
+            ActionsElem::Input_(input) if input_count < 5 => {
+                let mut input_elem = Element::new("input");
+                input_elem.attr("id", &input.id);
+                match &input.type_ {
+                    InputType::Text(default) => {
+                        input_elem.attr("type", "text");
+                        if let Some(content) = &input.place_holder_content {
+                            input_elem.attr("placeHolderContent", content);
+                        }
+                        if let Some(value) = default {
+                            input_elem.attr("defaultInput", value);
+                        }
+                    }
+                    InputType::Selection(default_id, selections) => {
+                        input_elem.attr("type", "selection");
+                        if let Some(value) = default_id {
+                            input_elem.attr("defaultInput", value);
+                        }
+                        for (id, title) in selections.iter().take(5) {
+                            let mut selection_elem = Element::new("selection");
+                            selection_elem.attr("id", id);
+                            selection_elem.attr("content", title);
+                            input_elem.children.push(selection_elem);
+                        }
+                    }
+                }
+                if let Some(title) = &input.title {
+                    input_elem.attr("title", title);
+                }
+                actions_elem.children.push(input_elem);
+                input_count += 1;
+            }
+            _ => {}
+        }
+    }
+    Some(actions_elem)
+}
+
+impl Toast {
+    /// Reads a toast from an XML payload, the inverse of [`Toast::to_xml`].
+    ///
+    /// The payload does not carry the app id, so it has to be given. Elements and attributes
+    /// the model cannot represent are skipped; use [`validate_xml`](crate::validate::validate_xml)
+    /// to find them.
+    pub fn from_xml(app_id: &str, xml: &str) -> Result<Toast, XmlError> {
+        let doc = roxmltree::Document::parse(xml).map_err(|e| {
+            let pos = e.pos();
+            XmlError { message: e.to_string(), position: Some((pos.row, pos.col)) }
+        })?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "toast" {
+            return Err(XmlError::at(root, format!("expected <toast>, found <{}>", root.tag_name().name())));
+        }
+        let mut toast = Toast {
+            app_id: app_id.to_string(),
+            use_btn_style: root.attribute("useButtonStyle") == Some("true"),
+            display_timestamp: root.attribute("displayTimestamp").map(str::to_string),
+            duration: parse_attr(root, "duration")?.unwrap_or_default(),
+            scenario: parse_attr(root, "scenario")?.unwrap_or_default(),
+            ..Toast::default()
+        };
+        for child in root.children().filter(roxmltree::Node::is_element) {
+            match child.tag_name().name() {
+                "header" => {
+                    toast.header = Some(Header {
+                        id: required(child, "id")?.to_string(),
+                        title: required(child, "title")?.to_string(),
+                        arguments: required(child, "arguments")?.to_string(),
+                        activation_type: parse_attr(child, "activationType")?.unwrap_or_default(),
+                    });
+                }
+                "visual" => {
+                    for binding in child.children().filter(|node| node.has_tag_name("binding")) {
+                        toast.binding_elems.push(parse_binding(binding)?);
+                    }
+                }
+                "audio" => {
+                    let src = match child.attribute("src") {
+                        Some(src) => src.parse().map_err(|e: XmlError| XmlError::at(child, e.message))?,
+                        None => SoundSrc::Default,
+                    };
+                    toast.audio = Some((child.attribute("loop") == Some("true"), child.attribute("silent") == Some("true"), src));
+                }
+                "actions" => {
+                    for elem in child.children().filter(roxmltree::Node::is_element) {
+                        match elem.tag_name().name() {
+                            "action" => toast.actions.push(ActionsElem::Action_(parse_action(elem)?)),
+                            "input" => toast.actions.push(ActionsElem::Input_(parse_input(elem)?)),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(toast)
+    }
+}
+
+fn parse_attr<T: FromStr<Err = XmlError>>(node: roxmltree::Node, name: &str) -> Result<Option<T>, XmlError> {
+    node.attribute(name)
+        .map(|value| value.parse().map_err(|e: XmlError| XmlError::at(node, e.message)))
+        .transpose()
+}
+
+fn parse_u32(node: roxmltree::Node, name: &str) -> Result<Option<u32>, XmlError> {
+    node.attribute(name)
+        .map(|value| value.parse().map_err(|_| XmlError::at(node, format!("{} must be a number, found {:?}", name, value))))
+        .transpose()
+}
+
+fn required<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Result<&'a str, XmlError> {
+    node.attribute(name)
+        .ok_or_else(|| XmlError::at(node, format!("<{}> is missing the {} attribute", node.tag_name().name(), name)))
+}
+
+fn parse_binding(node: roxmltree::Node) -> Result<BindingElem, XmlError> {
+    let template: BindingTemplate = required(node, "template")?.parse()
+        .map_err(|_| XmlError::at(node, format!("unsupported binding template {:?}", node.attribute("template").unwrap_or_default())))?;
+    let mut binding = BindingElem { lang: node.attribute("lang").map(str::to_string), template, ..BindingElem::default() };
+    if template.is_legacy() {
+        return parse_legacy_binding(node, binding);
+    }
+    let mut has_title = false;
+    for child in node.children().filter(roxmltree::Node::is_element) {
+        match child.tag_name().name() {
+            "text" => {
+                let text = child.text().unwrap_or_default().to_string();
+                if child.attribute("placement") == Some("attribution") {
+                    binding.attribution = Some(text);
+                } else if has_title {
+                    binding.elems.push(BindingInnerElement::Text(text));
+                } else {
+                    binding.title = (text, parse_u32(child, "hint-maxLines")?);
+                    has_title = true;
+                }
+            }
+            "image" => {
+                let image = parse_image(child)?;
+                match child.attribute("placement") {
+                    Some("appLogoOverride") => binding.icon = Some(image),
+                    Some("hero") => binding.hero = Some(image),
+                    _ => binding.elems.push(BindingInnerElement::Image(image)),
+                }
+            }
+            "group" => {
+                let mut subgroups = Vec::new();
+                for subgroup in child.children().filter(|node| node.has_tag_name("subgroup")) {
+                    let mut elems = Vec::new();
+                    for elem in subgroup.children().filter(roxmltree::Node::is_element) {
+                        match elem.tag_name().name() {
+                            "text" => elems.push(SubgroupElement::Text_(parse_inner_text(elem)?)),
+                            "image" => elems.push(SubgroupElement::Image_(parse_image(elem)?)),
+                            _ => {}
+                        }
+                    }
+                    subgroups.push(elems);
+                }
+                binding.elems.push(BindingInnerElement::Group(subgroups));
+            }
+            _ => {}
+        }
+    }
+    Ok(binding)
+}
+
+/// Reads the texts of a legacy binding in the order of their ids, and its image as the icon.
+fn parse_legacy_binding(node: roxmltree::Node, mut binding: BindingElem) -> Result<BindingElem, XmlError> {
+    let mut texts = Vec::new();
+    for child in node.children().filter(roxmltree::Node::is_element) {
+        match child.tag_name().name() {
+            "text" => texts.push((parse_u32(child, "id")?.unwrap_or(u32::MAX), child.text().unwrap_or_default().to_string())),
+            "image" if binding.icon.is_none() => binding.icon = Some(parse_image(child)?),
+            _ => {}
+        }
+    }
+    // Texts without an id keep their place after the numbered ones.
+    texts.sort_by_key(|(id, _)| *id);
+    let mut texts = texts.into_iter().map(|(_, text)| text);
+    binding.title = (texts.next().unwrap_or_default(), None);
+    binding.elems = texts.map(BindingInnerElement::Text).collect();
+    Ok(binding)
+}
+
+fn parse_image(node: roxmltree::Node) -> Result<Image, XmlError> {
+    Ok(Image {
+        src: required(node, "src")?.to_string(),
+        alt: node.attribute("alt").unwrap_or_default().to_string(),
+        hint_crop: parse_attr(node, "hint-crop")?.unwrap_or_default(),
+        hint_align: parse_attr(node, "hint-align")?.unwrap_or_default(),
+    })
+}
+
+fn parse_inner_text(node: roxmltree::Node) -> Result<InnerText, XmlError> {
+    // Older versions of this crate wrote the misspelled `hint-warp`.
+    let wrap = node.attribute("hint-wrap").or_else(|| node.attribute("hint-warp"));
+    Ok(InnerText {
+        text: node.text().unwrap_or_default().to_string(),
+        hint_style: parse_attr(node, "hint-style")?.unwrap_or_default(),
+        hint_warp: wrap.map(|value| value == "true"),
+        hint_max_lines: parse_u32(node, "hint-maxLines")?,
+        hint_min_lines: parse_u32(node, "hint-minLines")?,
+        hint_align: parse_attr(node, "hint-align")?.unwrap_or_default(),
+    })
+}
+
+fn parse_action(node: roxmltree::Node) -> Result<Action, XmlError> {
+    let mut action = Action {
+        content: required(node, "content")?.to_string(),
+        arguments: required(node, "arguments")?.to_string(),
+        image_uri: node.attribute("imageUri").map(str::to_string),
+        hint_input_id: node.attribute("hint-inputId").map(str::to_string),
+        hint_button_style: node.attribute("hint-buttonStyle").map(str::to_string),
+        hint_tool_tip: node.attribute("hint-toolTip").map(str::to_string),
+        ..Action::default()
+    };
+    match node.attribute("activationType") {
+        // Older versions of this crate wrote the context menu placement into activationType.
+        Some("contextMenu") => action.placement = ActionPlacement::ContextMenu,
+        Some(value) => action.activation_type = value.parse().map_err(|e: XmlError| XmlError::at(node, e.message))?,
+        None => {}
+    }
+    if node.attribute("placement") == Some("contextMenu") {
+        action.placement = ActionPlacement::ContextMenu;
+    }
+    Ok(action)
+}
+
+fn parse_input(node: roxmltree::Node) -> Result<Input, XmlError> {
+    let type_ = match required(node, "type")? {
+        "text" => InputType::Text(node.attribute("defaultInput").map(str::to_string)),
+        "selection" => {
+            let mut selections = Vec::new();
+            for selection in node.children().filter(|node| node.has_tag_name("selection")) {
+                // Older versions of this crate wrote the selection text as `title`.
+                let content = selection.attribute("content").or_else(|| selection.attribute("title")).unwrap_or_default();
+                selections.push((required(selection, "id")?.to_string(), content.to_string()));
+            }
+            let default = node.attribute("defaultInput").or_else(|| node.attribute("defaultSelectionBoxItemId"));
+            InputType::Selection(default.map(str::to_string), selections)
+        }
+        other => return Err(XmlError::at(node, format!("unknown input type {:?}", other))),
+    };
+    Ok(Input {
+        id: required(node, "id")?.to_string(),
+        type_,
+        place_holder_content: node.attribute("placeHolderContent").map(str::to_string),
+        title: node.attribute("title").map(str::to_string),
+    })
+}
+
+/// Declares the attribute value of every variant, giving the enum `attr()` and `FromStr`.
+macro_rules! attr_values {
+    ($ty:ident { $($variant:ident => $value:literal),* $(,)? }) => {
+        impl $ty {
+            /// The attribute value written to the payload.
+            pub fn attr(&self) -> &'static str {
+                match self {
+                    $($ty::$variant => $value,)*
+                }
+            }
+        }
+
+        impl FromStr for $ty {
+            type Err = XmlError;
+
+            fn from_str(s: &str) -> Result<Self, XmlError> {
+                match s {
+                    $($value => Ok($ty::$variant),)*
+                    _ => Err(XmlError::new(format!("unknown {} {:?}", stringify!($ty), s))),
+                }
+            }
+        }
+    };
+}
+
+/// Like `attr_values!`, for enums whose `Default` variant means leaving the attribute out.
+macro_rules! optional_attr_values {
+    ($ty:ident { $($variant:ident => $value:literal),* $(,)? }) => {
+        impl $ty {
+            /// The attribute value written to the payload, if any.
+            pub fn attr(&self) -> Option<&'static str> {
+                match self {
+                    $ty::Default => None,
+                    $($ty::$variant => Some($value),)*
+                }
+            }
+        }
+
+        impl FromStr for $ty {
+            type Err = XmlError;
+
+            fn from_str(s: &str) -> Result<Self, XmlError> {
+                match s {
+                    "" | "default" => Ok($ty::Default),
+                    $($value => Ok($ty::$variant),)*
+                    _ => Err(XmlError::new(format!("unknown {} {:?}", stringify!($ty), s))),
+                }
+            }
+        }
+    };
+}
+
+attr_values!(BindingTemplate {
+    ToastGeneric => "ToastGeneric",
+    ToastText01 => "ToastText01",
+    ToastText02 => "ToastText02",
+    ToastText03 => "ToastText03",
+    ToastText04 => "ToastText04",
+    ToastImageAndText01 => "ToastImageAndText01",
+    ToastImageAndText02 => "ToastImageAndText02",
+    ToastImageAndText03 => "ToastImageAndText03",
+    ToastImageAndText04 => "ToastImageAndText04",
+});
+
+optional_attr_values!(Duration { Short => "short", Long => "long" });
+
+optional_attr_values!(Scenario {
+    Alarm => "alarm",
+    Reminder => "reminder",
+    IncomingCall => "incomingCall",
+    Urgent => "urgent",
+});
+
+optional_attr_values!(HintCrop { Circle => "circle" });
+
+optional_attr_values!(TextStyle {
+    Caption => "caption",
+    CaptionSubtle => "captionSubtle",
+    Body => "body",
+    BodySubtle => "bodySubtle",
+    Base => "base",
+    BaseSubtle => "baseSubtle",
+    Subtitle => "subtitle",
+    SubtitleSubtle => "subtitleSubtle",
+    Title => "title",
+    TitleSubtle => "titleSubtle",
+    TitleNumeral => "titleNumeral",
+    Subheader => "subheader",
+    SubheaderSubtle => "subheaderSubtle",
+    SubheaderNumeral => "subheaderNumeral",
+    Header => "header",
+    HeaderSubtle => "headerSubtle",
+    HeaderNumeral => "headerNumeral",
+});
+
+optional_attr_values!(TextAlign {
+    Auto => "auto",
+    Left => "left",
+    Center => "center",
+    Right => "right",
+});
+
+optional_attr_values!(ImageAlign {
+    Stretch => "stretch",
+    Left => "left",
+    Center => "center",
+    Right => "right",
+});
+
+attr_values!(ActivationType {
+    Foreground => "foreground",
+    Background => "background",
+    Protocol => "protocol",
+});
+
+attr_values!(SoundSrc {
+    Default => "ms-winsoundevent:Notification.Default",
+    IM => "ms-winsoundevent:Notification.IM",
+    Mail => "ms-winsoundevent:Notification.Mail",
+    Reminder => "ms-winsoundevent:Notification.Reminder",
+    SMS => "ms-winsoundevent:Notification.SMS",
+    Alarm => "ms-winsoundevent:Notification.Looping.Alarm",
+    Alarm2 => "ms-winsoundevent:Notification.Looping.Alarm2",
+    Alarm3 => "ms-winsoundevent:Notification.Looping.Alarm3",
+    Alarm4 => "ms-winsoundevent:Notification.Looping.Alarm4",
+    Alarm5 => "ms-winsoundevent:Notification.Looping.Alarm5",
+    Alarm6 => "ms-winsoundevent:Notification.Looping.Alarm6",
+    Alarm7 => "ms-winsoundevent:Notification.Looping.Alarm7",
+    Alarm8 => "ms-winsoundevent:Notification.Looping.Alarm8",
+    Alarm9 => "ms-winsoundevent:Notification.Looping.Alarm9",
+    Alarm10 => "ms-winsoundevent:Notification.Looping.Alarm10",
+    Call => "ms-winsoundevent:Notification.Looping.Call",
+    Call2 => "ms-winsoundevent:Notification.Looping.Call2",
+    Call3 => "ms-winsoundevent:Notification.Looping.Call3",
+    Call4 => "ms-winsoundevent:Notification.Looping.Call4",
+    Call5 => "ms-winsoundevent:Notification.Looping.Call5",
+    Call6 => "ms-winsoundevent:Notification.Looping.Call6",
+    Call7 => "ms-winsoundevent:Notification.Looping.Call7",
+    Call8 => "ms-winsoundevent:Notification.Looping.Call8",
+    Call9 => "ms-winsoundevent:Notification.Looping.Call9",
+    Call10 => "ms-winsoundevent:Notification.Looping.Call10",
+});
+
+impl SoundSrc {
+    /// Only the alarm and call sounds can be looped.
+    pub fn is_looping(&self) -> bool {
+        self.attr().contains(".Looping.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::validate_xml;
+    use crate::tests::sample_toast;
+
+    fn binding_toast(elems: Vec<BindingInnerElement>) -> Toast {
+        Toast {
+            app_id: Toast::POWERSHELL_APP_ID.to_string(),
+            binding_elems: vec![BindingElem { title: ("Title".to_string(), None), elems, ..BindingElem::default() }],
+            ..Toast::default()
+        }
+    }
+
+    #[test]
+    fn test_hint_wrap() {
+        let text = InnerText { text: "Wrapped".to_string(), hint_warp: Some(true), ..InnerText::default() };
+        let toast = binding_toast(vec![BindingInnerElement::Group(vec![vec![SubgroupElement::Text_(text)]])]);
+        let xml = toast.to_xml();
+        assert!(xml.contains("<text hint-wrap=\"true\">Wrapped</text>"));
+        assert_eq!(validate_xml(&xml), Vec::new());
+        assert_eq!(Toast::from_xml(Toast::POWERSHELL_APP_ID, &xml.replace("hint-wrap", "hint-warp")), Ok(toast));
+    }
+
+    fn selection_toast(default: Option<&str>) -> Toast {
+        let selections = vec![("1h".to_string(), "In an hour".to_string()), ("1d".to_string(), "Tomorrow".to_string())];
+        let input = Input { id: "snooze".to_string(), type_: InputType::Selection(default.map(str::to_string), selections), ..Input::default() };
+        Toast { actions: vec![ActionsElem::Input_(input)], ..binding_toast(Vec::new()) }
+    }
+
+    #[test]
+    fn test_selection_content() {
+        let toast = selection_toast(None);
+        let xml = toast.to_xml();
+        assert!(xml.contains("<selection id=\"1h\" content=\"In an hour\"/><selection id=\"1d\" content=\"Tomorrow\"/>"));
+        assert_eq!(validate_xml(&xml), Vec::new());
+        assert_eq!(Toast::from_xml(Toast::POWERSHELL_APP_ID, &xml.replace(" content=", " title=")), Ok(toast));
+    }
+
+    #[test]
+    fn test_selection_default_input() {
+        let toast = selection_toast(Some("1d"));
+        let xml = toast.to_xml();
+        assert!(xml.contains("<input id=\"snooze\" type=\"selection\" defaultInput=\"1d\">"));
+        assert_eq!(validate_xml(&xml), Vec::new());
+        assert_eq!(Toast::from_xml(Toast::POWERSHELL_APP_ID, &xml.replace("defaultInput", "defaultSelectionBoxItemId")), Ok(toast));
+    }
+
+    #[test]
+    fn test_context_menu_placement() {
+        let action = Action {
+            content: "Mute".to_string(),
+            arguments: "mute".to_string(),
+            activation_type: ActivationType::Background,
+            placement: ActionPlacement::ContextMenu,
+            ..Action::default()
+        };
+        let toast = Toast { actions: vec![ActionsElem::Action_(action)], ..binding_toast(Vec::new()) };
+        let xml = toast.to_xml();
+        assert!(xml.contains("<action content=\"Mute\" arguments=\"mute\" activationType=\"background\" placement=\"contextMenu\"/>"));
+        assert_eq!(validate_xml(&xml), Vec::new());
+        // Older versions wrote the placement over the activation type, which was lost.
+        let old = xml.replace("activationType=\"background\" placement=\"contextMenu\"", "activationType=\"contextMenu\"");
+        let ActionsElem::Action_(action) = &Toast::from_xml(Toast::POWERSHELL_APP_ID, &old).unwrap().actions[0] else { panic!("expected an action") };
+        assert_eq!((&action.activation_type, &action.placement), (&ActivationType::Foreground, &ActionPlacement::ContextMenu));
+        assert_eq!(Toast::from_xml(Toast::POWERSHELL_APP_ID, &xml), Ok(toast));
+    }
+
+    #[test]
+    fn test_audio_loop() {
+        let audio = |loop_, src| Toast { audio: Some((loop_, false, src)), ..binding_toast(Vec::new()) }.to_xml();
+        assert!(audio(true, SoundSrc::Alarm2).contains("<audio loop=\"true\" silent=\"false\" src=\"ms-winsoundevent:Notification.Looping.Alarm2\"/>"));
+        assert!(audio(false, SoundSrc::Call).contains("<audio loop=\"false\" silent=\"false\" src=\"ms-winsoundevent:Notification.Looping.Call\"/>"));
+        assert!(audio(true, SoundSrc::Mail).contains("<audio loop=\"false\" silent=\"false\" src=\"ms-winsoundevent:Notification.Mail\"/>"));
+        assert_eq!(validate_xml(&audio(true, SoundSrc::Alarm2)), Vec::new());
+    }
+
+    #[test]
+    fn test_xml_round_trip() {
+        let toast = sample_toast();
+        let xml = toast.to_xml();
+        assert!(xml.starts_with("<toast useButtonStyle=\"true\" scenario=\"reminder\"><visual><binding template=\"ToastGeneric\" lang=\"en-US\">"));
+        assert!(xml.contains("<text>Build &lt;finished&gt;</text>"));
+        assert!(xml.contains("<action content=\"Settings\" arguments=\"settings\" activationType=\"foreground\" placement=\"contextMenu\"/>"));
+        assert_eq!(Toast::from_xml(Toast::POWERSHELL_APP_ID, &xml), Ok(toast.clone()));
+
+        let header = Header { id: "ci".to_string(), title: "CI".to_string(), arguments: "open=ci".to_string(), ..Header::default() };
+        let toast = Toast { header: Some(header), ..toast };
+        let xml = toast.to_xml();
+        assert!(xml.contains("scenario=\"reminder\"><header id=\"ci\" title=\"CI\" arguments=\"open=ci\" activationType=\"foreground\"/><visual>"));
+        assert_eq!(Toast::from_xml(Toast::POWERSHELL_APP_ID, &xml), Ok(toast));
+    }
+}